@@ -0,0 +1 @@
+ALTER TABLE config ADD COLUMN "web_password" TEXT;
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS "history" (
+	"id"	INTEGER PRIMARY KEY AUTOINCREMENT,
+	"track_id"	INTEGER NOT NULL,
+	"title"	TEXT NOT NULL,
+	"album"	TEXT,
+	"artist"	TEXT,
+	"played_at"	INTEGER NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE config ADD COLUMN "replaygain_mode" TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE config DROP COLUMN "profile";
+DROP TABLE IF EXISTS "profiles";
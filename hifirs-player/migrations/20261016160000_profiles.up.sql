@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS "profiles" (
+	"name"	TEXT PRIMARY KEY NOT NULL,
+	"username"	TEXT,
+	"user_token"	TEXT
+);
+ALTER TABLE config ADD COLUMN "profile" TEXT;
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS "playback_position" (
+	"id"	INTEGER PRIMARY KEY CHECK ("id" = 1),
+	"list_type"	TEXT NOT NULL,
+	"list_id"	TEXT,
+	"track_id"	INTEGER NOT NULL,
+	"track_position"	INTEGER NOT NULL,
+	"position_seconds"	INTEGER NOT NULL
+);
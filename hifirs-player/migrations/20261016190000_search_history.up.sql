@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS "search_history" (
+	"id"	INTEGER PRIMARY KEY AUTOINCREMENT,
+	"search_query"	TEXT NOT NULL UNIQUE
+);
@@ -0,0 +1,2 @@
+ALTER TABLE config DROP COLUMN "audio_device";
+ALTER TABLE config DROP COLUMN "audio_sink";
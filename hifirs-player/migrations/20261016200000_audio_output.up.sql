@@ -0,0 +1,2 @@
+ALTER TABLE config ADD COLUMN "audio_sink" TEXT;
+ALTER TABLE config ADD COLUMN "audio_device" TEXT;
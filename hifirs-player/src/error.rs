@@ -16,6 +16,31 @@ pub enum Error {
     Seek,
     #[snafu(display("sorry, could not resume previous session"))]
     Resume,
+    #[snafu(display("track {track_id} was not found"))]
+    TrackNotFound {
+        track_id: i32,
+    },
+    #[snafu(display("there is no track at position {position} in the queue"))]
+    InvalidQueuePosition {
+        position: u32,
+    },
+    #[snafu(display("the playing track can't be removed from the queue"))]
+    RemovePlayingTrack,
+    #[snafu(display("nothing is queued"))]
+    EmptyQueue,
+    #[snafu(display("failed to change the player state to {state}"))]
+    StateChange {
+        state: String,
+    },
+    #[snafu(display("the sleep timer can be at most {max} minutes, not {minutes}"))]
+    InvalidSleepTimer {
+        minutes: u64,
+        max: u64,
+    },
+    #[snafu(display("track {track_id} is not in the queue"))]
+    TrackNotInQueue {
+        track_id: u32,
+    },
     #[snafu(display("{message}"))]
     GStreamer {
         message: String,
@@ -24,6 +49,10 @@ pub enum Error {
     Client {
         message: String,
     },
+    #[snafu(display("{message}"))]
+    AudioOutput {
+        message: String,
+    },
     Notification,
     App,
 }
@@ -1,4 +1,4 @@
-use cached::proc_macro::cached;
+use cached::{proc_macro::cached, Cached};
 use error::Error;
 use flume::{Receiver, Sender};
 use futures::prelude::*;
@@ -7,28 +7,39 @@ use gst::{
     State as GstState, StateChangeSuccess, Structure,
 };
 use gstreamer as gst;
-use hifirs_qobuz_api::client::{self, UrlType};
+use hifirs_qobuz_api::{
+    client::{self, album::FeaturedType, api::ProxySettings, UrlType},
+    redact::Redacted,
+};
 use notification::{BroadcastReceiver, BroadcastSender, Notification};
 use once_cell::sync::{Lazy, OnceCell};
 use queue::{
     controls::{PlayerState, SafePlayerState},
-    TrackListValue,
+    QueueDuration, TrackListType, TrackListValue,
+};
+use service::{
+    Album, Artist, Favorites, Genre, Label, Playlist, ReleaseSort, ReplayGainMode, SearchResults,
+    Track,
 };
-use service::{Album, Artist, Favorites, Playlist, SearchResults, Track};
 use std::{
+    ops::{Deref, DerefMut},
     str::FromStr,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
     },
-    time::Duration,
+    time::{Duration, Instant},
+};
+use tokio::{
+    select,
+    sync::{RwLock, RwLockWriteGuard},
 };
-use tokio::{select, sync::RwLock};
 use tracing::{debug, instrument};
 
 pub mod error;
 pub mod mpris;
 pub mod notification;
+pub mod output;
 pub mod qobuz;
 pub mod queue;
 pub mod service;
@@ -73,6 +84,19 @@ static PLAYBIN: Lazy<Element> = Lazy::new(|| {
             element.set_property("compress", true);
             element.set_property("retries", 10);
             element.set_property("timeout", 30_u32);
+
+            let location = element.property::<Option<String>>("location");
+            let proxy = PROXY.as_ref().filter(|proxy| {
+                !location
+                    .as_deref()
+                    .is_some_and(|location| proxy.bypasses(location))
+            });
+
+            if let Some(proxy) = proxy {
+                element.set_property("proxy", &proxy.url);
+                element.set_property("proxy-id", proxy.username.as_deref());
+                element.set_property("proxy-pw", proxy.password.as_ref().map(|p| p.0.as_str()));
+            }
             element.set_property(
                 "extra-headers",
                 Structure::from_str("a-structure, DNT=1, Pragma=no-cache, Cache-Control=no-cache")
@@ -83,6 +107,7 @@ static PLAYBIN: Lazy<Element> = Lazy::new(|| {
         None
     });
 
+    playbin.set_property("audio-filter", &*REPLAYGAIN);
     playbin.add_property_deep_notify_watch(Some("caps"), true);
 
     // Connects to the `about-to-finish` signal so the player
@@ -100,6 +125,20 @@ static PLAYBIN: Lazy<Element> = Lazy::new(|| {
     playbin
 });
 
+// Streams go through the same proxy as the API requests, except for the
+// hosts in its `no_proxy` list.
+static PROXY: Lazy<Option<ProxySettings>> = Lazy::new(client::api::proxy_settings);
+
+// Applies the ReplayGain adjustment separately from the user volume.
+static REPLAYGAIN: Lazy<Element> = Lazy::new(|| {
+    gst::init().expect("error initializing gstreamer");
+
+    gst::ElementFactory::make("volume")
+        .name("replaygain")
+        .build()
+        .expect("error building volume element")
+});
+
 struct Broadcast {
     tx: BroadcastSender,
     rx: BroadcastReceiver,
@@ -122,15 +161,36 @@ static ABOUT_TO_FINISH: Lazy<AboutToFinish> = Lazy::new(|| {
 
     AboutToFinish { tx, rx }
 });
+
+#[derive(Debug, Clone, Copy)]
+struct SleepTimer {
+    deadline: Instant,
+    finish_track: bool,
+}
+
+static SLEEP_TIMER: Mutex<Option<SleepTimer>> = Mutex::new(None);
 static IS_BUFFERING: AtomicBool = AtomicBool::new(false);
+/// Last buffering percentage that was broadcast, to skip repeated messages.
+static BUFFERING_PERCENT: AtomicU32 = AtomicU32::new(100);
 static IS_LIVE: AtomicBool = AtomicBool::new(false);
+static TRACKS_PLAYED: AtomicU64 = AtomicU64::new(0);
+/// Sample rate the audio sink plays at, 0 until it is known.
+static OUTPUT_RATE: AtomicU32 = AtomicU32::new(0);
+static BIT_PERFECT: AtomicBool = AtomicBool::new(false);
 static QUEUE: OnceCell<SafePlayerState> = OnceCell::new();
+/// Tracklist that was last broadcast, to skip sending the same list twice.
+static LAST_TRACK_LIST: Mutex<Option<TrackListValue>> = Mutex::new(None);
+/// Seconds of playback between saving the position for `resume`.
+const SAVE_POSITION_SECONDS: u64 = 5;
+/// How often the position is checked and sent to the interfaces, unless configured.
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+static REFRESH_INTERVAL: OnceCell<Duration> = OnceCell::new();
 static USER_AGENTS: &[&str] = &[
     "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/114.0.0.0 Safari/537.36",
     "Mozilla/5.0 (Macintosh; Intel Mac OS X 13_4) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/114.0.0.0 Safari/537.36"
 ];
 
-#[instrument]
+#[instrument(skip(username, password), fields(username = ?username.map(Redacted)))]
 pub async fn init(username: Option<&str>, password: Option<&str>) -> Result<()> {
     let state = Arc::new(RwLock::new(PlayerState::new(username, password).await));
     let version = gstreamer::version();
@@ -155,7 +215,9 @@ pub async fn stop() -> Result<()> {
 #[instrument]
 /// Sets the player to a specific state.
 pub async fn set_player_state(state: gst::State) -> Result<()> {
-    let ret = PLAYBIN.set_state(state)?;
+    let ret = PLAYBIN.set_state(state).map_err(|_| Error::StateChange {
+        state: format!("{state:?}").to_lowercase(),
+    })?;
 
     match ret {
         StateChangeSuccess::Success => {
@@ -180,16 +242,70 @@ pub async fn set_player_state(state: gst::State) -> Result<()> {
 
     Ok(())
 }
+
+/// Remember the list as the last one broadcast, returns false if it was already.
+fn track_list_changed(list: &TrackListValue) -> bool {
+    let mut last = LAST_TRACK_LIST.lock().unwrap();
+
+    if last.as_ref() == Some(list) {
+        return false;
+    }
+
+    *last = Some(list.clone());
+    true
+}
+
 async fn broadcast_track_list<'a>(list: &TrackListValue) -> Result<()> {
-    BROADCAST_CHANNELS
-        .tx
-        .broadcast(Notification::CurrentTrackList { list: list.clone() })
-        .await?;
+    if track_list_changed(list) {
+        BROADCAST_CHANNELS
+            .tx
+            .broadcast(Notification::CurrentTrackList { list: list.clone() })
+            .await?;
+    }
     Ok(())
 }
+
+/// Write access to the queue for actions that edit it.
+///
+/// The tracklist is broadcast when the guard is dropped, if it changed, so the
+/// interfaces stay in sync without every action having to send it.
+struct QueueWriteGuard(RwLockWriteGuard<'static, PlayerState>);
+
+async fn write_queue() -> QueueWriteGuard {
+    QueueWriteGuard(QUEUE.get().unwrap().write().await)
+}
+
+impl Deref for QueueWriteGuard {
+    type Target = PlayerState;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for QueueWriteGuard {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Drop for QueueWriteGuard {
+    fn drop(&mut self) {
+        let list = self.0.track_list();
+
+        if track_list_changed(&list) {
+            _ = BROADCAST_CHANNELS
+                .tx
+                .try_broadcast(Notification::CurrentTrackList { list });
+        }
+    }
+}
+
 #[instrument]
 /// Toggle play and pause.
 pub async fn play_pause() -> Result<()> {
+    ensure_queued().await?;
+
     if is_playing() {
         pause().await?;
     } else if is_paused() || is_ready() {
@@ -202,15 +318,75 @@ pub async fn play_pause() -> Result<()> {
 #[instrument]
 /// Play the player.
 pub async fn play() -> Result<()> {
+    ensure_queued().await?;
+
     if let Some(queue) = QUEUE.get() {
         let mut state = queue.write().await;
         state.set_target_status(GstState::Playing);
     }
 
+    // A paused stream is already open, only a stopped one opens the url again.
+    if !is_paused() {
+        refresh_expired_url().await;
+    }
+
     set_player_state(gst::State::Playing).await?;
     Ok(())
 }
 
+/// Point the player at a new url for the current track when its url has expired,
+/// such as after being stopped for a long time.
+async fn refresh_expired_url() {
+    if let Some(queue) = QUEUE.get() {
+        if let Some(track_url) = queue.write().await.refresh_track_url().await {
+            PLAYBIN.set_property("uri", track_url);
+        }
+    }
+}
+
+/// Fails with `Error::EmptyQueue` when there is nothing to play.
+async fn ensure_queued() -> Result<()> {
+    if let Some(queue) = QUEUE.get() {
+        check_queued(&queue.read().await.track_list())?;
+    }
+
+    Ok(())
+}
+
+fn check_queued(track_list: &TrackListValue) -> Result<()> {
+    if track_list.total() == 0 {
+        Err(Error::EmptyQueue)
+    } else {
+        Ok(())
+    }
+}
+
+/// The position `next` skips to, or `None` when nothing or the last track is playing.
+fn next_position(track_list: &TrackListValue, current_position: u32) -> Option<u32> {
+    if track_list.current_track().is_none() {
+        debug!("nothing is playing, ignoring next");
+        return None;
+    }
+
+    if current_position >= track_list.total() {
+        debug!("playing the last track, ignoring next");
+        return None;
+    }
+
+    Some(current_position + 1)
+}
+
+/// The position `previous` skips to, or `None` when nothing is playing.
+fn previous_position(track_list: &TrackListValue, current_position: u32) -> Option<u32> {
+    if track_list.current_track().is_none() {
+        debug!("nothing is playing, ignoring previous");
+        return None;
+    }
+
+    // On the first track, previous starts it over.
+    Some(current_position.saturating_sub(1).max(1))
+}
+
 #[instrument]
 /// Pause the player.
 pub async fn pause() -> Result<()> {
@@ -258,8 +434,9 @@ pub fn volume() -> f64 {
     PLAYBIN.property::<f64>("volume")
 }
 #[instrument]
-/// Set volume
+/// Set volume. Ignored in bit perfect mode, where the volume stays at 100%.
 pub fn set_volume(value: f64) {
+    let value = output_volume(value, is_bit_perfect());
     PLAYBIN.set_property("volume", value);
 
     tokio::task::spawn(async move {
@@ -274,7 +451,7 @@ pub fn set_volume(value: f64) {
 pub async fn seek(time: ClockTime, flags: Option<SeekFlags>) -> Result<()> {
     let flags = flags.unwrap_or(SeekFlags::FLUSH | SeekFlags::TRICKMODE_KEY_UNITS);
 
-    PLAYBIN.seek_simple(flags, time)?;
+    PLAYBIN.seek_simple(flags, time).map_err(|_| Error::Seek)?;
     Ok(())
 }
 
@@ -316,10 +493,14 @@ pub async fn jump_backward() -> Result<()> {
 #[instrument]
 /// Skip to a specific track in the playlist.
 pub async fn skip(new_position: u32, force: bool) -> Result<()> {
-    let mut state = QUEUE.get().unwrap().write().await;
+    let mut state = write_queue().await;
     let current_position = state.current_track_position();
     let total_tracks = state.track_list().total();
 
+    if total_tracks == 0 {
+        return Err(Error::EmptyQueue);
+    }
+
     // Typical previous skip functionality where if,
     // the track is greater than 1 second into playing,
     // then it goes to the beginning. If triggered again
@@ -343,15 +524,20 @@ pub async fn skip(new_position: u32, force: bool) -> Result<()> {
         }
     }
 
-    ready().await?;
+    if !state.has_position(new_position) {
+        return Err(Error::InvalidQueuePosition {
+            position: new_position,
+        });
+    }
 
     if let Some(next_track_to_play) = state.skip_track(new_position).await {
-        let list = state.track_list();
         let target_status = state.target_status();
 
         drop(state);
 
-        broadcast_track_list(&list).await?;
+        // Only stop the playing track once the next one is known to be playable.
+        ready().await?;
+
         BROADCAST_CHANNELS
             .tx
             .broadcast(Notification::Position {
@@ -363,27 +549,100 @@ pub async fn skip(new_position: u32, force: bool) -> Result<()> {
 
         PLAYBIN.set_property("uri", next_track_to_play);
         set_player_state(target_status).await?;
+
+        Ok(())
+    } else {
+        Err(Error::TrackURL)
+    }
+}
+
+/// Skip to the track with the given id, if it is in the queue.
+pub async fn skip_to_track_id(track_id: u32) -> Result<()> {
+    let index = QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .track_list()
+        .track_index(track_id);
+
+    match index {
+        Some(index) => skip(index, true).await,
+        None => Err(Error::TrackNotInQueue { track_id }),
     }
+}
+
+#[instrument]
+/// Insert a track right after the one that is playing.
+pub async fn add_next(track_id: i32) -> Result<()> {
+    let mut state = write_queue().await;
+
+    state
+        .add_next(track_id)
+        .await
+        .ok_or(Error::TrackNotFound { track_id })
+}
+
+#[instrument]
+/// Add a track to the end of the queue.
+pub async fn add_to_queue(track_id: i32) -> Result<()> {
+    let mut state = write_queue().await;
+
+    state
+        .add_to_queue(track_id)
+        .await
+        .ok_or(Error::TrackNotFound { track_id })
+}
+
+#[instrument]
+/// Remove the track at the given position from the queue.
+pub async fn remove_from_queue(position: u32) -> Result<()> {
+    write_queue().await.remove_track(position)
+}
+
+#[instrument]
+/// Move a track in the queue from one position to another.
+pub async fn move_track(from: u32, to: u32) -> Result<()> {
+    write_queue().await.move_track(from, to)
+}
+
+#[instrument]
+/// Stop playback and empty the queue.
+pub async fn clear_queue() -> Result<()> {
+    stop().await?;
+
+    write_queue().await.clear_queue();
 
     Ok(())
 }
 
+#[instrument]
+/// Remove the played tracks from the queue, keeping the playing and upcoming ones.
+pub async fn clear_played() {
+    let removed = write_queue().await.clear_played();
+    debug!("removed {removed} played tracks from the queue");
+}
+
 pub async fn next() -> Result<()> {
     let state = QUEUE.get().unwrap().read().await;
-
-    let current_position = state.current_track_position();
+    let position = next_position(&state.track_list(), state.current_track_position());
     drop(state);
-    skip(current_position + 1, true).await?;
+
+    if let Some(position) = position {
+        skip(position, true).await?;
+    }
 
     Ok(())
 }
 
 pub async fn previous() -> Result<()> {
     let state = QUEUE.get().unwrap().read().await;
-
-    let current_position = state.current_track_position();
+    let position = previous_position(&state.track_list(), state.current_track_position());
     drop(state);
-    skip(current_position - 1, false).await?;
+
+    if let Some(position) = position {
+        skip(position, false).await?;
+    }
 
     Ok(())
 }
@@ -393,20 +652,17 @@ pub async fn previous() -> Result<()> {
 pub async fn play_track(track_id: i32) -> Result<()> {
     ready().await?;
 
-    let mut state = QUEUE.get().unwrap().write().await;
+    let mut state = write_queue().await;
 
     if let Some(track_url) = state.play_track(track_id).await {
-        let list = state.track_list();
-        broadcast_track_list(&list).await?;
-
         drop(state);
 
         PLAYBIN.set_property("uri", Some(track_url.as_str()));
 
-        play().await?;
+        play().await
+    } else {
+        Err(Error::TrackNotFound { track_id })
     }
-
-    Ok(())
 }
 
 #[instrument]
@@ -414,40 +670,114 @@ pub async fn play_track(track_id: i32) -> Result<()> {
 pub async fn play_album(album_id: &str) -> Result<()> {
     ready().await?;
 
-    let mut state = QUEUE.get().unwrap().write().await;
+    let mut state = write_queue().await;
 
     if let Some(track_url) = state.play_album(album_id).await {
-        let list = state.track_list();
-        broadcast_track_list(&list).await?;
-
         drop(state);
 
         PLAYBIN.set_property("uri", Some(track_url));
 
-        play().await?;
+        play().await
+    } else {
+        Err(Error::TrackURL)
     }
-
-    Ok(())
 }
 #[instrument]
 /// Plays all tracks in a playlist.
 pub async fn play_playlist(playlist_id: i64) -> Result<()> {
     ready().await?;
 
-    let mut state = QUEUE.get().unwrap().write().await;
+    let mut state = write_queue().await;
     if let Some(track_url) = state.play_playlist(playlist_id).await {
-        let list = state.track_list();
-        broadcast_track_list(&list).await?;
+        drop(state);
+
+        PLAYBIN.set_property("uri", Some(track_url.as_str()));
 
+        play().await
+    } else {
+        Err(Error::TrackURL)
+    }
+}
+#[instrument]
+/// Plays the most popular tracks of an artist.
+pub async fn play_artist(artist_id: i32) -> Result<()> {
+    ready().await?;
+
+    let mut state = write_queue().await;
+    if let Some(track_url) = state.play_artist(artist_id).await {
         drop(state);
 
         PLAYBIN.set_property("uri", Some(track_url.as_str()));
 
-        play().await?;
+        play().await
+    } else {
+        Err(Error::TrackURL)
     }
+}
+/// Save the playing tracklist, track and position so `resume` can continue from there.
+async fn save_playback_position(position: ClockTime) {
+    let state = QUEUE.get().unwrap().read().await;
 
-    Ok(())
+    let Some(track) = state.current_track() else {
+        return;
+    };
+
+    let list_type = state.list_type();
+    let list_id = match list_type {
+        TrackListType::Album => state.album().map(|album| album.id.clone()),
+        TrackListType::Playlist => state.playlist().map(|playlist| playlist.id.to_string()),
+        TrackListType::Artist => state.artist().map(|artist| artist.id.to_string()),
+        TrackListType::Track | TrackListType::Unknown => None,
+    };
+
+    let playback_position = sql::db::PlaybackPosition {
+        list_type: list_type.to_string(),
+        list_id,
+        track_id: track.id as i64,
+        track_position: track.position as i64,
+        position_seconds: position.seconds() as i64,
+    };
+
+    drop(state);
+
+    sql::db::set_playback_position(&playback_position).await;
 }
+
+#[instrument]
+/// Continue playing where playback was last saved, including the position in the track.
+pub async fn resume() -> Result<()> {
+    let saved = sql::db::playback_position().await.ok_or(Error::Resume)?;
+
+    match (saved.list_type.as_str(), saved.list_id.as_deref()) {
+        ("album", Some(album_id)) => play_album(album_id).await?,
+        ("playlist", Some(playlist_id)) => {
+            play_playlist(playlist_id.parse().map_err(|_| Error::Resume)?).await?
+        }
+        ("artist", Some(artist_id)) => {
+            play_artist(artist_id.parse().map_err(|_| Error::Resume)?).await?
+        }
+        _ => play_track(saved.track_id as i32).await?,
+    }
+
+    let track_position = saved.track_position as u32;
+    let current_position = QUEUE.get().unwrap().read().await.current_track_position();
+
+    if current_position != track_position {
+        skip(track_position, true).await?;
+    }
+
+    // The stream can only be seeked once it has started playing.
+    for _ in 0..100 {
+        if is_playing() {
+            return seek(ClockTime::from_seconds(saved.position_seconds as u64), None).await;
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    Err(Error::Resume)
+}
+
 #[instrument]
 /// Play an item from Qobuz web uri
 pub async fn play_uri(uri: &str) -> Result<()> {
@@ -476,21 +806,113 @@ pub async fn play_uri(uri: &str) -> Result<()> {
 /// In response to the about-to-finish signal,
 /// prepare the next track by downloading the stream url.
 async fn prep_next_track() -> Result<()> {
-    let mut state = QUEUE.get().unwrap().write().await;
+    if sleep_timer_expired() {
+        debug!("sleep timer expired, not preparing the next track");
+        return Ok(());
+    }
 
-    let total_tracks = state.track_list().total();
-    let current_position = state.current_track_position();
+    let mut state = QUEUE.get().unwrap().write().await;
 
-    if total_tracks == current_position {
-        debug!("no more tracks left");
-    } else if let Some(next_track_url) = state.skip_track(current_position + 1).await {
+    if let Some(next_track_url) = state.advance().await {
         drop(state);
 
         PLAYBIN.set_property("uri", next_track_url);
+    } else {
+        debug!("no more tracks left");
     }
 
     Ok(())
 }
+/// Longest sleep timer that can be set, a day.
+pub const MAX_SLEEP_TIMER_MINUTES: u64 = 24 * 60;
+
+#[instrument]
+/// Pause playback after `minutes`. When `finish_track` is set, the track
+/// playing when the timer runs out is allowed to finish first.
+pub async fn set_sleep_timer(minutes: u64, finish_track: bool) -> Result<()> {
+    let duration = sleep_timer_duration(minutes)?;
+
+    *SLEEP_TIMER.lock().unwrap() = Some(SleepTimer {
+        deadline: Instant::now() + duration,
+        finish_track,
+    });
+
+    broadcast_sleep_timer(Some(duration.as_secs())).await;
+
+    Ok(())
+}
+fn sleep_timer_duration(minutes: u64) -> Result<Duration> {
+    if minutes > MAX_SLEEP_TIMER_MINUTES {
+        return Err(Error::InvalidSleepTimer {
+            minutes,
+            max: MAX_SLEEP_TIMER_MINUTES,
+        });
+    }
+
+    Ok(Duration::from_secs(minutes * 60))
+}
+#[instrument]
+/// Cancel the sleep timer, if one is set.
+pub async fn cancel_sleep_timer() {
+    if SLEEP_TIMER.lock().unwrap().take().is_some() {
+        broadcast_sleep_timer(None).await;
+    }
+}
+#[instrument]
+/// Returns the time left on the sleep timer, if one is set.
+pub fn sleep_timer_remaining() -> Option<Duration> {
+    SLEEP_TIMER
+        .lock()
+        .unwrap()
+        .map(|timer| timer.deadline.saturating_duration_since(Instant::now()))
+}
+async fn cancel_sleep_timer_if_expired() {
+    if sleep_timer_expired() {
+        cancel_sleep_timer().await;
+    }
+}
+fn sleep_timer_expired() -> bool {
+    sleep_timer_remaining().is_some_and(|remaining| remaining.is_zero())
+}
+async fn broadcast_sleep_timer(remaining_seconds: Option<u64>) {
+    _ = BROADCAST_CHANNELS
+        .tx
+        .broadcast(Notification::SleepTimer { remaining_seconds })
+        .await;
+}
+/// Adjust the volume of the track that just started to the configured ReplayGain mode.
+async fn apply_replay_gain(track: &Track) {
+    let mode = sql::db::replaygain_mode().await;
+    let volume = replay_gain_volume(track, mode, is_bit_perfect());
+
+    debug!("replaygain {mode}, volume {volume}");
+    REPLAYGAIN.set_property("volume", volume);
+}
+/// The ReplayGain volume for `track`, left at unity in bit perfect mode.
+fn replay_gain_volume(track: &Track, mode: ReplayGainMode, bit_perfect: bool) -> f64 {
+    if bit_perfect {
+        return 1.0;
+    }
+
+    // The volume element does not go above 10x.
+    track.replay_gain.volume(mode).min(10.0)
+}
+/// The user volume to play at, always unity in bit perfect mode.
+fn output_volume(requested: f64, bit_perfect: bool) -> f64 {
+    if bit_perfect {
+        1.0
+    } else {
+        requested
+    }
+}
+#[instrument]
+/// Broadcast an error so connected clients can display it.
+pub async fn broadcast_error(error: Error) {
+    _ = BROADCAST_CHANNELS
+        .tx
+        .broadcast(Notification::Error { error })
+        .await;
+}
 #[instrument]
 /// Get a notification channel receiver
 pub fn notify_receiver() -> BroadcastReceiver {
@@ -501,11 +923,30 @@ pub fn notify_receiver() -> BroadcastReceiver {
 pub async fn current_tracklist() -> TrackListValue {
     QUEUE.get().unwrap().read().await.track_list()
 }
+/// Total and remaining duration of the queue, from the current position.
+pub async fn queue_duration() -> QueueDuration {
+    let position_seconds = position().map_or(0, |position| position.seconds() as u32);
+
+    QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .queue_duration(position_seconds)
+}
 #[instrument]
 /// Returns the current track loaded in the player.
 pub async fn current_track() -> Option<Track> {
     QUEUE.get().unwrap().read().await.current_track().cloned()
 }
+/// Number of tracks started since startup.
+pub fn tracks_played() -> u64 {
+    TRACKS_PLAYED.load(Ordering::Relaxed)
+}
+/// Number of requests sent to the Qobuz API since startup, and how many of them failed.
+pub fn api_request_counts() -> (u64, u64) {
+    client::api::request_counts()
+}
 #[instrument]
 /// Returns true if the player is currently buffering data.
 pub fn is_buffering() -> bool {
@@ -514,12 +955,31 @@ pub fn is_buffering() -> bool {
 #[instrument]
 /// Search the service.
 pub async fn search(query: &str) -> SearchResults {
+    search_page(query, 20, 0).await
+}
+
+#[instrument]
+/// Search the service for a specific page of results.
+pub async fn search_page(query: &str, limit: u32, offset: u32) -> SearchResults {
+    QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .search_all(query, limit, offset)
+        .await
+        .unwrap_or_default()
+}
+
+#[instrument]
+/// Search the service for tracks only.
+pub async fn search_tracks_page(query: &str, limit: u32, offset: u32) -> SearchResults {
     QUEUE
         .get()
         .unwrap()
         .read()
         .await
-        .search_all(query)
+        .search_tracks(query, limit, offset)
         .await
         .unwrap_or_default()
 }
@@ -551,6 +1011,12 @@ pub async fn artist(artist_id: i32) -> Artist {
         .unwrap_or_default()
 }
 
+#[instrument]
+/// Get track
+pub async fn track(track_id: i32) -> Option<Track> {
+    QUEUE.get().unwrap().read().await.get_track(track_id).await
+}
+
 #[instrument]
 /// Get similar artists
 pub async fn similar_artists(artist_id: i32) -> Vec<Artist> {
@@ -566,27 +1032,79 @@ pub async fn similar_artists(artist_id: i32) -> Vec<Artist> {
 #[instrument]
 /// Get album
 pub async fn album(id: &str) -> Album {
+    find_album(id).await.unwrap()
+}
+
+#[instrument]
+/// Get album, or `None` when it does not exist.
+pub async fn find_album(id: &str) -> Option<Album> {
+    QUEUE.get().unwrap().read().await.get_album(id).await
+}
+
+#[instrument]
+/// Get the album a track belongs to, with all of its tracks.
+pub async fn album_for_track(track_id: i32) -> Option<Album> {
+    let state = QUEUE.get().unwrap().read().await;
+    let album_id = state.get_track(track_id).await?.album?.id;
+
+    state.get_album(&album_id).await
+}
+
+#[instrument]
+/// Get suggested albums
+pub async fn suggested_albums(album_id: &str) -> Vec<Album> {
     QUEUE
         .get()
         .unwrap()
         .read()
         .await
-        .get_album(id)
+        .get_suggested_albums(album_id)
         .await
         .unwrap()
 }
 
 #[instrument]
-/// Get suggested albums
-pub async fn suggested_albums(album_id: &str) -> Vec<Album> {
+#[cached(size = 10, time = 600, option = true)]
+/// Get a label and its releases. Labels that fail to load are not cached.
+pub async fn label(label_id: u32) -> Option<Label> {
+    QUEUE.get().unwrap().read().await.get_label(label_id).await
+}
+
+#[instrument]
+#[cached(size = 10, time = 600, result = true)]
+/// Get the top level genres, or the children of `parent_id`. Only successful
+/// responses are cached.
+pub async fn genres(parent_id: Option<u32>) -> Result<Vec<Genre>> {
     QUEUE
         .get()
         .unwrap()
         .read()
         .await
-        .get_suggested_albums(album_id)
+        .get_genres(parent_id)
         .await
+}
+
+#[instrument]
+#[cached(size = 10, time = 600, result = true)]
+/// Get featured albums of a type, optionally only those in a genre. Only
+/// successful responses are cached.
+pub async fn featured_albums(
+    featured_type: FeaturedType,
+    genre_id: Option<u32>,
+) -> Result<Vec<Album>> {
+    QUEUE
+        .get()
         .unwrap()
+        .read()
+        .await
+        .get_featured_albums(featured_type, genre_id)
+        .await
+}
+
+#[instrument]
+/// Get new releases in a genre.
+pub async fn genre_albums(genre_id: u32) -> Result<Vec<Album>> {
+    featured_albums(FeaturedType::NewReleases, Some(genre_id)).await
 }
 
 #[instrument]
@@ -604,15 +1122,33 @@ pub async fn playlist(id: i64) -> Playlist {
 
 #[instrument]
 #[cached(size = 10, time = 600)]
-/// Fetch the albums for a specific artist.
-pub async fn artist_albums(artist_id: i32) -> Vec<Album> {
-    (QUEUE
+/// Fetch the albums for a specific artist, in the `sort` order.
+pub async fn artist_albums(artist_id: i32, sort: ReleaseSort) -> Vec<Album> {
+    let mut albums = (QUEUE
         .get()
         .unwrap()
         .read()
         .await
         .fetch_artist_albums(artist_id)
         .await)
+        .unwrap_or_default();
+
+    Album::sort(&mut albums, sort);
+
+    albums
+}
+
+#[instrument]
+#[cached(size = 10, time = 600)]
+/// Fetch the most popular tracks for a specific artist.
+pub async fn artist_tracks(artist_id: i32) -> Vec<Track> {
+    (QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .fetch_artist_tracks(artist_id)
+        .await)
         .unwrap_or_default()
 }
 
@@ -640,6 +1176,89 @@ pub async fn remove_favorite_album(id: &str) {
         .await;
 }
 
+#[instrument]
+/// Download a cover image.
+pub async fn cover_art(url: &str) -> Option<Vec<u8>> {
+    QUEUE.get().unwrap().read().await.cover_art(url).await
+}
+
+#[instrument]
+/// Add track to favorites
+pub async fn add_favorite_track(id: &str) {
+    _ = QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .add_favorite_track(id)
+        .await;
+}
+
+#[instrument]
+/// Remove track from favorites
+pub async fn remove_favorite_track(id: &str) {
+    _ = QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .remove_favorite_track(id)
+        .await;
+}
+
+/// Fetch favorites from the service, bypassing and replacing the cache.
+async fn refresh_favorites() -> Favorites {
+    let favorites = QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .favorites()
+        .await
+        .unwrap_or_default();
+
+    let mut cache = FAVORITES.lock().await;
+    cache.cache_clear();
+    cache.cache_set((), favorites.clone());
+
+    favorites
+}
+
+#[instrument]
+/// Flip the favorite status of an album and return the resulting status.
+///
+/// The current status is read from the service rather than the cache, and
+/// the returned status is read back after the change, so toggling an album
+/// that was concurrently changed elsewhere settles on the actual state.
+pub async fn toggle_favorite_album(id: &str) -> bool {
+    let is_favorite = |favorites: &Favorites| favorites.albums.iter().any(|a| a.id == id);
+
+    if is_favorite(&refresh_favorites().await) {
+        remove_favorite_album(id).await;
+    } else {
+        add_favorite_album(id).await;
+    }
+
+    is_favorite(&refresh_favorites().await)
+}
+
+#[instrument]
+/// Flip the favorite status of a track and return the resulting status.
+///
+/// See [`toggle_favorite_album`].
+pub async fn toggle_favorite_track(id: u32) -> bool {
+    let is_favorite = |favorites: &Favorites| favorites.tracks.iter().any(|t| t.id == id);
+    let track_id = id.to_string();
+
+    if is_favorite(&refresh_favorites().await) {
+        remove_favorite_track(&track_id).await;
+    } else {
+        add_favorite_track(&track_id).await;
+    }
+
+    is_favorite(&refresh_favorites().await)
+}
+
 #[instrument]
 /// Add artist to favorites
 pub async fn add_favorite_artist(id: &str) {
@@ -716,22 +1335,123 @@ pub async fn user_playlists() -> Vec<Playlist> {
         .unwrap_or_default()
 }
 
+/// Check the position and send it to the interfaces at this interval instead of
+/// the default. Must be called before the player starts.
+pub fn set_refresh_interval(interval: Duration) {
+    _ = REFRESH_INTERVAL.set(interval);
+}
+
+/// Play through the `sink` element, on `device` when given, instead of the default
+/// output. The default output is kept when the sink or device is not available.
+pub fn set_audio_output(sink: &str, device: Option<&str>) -> Result<()> {
+    let element = output::make_sink(sink, device)?;
+    PLAYBIN.set_property("audio-sink", &element);
+
+    Ok(())
+}
+
+/// Play every track at its own sample rate, without converting or resampling it on the
+/// way to the output. Playback fails instead when the output can not play the track as is.
+/// The ReplayGain filter is taken out and the volume is held at 100%, so the samples reach
+/// the output untouched.
+pub fn set_bit_perfect(enabled: bool) {
+    BIT_PERFECT.store(enabled, Ordering::Relaxed);
+
+    if enabled {
+        PLAYBIN.set_property_from_str("flags", "audio+buffering+native-audio");
+        PLAYBIN.set_property("audio-filter", None::<&Element>);
+        PLAYBIN.set_property("volume", 1.0);
+        REPLAYGAIN.set_property("volume", 1.0);
+    } else {
+        PLAYBIN.set_property_from_str("flags", "audio+buffering");
+        PLAYBIN.set_property("audio-filter", &*REPLAYGAIN);
+    }
+}
+
+/// Returns true if tracks are played without resampling.
+pub fn is_bit_perfect() -> bool {
+    BIT_PERFECT.load(Ordering::Relaxed)
+}
+
+/// The sample rate in Hz the output is playing at, once playback has started.
+pub fn output_rate() -> Option<u32> {
+    match OUTPUT_RATE.load(Ordering::Relaxed) {
+        0 => None,
+        rate => Some(rate),
+    }
+}
+
+/// The sample rate in caps set on a pad of the audio sink, which is what the device plays at.
+fn sink_rate(object: &gst::Object, value: Option<&gst::glib::Value>) -> Option<u32> {
+    let pad = object.downcast_ref::<gst::Pad>()?;
+    let element = pad.parent_element()?;
+
+    if !element.element_flags().contains(gst::ElementFlags::SINK) {
+        return None;
+    }
+
+    let caps = value?.get::<gst::Caps>().ok()?;
+    let rate = caps.structure(0)?.get::<i32>("rate").ok()?;
+
+    Some(rate as u32)
+}
+
 /// Inserts the most recent position into the state at a set interval.
 #[instrument]
 pub async fn clock_loop() {
-    debug!("starting clock loop");
+    let refresh_interval = *REFRESH_INTERVAL.get_or_init(|| DEFAULT_REFRESH_INTERVAL);
+    debug!(
+        "starting clock loop, refreshing every {:?}",
+        refresh_interval
+    );
 
-    let mut interval = tokio::time::interval(Duration::from_millis(250));
+    let mut interval = tokio::time::interval(refresh_interval);
     let mut last_position = ClockTime::default();
+    let mut last_saved_position = ClockTime::default();
+    let mut last_remaining = None;
 
     loop {
         interval.tick().await;
 
+        let sleep_timer = *SLEEP_TIMER.lock().unwrap();
+
+        if let Some(timer) = sleep_timer {
+            let remaining = timer.deadline.saturating_duration_since(Instant::now());
+
+            if !remaining.is_zero() {
+                if last_remaining != Some(remaining.as_secs()) {
+                    last_remaining = Some(remaining.as_secs());
+                    broadcast_sleep_timer(last_remaining).await;
+                }
+            } else if !timer.finish_track || !is_playing() {
+                // With `finish_track` the timer is cleared once the
+                // current track reaches the end of the stream instead.
+                debug!("sleep timer expired, pausing playback");
+                cancel_sleep_timer().await;
+
+                if is_playing() {
+                    if let Err(error) = pause().await {
+                        debug!(?error);
+                    }
+                }
+            }
+        } else {
+            last_remaining = None;
+        }
+
         if current_state() == GstState::Playing {
             if let Some(position) = position() {
                 if position.seconds() != last_position.seconds() {
                     last_position = position;
 
+                    // Only write to the database after a meaningful change.
+                    if position.seconds().abs_diff(last_saved_position.seconds())
+                        >= SAVE_POSITION_SECONDS
+                    {
+                        last_saved_position = position;
+                        save_playback_position(position).await;
+                    }
+
                     BROADCAST_CHANNELS
                         .tx
                         .broadcast(Notification::Position { clock: position })
@@ -746,6 +1466,11 @@ pub async fn clock_loop() {
 pub async fn quit() -> Result<()> {
     debug!("stopping player");
 
+    // The position is only saved every few seconds while playing.
+    if let Some(position) = position() {
+        save_playback_position(position).await;
+    }
+
     QUEUE.get().unwrap().read().await.quit();
 
     if is_playing() {
@@ -822,11 +1547,32 @@ async fn handle_message(msg: &Message) -> Result<()> {
             debug!("END OF STREAM");
             let mut q = QUEUE.get().unwrap().write().await;
             q.set_target_status(GstState::Paused);
+
+            // When the sleep timer let the last track finish, stop on the
+            // track that would have played next instead of the first one.
+            let next_position = if sleep_timer_expired() {
+                q.current_track_position() + 1
+            } else {
+                1
+            };
+            let total_tracks = q.track_list().total();
             drop(q);
 
-            skip(1, true).await?;
+            cancel_sleep_timer_if_expired().await;
+
+            if next_position > total_tracks {
+                skip(1, true).await?;
+            } else {
+                skip(next_position, true).await?;
+            }
         }
         MessageView::StreamStart(_) => {
+            if let Some(track) = current_track().await {
+                apply_replay_gain(&track).await;
+                sql::db::add_history(&track).await;
+                TRACKS_PLAYED.fetch_add(1, Ordering::Relaxed);
+            }
+
             if is_playing() {
                 let list = QUEUE.get().unwrap().read().await.track_list();
                 broadcast_track_list(&list).await?;
@@ -853,7 +1599,46 @@ async fn handle_message(msg: &Message) -> Result<()> {
                 .broadcast(Notification::Position { clock: position })
                 .await?;
         }
-        MessageView::PropertyNotify(_) => {}
+        MessageView::PropertyNotify(notify) => {
+            let (object, name, value) = notify.get();
+
+            if name != "caps" {
+                return Ok(());
+            }
+
+            if let Some(rate) = sink_rate(object, value) {
+                if OUTPUT_RATE.swap(rate, Ordering::Relaxed) == rate {
+                    return Ok(());
+                }
+
+                debug!("output sample rate is now {rate} Hz");
+                BROADCAST_CHANNELS
+                    .tx
+                    .broadcast(Notification::OutputRate { rate })
+                    .await?;
+
+                // A sound server can still resample behind our back, so make it visible.
+                if is_bit_perfect() {
+                    let native_rate = current_track()
+                        .await
+                        .map(|track| (track.sampling_rate * 1000.0).round() as u32)
+                        .unwrap_or_default();
+
+                    if native_rate > 0 && native_rate != rate {
+                        BROADCAST_CHANNELS
+                            .tx
+                            .broadcast(Notification::Error {
+                                error: Error::AudioOutput {
+                                    message: format!(
+                                        "the output plays at {rate} Hz instead of the native {native_rate} Hz"
+                                    ),
+                                },
+                            })
+                            .await?;
+                    }
+                }
+            }
+        }
         MessageView::Buffering(buffering) => {
             if IS_LIVE.load(Ordering::Relaxed) {
                 debug!("stream is live, ignore buffering");
@@ -863,15 +1648,23 @@ async fn handle_message(msg: &Message) -> Result<()> {
 
             let target_status = QUEUE.get().unwrap().read().await.target_status();
 
+            let mut finished = false;
+
             if percent < 100 && !is_paused() && !IS_BUFFERING.load(Ordering::Relaxed) {
-                pause().await?;
                 IS_BUFFERING.store(true, Ordering::Relaxed);
+                pause().await?;
             } else if percent > 99 && IS_BUFFERING.load(Ordering::Relaxed) && is_paused() {
-                set_player_state(target_status).await?;
                 IS_BUFFERING.store(false, Ordering::Relaxed);
+                set_player_state(target_status).await?;
+                finished = true;
             }
 
-            if percent.rem_euclid(10) == 0 {
+            // Report every tenth percent once, and always the end of buffering
+            // so listeners can clear it.
+            let percent_changed =
+                BUFFERING_PERCENT.swap(percent as u32, Ordering::Relaxed) != percent as u32;
+
+            if finished || (percent_changed && percent.rem_euclid(10) == 0) {
                 debug!("buffering {}%", percent);
                 BROADCAST_CHANNELS
                     .tx
@@ -917,6 +1710,7 @@ async fn handle_message(msg: &Message) -> Result<()> {
                 .await?;
 
             ready().await?;
+            refresh_expired_url().await;
             pause().await?;
             play().await?;
 
@@ -932,3 +1726,132 @@ async fn handle_message(msg: &Message) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use service::TrackStatus;
+    use std::collections::BTreeMap;
+
+    fn track(position: u32, status: TrackStatus) -> Track {
+        Track {
+            id: position,
+            number: position,
+            title: format!("Track {position}"),
+            album: None,
+            artist: None,
+            duration_seconds: 180,
+            explicit: false,
+            hires_available: false,
+            sampling_rate: 44.1,
+            bit_depth: 16,
+            status,
+            track_url: None,
+            track_url_expires_at: None,
+            available: true,
+            cover_art: None,
+            position,
+            media_number: 1,
+            format: None,
+            replay_gain: Default::default(),
+        }
+    }
+
+    /// A queue of `total` tracks with the one at `playing` playing.
+    fn track_list(total: u32, playing: u32) -> TrackListValue {
+        let tracks = (1..=total)
+            .map(|position| {
+                let status = if position == playing {
+                    TrackStatus::Playing
+                } else {
+                    TrackStatus::Unplayed
+                };
+
+                (position, track(position, status))
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        TrackListValue::new(Some(&tracks))
+    }
+
+    #[test]
+    fn sleep_timer_is_capped_at_a_day() {
+        assert_eq!(sleep_timer_duration(0), Ok(Duration::ZERO));
+        assert_eq!(
+            sleep_timer_duration(MAX_SLEEP_TIMER_MINUTES),
+            Ok(Duration::from_secs(24 * 60 * 60))
+        );
+        assert_eq!(
+            sleep_timer_duration(MAX_SLEEP_TIMER_MINUTES + 1),
+            Err(Error::InvalidSleepTimer {
+                minutes: MAX_SLEEP_TIMER_MINUTES + 1,
+                max: MAX_SLEEP_TIMER_MINUTES
+            })
+        );
+        assert!(sleep_timer_duration(u64::MAX).is_err());
+    }
+
+    #[test]
+    fn bit_perfect_plays_at_unity_gain() {
+        let mut track = track(1, TrackStatus::Playing);
+        track.replay_gain.track_gain = Some(-7.5);
+        track.replay_gain.album_gain = Some(-6.0);
+
+        for mode in [
+            ReplayGainMode::Off,
+            ReplayGainMode::Track,
+            ReplayGainMode::Album,
+        ] {
+            assert_eq!(replay_gain_volume(&track, mode, true), 1.0);
+        }
+        assert_eq!(output_volume(0.25, true), 1.0);
+    }
+
+    #[test]
+    fn replay_gain_and_volume_apply_outside_bit_perfect_mode() {
+        let mut track = track(1, TrackStatus::Playing);
+        track.replay_gain.track_gain = Some(-20.0);
+
+        assert!((replay_gain_volume(&track, ReplayGainMode::Track, false) - 0.1).abs() < 1e-9);
+        assert_eq!(output_volume(0.25, false), 0.25);
+    }
+
+    #[test]
+    fn play_refuses_an_empty_queue() {
+        assert_eq!(
+            check_queued(&TrackListValue::new(None)),
+            Err(Error::EmptyQueue)
+        );
+        assert_eq!(check_queued(&track_list(2, 0)), Ok(()));
+    }
+
+    #[test]
+    fn next_ignores_an_empty_queue() {
+        assert_eq!(next_position(&TrackListValue::new(None), 0), None);
+    }
+
+    #[test]
+    fn previous_ignores_an_empty_queue() {
+        assert_eq!(previous_position(&TrackListValue::new(None), 0), None);
+    }
+
+    #[test]
+    fn next_and_previous_ignore_a_queue_that_is_not_playing() {
+        let track_list = track_list(3, 0);
+
+        assert_eq!(next_position(&track_list, 0), None);
+        assert_eq!(previous_position(&track_list, 0), None);
+    }
+
+    #[test]
+    fn next_stops_at_the_last_track() {
+        assert_eq!(next_position(&track_list(3, 2), 2), Some(3));
+        assert_eq!(next_position(&track_list(3, 3), 3), None);
+    }
+
+    #[test]
+    fn previous_starts_the_first_track_over() {
+        assert_eq!(previous_position(&track_list(3, 2), 2), Some(1));
+        assert_eq!(previous_position(&track_list(3, 1), 1), Some(1));
+    }
+}
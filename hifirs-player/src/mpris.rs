@@ -185,7 +185,23 @@ pub async fn receive_notifications(conn: &Connection) {
                         .expect("failed to signal metadata change");
                 }
                 Notification::Error { error: _ } => {}
-                Notification::Volume { volume: _ } => {}
+                Notification::SleepTimer {
+                    remaining_seconds: _,
+                } => {}
+                Notification::OutputRate { rate: _ } => {}
+                Notification::Volume { volume: _ } => {
+                    let iface_ref = object_server
+                        .interface::<_, MprisPlayer>("/org/mpris/MediaPlayer2")
+                        .await
+                        .expect("failed to get object server");
+
+                    iface_ref
+                        .get_mut()
+                        .await
+                        .volume_changed(iface_ref.signal_context())
+                        .await
+                        .expect("failed to signal volume change");
+                }
             }
         }
     }
@@ -317,7 +333,11 @@ impl MprisPlayer {
     }
     #[zbus(property, name = "Volume")]
     fn volume(&self) -> f64 {
-        1.0
+        crate::volume()
+    }
+    #[zbus(property, name = "Volume")]
+    fn set_volume(&mut self, value: f64) {
+        crate::set_volume(value.clamp(0.0, 1.0));
     }
     #[zbus(property, name = "Position")]
     async fn position(&self) -> i64 {
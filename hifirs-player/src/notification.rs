@@ -42,4 +42,10 @@ pub enum Notification {
     Volume {
         volume: f64,
     },
+    SleepTimer {
+        remaining_seconds: Option<u64>,
+    },
+    OutputRate {
+        rate: u32,
+    },
 }
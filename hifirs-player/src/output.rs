@@ -0,0 +1,86 @@
+//! Choosing the audio output, so playback can go straight to a specific DAC.
+
+use crate::{error::Error, Result};
+use gstreamer::{self as gst, prelude::*, Element};
+use serde::Serialize;
+
+/// Properties sinks use to pick the device to play on.
+const DEVICE_PROPERTIES: [&str; 2] = ["device", "target-object"];
+
+/// An audio output, with the sink and device to configure to play on it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioDevice {
+    pub name: String,
+    pub sink: String,
+    pub device: Option<String>,
+}
+
+/// The audio outputs GStreamer can find on this system.
+pub fn devices() -> Vec<AudioDevice> {
+    gst::init().expect("error initializing gstreamer");
+
+    let monitor = gst::DeviceMonitor::new();
+    monitor.add_filter(Some("Audio/Sink"), None);
+
+    if monitor.start().is_err() {
+        return vec![];
+    }
+
+    let devices = monitor
+        .devices()
+        .into_iter()
+        .filter_map(|device| {
+            let element = device.create_element(None).ok()?;
+            let sink = element.factory()?.name().to_string();
+
+            Some(AudioDevice {
+                name: device.display_name().to_string(),
+                sink,
+                device: device_property(&element)
+                    .and_then(|property| element.property::<Option<String>>(property)),
+            })
+        })
+        .collect();
+
+    monitor.stop();
+
+    devices
+}
+
+/// Build the `sink` element, playing on `device` when given. Fails when the sink
+/// is not installed or the device can not be opened.
+pub fn make_sink(sink: &str, device: Option<&str>) -> Result<Element> {
+    gst::init().expect("error initializing gstreamer");
+
+    let element = gst::ElementFactory::make(sink)
+        .build()
+        .map_err(|_| Error::AudioOutput {
+            message: format!("audio sink {sink} is not available"),
+        })?;
+
+    if let Some(device) = device {
+        let property = device_property(&element).ok_or_else(|| Error::AudioOutput {
+            message: format!("audio sink {sink} does not support choosing a device"),
+        })?;
+        element.set_property(property, device);
+
+        // Opening the device is the only reliable way to tell that it is still there.
+        let opened = element.set_state(gst::State::Ready).is_ok();
+        _ = element.set_state(gst::State::Null);
+
+        if !opened {
+            return Err(Error::AudioOutput {
+                message: format!("audio device {device} is not available"),
+            });
+        }
+    }
+
+    Ok(element)
+}
+
+fn device_property(element: &Element) -> Option<&'static str> {
+    DEVICE_PROPERTIES
+        .into_iter()
+        .find(|property| element.find_property(property).is_some())
+}
@@ -43,6 +43,7 @@ impl From<QobuzAlbum> for Album {
                 .to_string()
                 .parse::<u32>()
                 .expect("error converting year"),
+            release_date: value.release_date_original,
             hires_available: value.hires_streamable,
             explicit: value.parental_warning,
             available: value.streamable,
@@ -1,20 +1,45 @@
 use crate::{
-    service::{Album, Artist, Favorites, MusicService, Playlist, SearchResults, Track},
+    service::{
+        Album, Artist, Favorites, Genre, Label, MusicService, Playlist, ReplayGain, SearchResults,
+        Track, TrackUrl,
+    },
     sql::db,
 };
 use async_trait::async_trait;
 use hifirs_qobuz_api::client::{
+    album::FeaturedType,
     album_suggestion::AlbumSuggestion,
     api::{self, Client as QobuzClient},
     favorites::Favorites as QobuzFavorites,
     release::{Release, Track as QobuzTrack},
     search_results::SearchAllResults,
+    AudioQuality, TrackURL,
 };
-use std::{collections::BTreeMap, str::FromStr};
-use tracing::{debug, error, info};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::OnceLock,
+};
+use tracing::{debug, error, info, instrument};
 
 pub type Result<T, E = hifirs_qobuz_api::Error> = std::result::Result<T, E>;
 
+/// How long a scraped app_id and secret are trusted before fetching new ones.
+const SECRETS_TTL_SECONDS: i64 = 60 * 60 * 24 * 7;
+
+/// Highest quality to stream, set once at startup.
+static QUALITY: OnceLock<AudioQuality> = OnceLock::new();
+
+/// Directory with downloaded tracks to play instead of streaming, set once at startup.
+static DOWNLOAD_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Environment variables that, when both are set, replace the scraped app_id and secret.
+const APP_ID_ENV: &str = "QOBUZ_APP_ID";
+const SECRET_ENV: &str = "QOBUZ_SECRET";
+const API_URL_ENV: &str = "QOBUZ_API_URL";
+const PLAY_URL_ENV: &str = "QOBUZ_PLAY_URL";
+
 pub mod album;
 pub mod artist;
 pub mod playlist;
@@ -23,7 +48,7 @@ pub mod track;
 #[async_trait]
 impl MusicService for QobuzClient {
     async fn login(&self, username: &str, password: &str) {
-        self.login(username, password).await;
+        _ = self.login(username, password).await;
     }
 
     async fn album(&self, album_id: &str) -> Option<Album> {
@@ -77,6 +102,61 @@ impl MusicService for QobuzClient {
         }
     }
 
+    async fn label(&self, label_id: u32) -> Option<Label> {
+        match self.label(label_id as i64, None).await {
+            Ok(label) => Some(Label {
+                id: label.id as u32,
+                name: label.name,
+                albums: label.albums.items.into_iter().map(|x| x.into()).collect(),
+            }),
+            Err(err) => {
+                error!("failed to get label: {}", err);
+                None
+            }
+        }
+    }
+
+    async fn genres(&self, parent_id: Option<u32>) -> crate::Result<Vec<Genre>> {
+        match self.genres(parent_id.map(|id| id as i64)).await {
+            Ok(genres) => Ok(genres
+                .genres
+                .items
+                .into_iter()
+                .map(|genre| Genre {
+                    id: genre.id as u32,
+                    parent_id: genre.parent_id().map(|id| id as u32),
+                    name: genre.name,
+                })
+                .collect()),
+            Err(err) => {
+                error!("failed to get genres: {}", err);
+                Err(err.into())
+            }
+        }
+    }
+
+    async fn featured_albums(
+        &self,
+        featured_type: FeaturedType,
+        genre_id: Option<u32>,
+    ) -> crate::Result<Vec<Album>> {
+        match self
+            .featured_albums(featured_type, genre_id.map(|id| id as i64), None)
+            .await
+        {
+            Ok(featured) => Ok(featured
+                .albums
+                .items
+                .into_iter()
+                .map(|x| x.into())
+                .collect()),
+            Err(err) => {
+                error!("failed to get featured albums: {}", err);
+                Err(err.into())
+            }
+        }
+    }
+
     async fn artist_releases(&self, artist_id: i32) -> Option<Vec<Album>> {
         match self.artist_releases(artist_id, None).await {
             Ok(artist_releases) => Some(artist_releases.into_iter().map(|x| x.into()).collect()),
@@ -84,6 +164,13 @@ impl MusicService for QobuzClient {
         }
     }
 
+    async fn artist_tracks(&self, artist_id: i32) -> Option<Vec<Track>> {
+        match self.artist_tracks(artist_id, None).await {
+            Ok(tracks) => Some(tracks.into_iter().map(|x| x.into()).collect()),
+            Err(_) => None,
+        }
+    }
+
     async fn playlist(&self, playlist_id: i64) -> Option<Playlist> {
         match self.playlist(playlist_id).await {
             Ok(playlist) => Some(playlist.into()),
@@ -91,13 +178,33 @@ impl MusicService for QobuzClient {
         }
     }
 
-    async fn search(&self, query: &str) -> Option<SearchResults> {
-        match self.search_all(query, 20).await {
+    async fn search(&self, query: &str, limit: u32, offset: u32) -> Option<SearchResults> {
+        match self.search_all(query, limit as i32, offset as i32).await {
             Ok(results) => Some(results.into()),
             Err(_) => None,
         }
     }
 
+    async fn search_tracks(&self, query: &str, limit: u32, offset: u32) -> Option<SearchResults> {
+        match self
+            .search_tracks(query, Some(limit as i32), Some(offset as i32))
+            .await
+        {
+            Ok(results) => Some(SearchResults {
+                query: results.query,
+                total: results.tracks.total as u32,
+                tracks: results
+                    .tracks
+                    .items
+                    .into_iter()
+                    .map(|t| t.into())
+                    .collect::<Vec<Track>>(),
+                ..Default::default()
+            }),
+            Err(_) => None,
+        }
+    }
+
     async fn favorites(&self) -> Option<Favorites> {
         match self.favorites(1000).await {
             Ok(results) => Some(results.into()),
@@ -111,6 +218,19 @@ impl MusicService for QobuzClient {
     async fn remove_favorite_album(&self, id: &str) {
         _ = self.remove_favorite_album(id).await;
     }
+    async fn cover_art(&self, url: &str) -> Option<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.download(url, &mut bytes).await.ok()?;
+
+        Some(bytes)
+    }
+
+    async fn add_favorite_track(&self, id: &str) {
+        _ = self.add_favorite_track(id).await;
+    }
+    async fn remove_favorite_track(&self, id: &str) {
+        _ = self.remove_favorite_track(id).await;
+    }
     async fn add_favorite_artist(&self, id: &str) {
         _ = self.add_favorite_artist(id).await;
     }
@@ -124,9 +244,27 @@ impl MusicService for QobuzClient {
         _ = self.remove_favorite_playlist(id).await;
     }
 
-    async fn track_url(&self, track_id: i32) -> Option<String> {
-        match self.track_url(track_id, None).await {
-            Ok(track_url) => Some(track_url.url),
+    #[instrument(skip(self), fields(quality = ?quality()))]
+    async fn track_url(&self, track_id: i32) -> Option<TrackUrl> {
+        if let Some(track_url) = local_track_url(track_id) {
+            return Some(track_url);
+        }
+
+        match self.track_url_with_fallback(track_id, quality()).await {
+            Ok(track_url) => Some(track_url.into()),
+            Err(hifirs_qobuz_api::Error::Unauthorized) => {
+                info!("track url rejected, refreshing app_id and secret");
+
+                if let Err(err) = refresh_secrets(self).await {
+                    error!("failed to refresh app_id and secret: {}", err);
+                    return None;
+                }
+
+                match self.track_url_with_fallback(track_id, quality()).await {
+                    Ok(track_url) => Some(track_url.into()),
+                    Err(_) => None,
+                }
+            }
             Err(_) => None,
         }
     }
@@ -145,13 +283,105 @@ impl MusicService for QobuzClient {
     }
 }
 
+/// Stream at the given quality instead of the highest one available.
+pub fn set_quality(quality: AudioQuality) {
+    _ = QUALITY.set(quality);
+}
+
+/// The quality tracks are streamed at.
+pub fn quality() -> AudioQuality {
+    QUALITY.get().copied().unwrap_or_default()
+}
+
+/// Play downloaded tracks from this directory instead of streaming them.
+pub fn set_download_dir(dir: PathBuf) {
+    _ = DOWNLOAD_DIR.set(dir);
+}
+
+/// The name a downloaded track is saved under, which is how it is found again
+/// for offline playback.
+pub fn download_file_name(track_id: i32, mime_type: &str) -> String {
+    format!("{track_id}.{}", file_extension(mime_type))
+}
+
+/// The file extension for audio of the given mime type.
+pub fn file_extension(mime_type: &str) -> &'static str {
+    match mime_type {
+        "audio/mpeg" => "mp3",
+        _ => "flac",
+    }
+}
+
+/// A previously downloaded file for the track, if there is one.
+fn local_track_url(track_id: i32) -> Option<TrackUrl> {
+    let dir = DOWNLOAD_DIR.get()?;
+
+    ["audio/flac", "audio/mpeg"]
+        .into_iter()
+        .find_map(|mime_type| {
+            let path = dir.join(download_file_name(track_id, mime_type));
+            path.is_file().then_some((path, mime_type))
+        })
+        .and_then(|(path, mime_type)| {
+            let uri = file_uri(&path)?;
+            debug!("playing track {} from {}", track_id, path.display());
+
+            Some(TrackUrl {
+                url: uri,
+                // Unknown without reading the file, the track keeps its own.
+                sampling_rate: 0.0,
+                bit_depth: 0,
+                format: if mime_type == "audio/mpeg" {
+                    "MP3"
+                } else {
+                    "FLAC"
+                }
+                .to_string(),
+                expires_at: None,
+            })
+        })
+}
+
+fn file_uri(path: &Path) -> Option<String> {
+    let path = path.canonicalize().ok()?;
+
+    match gstreamer::glib::filename_to_uri(&path, None) {
+        Ok(uri) => Some(uri.to_string()),
+        Err(error) => {
+            error!("failed to build a uri for {}: {}", path.display(), error);
+            None
+        }
+    }
+}
+
 pub async fn make_client(username: Option<&str>, password: Option<&str>) -> Result<QobuzClient> {
     let mut client = api::new(None, None, None).await?;
 
+    if let Ok(url) = std::env::var(API_URL_ENV) {
+        debug!("using api url from the environment: {url}");
+        client.set_base_url(&url);
+    }
+
+    if let Ok(url) = std::env::var(PLAY_URL_ENV) {
+        debug!("using web player url from the environment: {url}");
+        client.set_play_url(&url);
+    }
+
     setup_client(&mut client, username, password).await
 }
 
-/// Setup app_id, secret and user credentials for authentication
+/// Log in with a username and password, replacing any cached token.
+/// The password itself is never stored.
+pub async fn login(username: &str, password: &str) -> Result<QobuzClient> {
+    db::clear_user_token().await;
+
+    make_client(Some(username), Some(password)).await
+}
+
+/// Setup app_id, secret and user credentials for authentication.
+///
+/// The app_id and secret come from `QOBUZ_APP_ID` and `QOBUZ_SECRET` when both
+/// are set, otherwise from the database, scraping new ones when needed.
 pub async fn setup_client(
     client: &mut QobuzClient,
     username: Option<&str>,
@@ -162,70 +392,180 @@ pub async fn setup_client(
     if let Some(config) = db::get_config().await {
         let mut refresh_config = false;
 
-        if let Some(app_id) = config.app_id {
-            debug!("using app_id from cache");
+        let from_env = if let (Ok(app_id), Ok(secret)) =
+            (std::env::var(APP_ID_ENV), std::env::var(SECRET_ENV))
+        {
+            debug!("using app_id and secret from the environment");
             client.set_app_id(app_id);
+            client.set_active_secret(secret);
+            true
         } else {
-            debug!("app_id not found, will have to refresh config");
-            refresh_config = true;
+            false
+        };
+
+        if let Some(user_agent) = config.user_agent.as_deref() {
+            client.set_user_agent(user_agent)?;
         }
 
-        if let Some(secret) = config.active_secret {
-            debug!("using active secret from cache");
-            client.set_active_secret(secret);
-        } else {
-            debug!("active_secret not found, will have to refresh config");
-            refresh_config = true;
+        client.set_max_quality(
+            config
+                .max_quality
+                .as_deref()
+                .and_then(|quality| quality.parse().ok()),
+        );
+
+        let now = chrono::Utc::now().timestamp();
+
+        if !from_env {
+            if config
+                .secrets_updated_at
+                .is_none_or(|updated_at| now - updated_at > SECRETS_TTL_SECONDS)
+            {
+                debug!("cached app_id and secret are stale, will have to refresh config");
+                refresh_config = true;
+            }
+
+            if let Some(app_id) = config.app_id {
+                debug!("using app_id from cache");
+                client.set_app_id(app_id);
+            } else {
+                debug!("app_id not found, will have to refresh config");
+                refresh_config = true;
+            }
+
+            if let Some(secret) = config.active_secret {
+                debug!("using active secret from cache");
+                client.set_active_secret(secret);
+            } else {
+                debug!("active_secret not found, will have to refresh config");
+                refresh_config = true;
+            }
         }
 
         if refresh_config {
             client.refresh().await?;
 
             if let Some(id) = client.get_app_id() {
-                db::set_app_id(id).await;
-            }
-
-            if let Some(secret) = client.get_active_secret() {
-                db::set_active_secret(secret).await;
+                db::set_app_id(&id).await;
             }
         }
 
+        let (username, password): (Option<String>, Option<String>) =
+            if let (Some(u), Some(p)) = (username, password) {
+                (Some(u.to_string()), Some(p.to_string()))
+            } else if let (Some(u), Some(p)) = (config.username, config.password) {
+                (Some(u), Some(p))
+            } else {
+                (None, None)
+            };
+
         if let Some(token) = config.user_token {
             info!("using token from cache");
             client.set_token(token);
-        } else {
-            let (username, password): (Option<String>, Option<String>) =
-                if let (Some(u), Some(p)) = (username, password) {
-                    (Some(u.to_string()), Some(p.to_string()))
-                } else if let (Some(u), Some(p)) = (config.username, config.password) {
-                    (Some(u), Some(p))
-                } else {
-                    (None, None)
-                };
 
+            // Lets the client log in again by itself if the token has expired.
             if let (Some(username), Some(password)) = (username, password) {
-                info!("setting auth using username and password from cache");
-                client.login(&username, &password).await?;
-                client.test_secrets().await?;
+                client.set_credentials(username, password);
+            }
+        } else if let (Some(username), Some(password)) = (username, password) {
+            info!("setting auth using username and password");
+            client.login(&username, &password).await?;
 
-                if let Some(token) = client.get_token() {
-                    db::set_user_token(token).await;
-                }
+            if let Some(token) = client.get_token() {
+                db::set_user_token(&token).await;
+            }
 
-                if let Some(secret) = client.get_active_secret() {
-                    db::set_active_secret(secret).await;
-                }
+            // Only the token is kept, a password hash saved by an older version is dropped.
+            db::clear_password().await;
+        }
+
+        if !from_env
+            && client.signed_in()
+            && (refresh_config || client.get_active_secret().is_none())
+        {
+            client.test_secrets().await?;
+
+            if let Some(secret) = client.get_active_secret() {
+                db::set_active_secret(&secret).await;
             }
+
+            db::set_secrets_updated_at(now).await;
         }
     }
 
     Ok(client.clone())
 }
 
+/// Check that the saved token is still accepted, logging in again when the client has
+/// credentials to do so. A token that is rejected regardless is removed from the
+/// database so that the user is asked to log in.
+pub async fn verify_token(client: &QobuzClient) -> bool {
+    match client.favorites(1).await {
+        Ok(_) => {
+            if let Some(token) = client.get_token() {
+                db::set_user_token(&token).await;
+            }
+
+            true
+        }
+        Err(hifirs_qobuz_api::Error::Unauthorized) => {
+            info!("saved token was rejected");
+            db::clear_user_token().await;
+
+            false
+        }
+        // Being offline or a Qobuz outage is no reason to drop the token.
+        Err(_) => true,
+    }
+}
+
+/// Replace a rejected app_id and secret with freshly scraped ones.
+async fn refresh_secrets(client: &QobuzClient) -> Result<()> {
+    client.reauthorize().await?;
+
+    if let Some(id) = client.get_app_id() {
+        db::set_app_id(&id).await;
+    }
+
+    if let Some(secret) = client.get_active_secret() {
+        db::set_active_secret(&secret).await;
+    }
+
+    db::set_secrets_updated_at(chrono::Utc::now().timestamp()).await;
+
+    Ok(())
+}
+
+impl From<TrackURL> for TrackUrl {
+    fn from(value: TrackURL) -> Self {
+        let format = match value.mime_type.as_str() {
+            "audio/flac" => "FLAC".to_string(),
+            "audio/mpeg" => "MP3".to_string(),
+            other => other.trim_start_matches("audio/").to_uppercase(),
+        };
+
+        Self {
+            expires_at: value.expires_at(),
+            url: value.url,
+            sampling_rate: value.sampling_rate as f32,
+            bit_depth: value.bit_depth as u32,
+            format,
+        }
+    }
+}
+
 impl From<SearchAllResults> for SearchResults {
     fn from(s: SearchAllResults) -> Self {
+        let total = s
+            .albums
+            .total
+            .max(s.tracks.total)
+            .max(s.artists.total)
+            .max(s.playlists.total) as u32;
+
         Self {
             query: s.query,
+            total,
             albums: s
                 .albums
                 .items
@@ -298,10 +638,18 @@ impl From<QobuzTrack> for Track {
             bit_depth: s.audio_info.maximum_bit_depth,
             status: crate::service::TrackStatus::Unplayed,
             track_url: None,
+            track_url_expires_at: None,
             available: s.rights.streamable,
             cover_art: None,
             position: s.physical_support.track_number as u32,
             media_number: s.physical_support.media_number as u32,
+            format: None,
+            replay_gain: ReplayGain {
+                track_gain: s.audio_info.replaygain_track_gain,
+                track_peak: s.audio_info.replaygain_track_peak,
+                album_gain: s.audio_info.replaygain_album_gain,
+                album_peak: s.audio_info.replaygain_album_peak,
+            },
         }
     }
 }
@@ -349,6 +697,7 @@ impl From<Release> for Album {
                 .to_string()
                 .parse::<u32>()
                 .expect("error converting year"),
+            release_date: s.dates.original,
             hires_available: s.rights.hires_streamable,
             explicit: s.parental_warning,
             total_tracks: s.tracks_count as u32,
@@ -385,6 +734,7 @@ impl From<AlbumSuggestion> for Album {
                 .to_string()
                 .parse::<u32>()
                 .expect("error converting year"),
+            release_date: s.dates.original,
             hires_available: s.rights.hires_streamable,
             explicit: s.parental_warning,
             total_tracks: s.track_count as u32,
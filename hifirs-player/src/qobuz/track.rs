@@ -1,4 +1,4 @@
-use crate::service::{Album, Artist, Track, TrackStatus};
+use crate::service::{Album, Artist, ReplayGain, Track, TrackStatus};
 use hifirs_qobuz_api::client::track::Track as QobuzTrack;
 
 impl From<QobuzTrack> for Track {
@@ -41,10 +41,18 @@ impl From<QobuzTrack> for Track {
             bit_depth: value.maximum_bit_depth as u32,
             status,
             track_url: None,
+            track_url_expires_at: None,
             available: value.streamable,
             position: value.position.unwrap_or(value.track_number as usize) as u32,
             cover_art,
             media_number: value.media_number as u32,
+            format: None,
+            replay_gain: ReplayGain {
+                track_gain: value.audio_info.replaygain_track_gain,
+                track_peak: value.audio_info.replaygain_track_peak,
+                album_gain: value.audio_info.replaygain_album_gain,
+                album_peak: value.audio_info.replaygain_album_peak,
+            },
         }
     }
 }
@@ -1,19 +1,23 @@
 use gstreamer::State as GstState;
+use hifirs_qobuz_api::client::album::FeaturedType;
 use std::{collections::BTreeMap, sync::Arc};
 use tokio::sync::{
     broadcast::{Receiver as BroadcastReceiver, Sender as BroadcastSender},
     RwLock,
 };
-use tracing::debug;
+use tracing::{debug, instrument};
 
 use crate::{
+    error::Error,
     position, qobuz,
     service::{
-        Album, Artist, Favorites, MusicService, Playlist, SearchResults, Track, TrackStatus,
+        Album, Artist, Favorites, Genre, Label, MusicService, Playlist, SearchResults, Track,
+        TrackStatus,
     },
+    Result,
 };
 
-use super::{TrackListType, TrackListValue};
+use super::{streamable_tracks, QueueDuration, TrackListType, TrackListValue};
 
 #[derive(Debug, Clone)]
 pub struct PlayerState {
@@ -53,6 +57,12 @@ impl From<PlayerState> for SavedState {
                     .id
                     .to_string(),
                 TrackListType::Track => current_track.id.to_string(),
+                TrackListType::Artist => state
+                    .tracklist
+                    .get_artist()
+                    .expect("failed to get artist id")
+                    .id
+                    .to_string(),
                 TrackListType::Unknown => "".to_string(),
             };
 
@@ -71,18 +81,17 @@ impl From<PlayerState> for SavedState {
 }
 
 impl PlayerState {
+    #[instrument(skip(self))]
     pub async fn play_album(&mut self, album_id: &str) -> Option<String> {
         debug!("setting up album to play");
 
         if let Some(album) = self.service.album(album_id).await {
-            let mut tracklist = TrackListValue::new(Some(&album.tracks));
+            let mut tracklist = TrackListValue::new(Some(&streamable_tracks(&album.tracks)));
             tracklist.set_album(album);
             tracklist.set_list_type(TrackListType::Album);
             tracklist.set_track_status(1, TrackStatus::Playing);
 
-            self.replace_list(tracklist.clone());
-
-            if let Some(mut entry) = tracklist.queue.first_entry() {
+            let track_url = if let Some(mut entry) = tracklist.queue.first_entry() {
                 let first_track = entry.get_mut();
 
                 self.attach_track_url(first_track).await;
@@ -92,11 +101,16 @@ impl PlayerState {
                 first_track.track_url.clone()
             } else {
                 None
-            }
+            };
+
+            self.replace_list(tracklist);
+
+            track_url
         } else {
             None
         }
     }
+    #[instrument(skip(self))]
     pub async fn play_track(&mut self, track_id: i32) -> Option<String> {
         debug!("setting up track to play");
 
@@ -104,15 +118,16 @@ impl PlayerState {
             track.status = TrackStatus::Playing;
             track.number = 1;
 
+            self.attach_track_url(&mut track).await;
+
             let mut queue = BTreeMap::new();
             queue.entry(track.position).or_insert_with(|| track.clone());
 
             let mut tracklist = TrackListValue::new(Some(&queue));
             tracklist.set_list_type(TrackListType::Track);
 
-            self.replace_list(tracklist.clone());
+            self.replace_list(tracklist);
 
-            self.attach_track_url(&mut track).await;
             self.set_current_track(track.clone());
             self.set_target_status(GstState::Playing);
 
@@ -121,19 +136,18 @@ impl PlayerState {
             None
         }
     }
+    #[instrument(skip(self))]
     pub async fn play_playlist(&mut self, playlist_id: i64) -> Option<String> {
         debug!("setting up playlist to play");
 
         if let Some(playlist) = self.service.playlist(playlist_id).await {
-            let mut tracklist = TrackListValue::new(Some(&playlist.tracks));
+            let mut tracklist = TrackListValue::new(Some(&streamable_tracks(&playlist.tracks)));
 
             tracklist.set_playlist(playlist);
             tracklist.set_list_type(TrackListType::Playlist);
             tracklist.set_track_status(1, TrackStatus::Playing);
 
-            self.replace_list(tracklist.clone());
-
-            if let Some(mut entry) = tracklist.queue.first_entry() {
+            let track_url = if let Some(mut entry) = tracklist.queue.first_entry() {
                 let first_track = entry.get_mut();
 
                 self.attach_track_url(first_track).await;
@@ -143,12 +157,50 @@ impl PlayerState {
                 first_track.track_url.clone()
             } else {
                 None
-            }
+            };
+
+            self.replace_list(tracklist);
+
+            track_url
         } else {
             None
         }
     }
 
+    #[instrument(skip(self))]
+    pub async fn play_artist(&mut self, artist_id: i32) -> Option<String> {
+        debug!("setting up artist to play");
+
+        let artist = self.service.artist(artist_id).await?;
+        let tracks = self.service.artist_tracks(artist_id).await?;
+
+        let queue = tracks
+            .into_iter()
+            .enumerate()
+            .map(|(i, track)| (i as u32 + 1, track))
+            .collect::<BTreeMap<u32, Track>>();
+
+        let mut tracklist = TrackListValue::new(Some(&streamable_tracks(&queue)));
+        tracklist.set_artist(artist);
+        tracklist.set_track_status(1, TrackStatus::Playing);
+
+        let track_url = if let Some(mut entry) = tracklist.queue.first_entry() {
+            let first_track = entry.get_mut();
+
+            self.attach_track_url(first_track).await;
+            self.set_current_track(first_track.clone());
+            self.set_target_status(GstState::Playing);
+
+            first_track.track_url.clone()
+        } else {
+            None
+        };
+
+        self.replace_list(tracklist);
+
+        track_url
+    }
+
     pub fn set_status(&mut self, status: GstState) {
         self.status = status;
     }
@@ -173,6 +225,18 @@ impl PlayerState {
         self.tracklist.current_track()
     }
 
+    pub fn queue_duration(&self, position_seconds: u32) -> QueueDuration {
+        self.tracklist.duration(position_seconds)
+    }
+
+    /// Whether a track in the queue has the given position.
+    pub fn has_position(&self, position: u32) -> bool {
+        self.tracklist
+            .queue
+            .values()
+            .any(|track| track.position == position)
+    }
+
     pub fn current_track_position(&self) -> u32 {
         if let Some(track) = &self.current_track {
             track.position
@@ -181,6 +245,84 @@ impl PlayerState {
         }
     }
 
+    /// Queue a track to play right after the current one.
+    pub async fn add_next(&mut self, track_id: i32) -> Option<()> {
+        let track = self.queued_track(track_id).await?;
+        let position = self.current_track_position();
+
+        self.tracklist.insert_after(position, track);
+        self.sync_current_track();
+
+        Some(())
+    }
+
+    /// Queue a track at the end of the tracklist.
+    pub async fn add_to_queue(&mut self, track_id: i32) -> Option<()> {
+        let track = self.queued_track(track_id).await?;
+
+        self.tracklist.append(track);
+
+        Some(())
+    }
+
+    /// Remove a track from the queue, the playing track can't be removed.
+    pub fn remove_track(&mut self, position: u32) -> Result<Track> {
+        let removed = self.tracklist.remove(position)?;
+
+        self.sync_current_track();
+
+        Ok(removed)
+    }
+
+    /// Reorder the queue without interrupting the playing track.
+    pub fn move_track(&mut self, from: u32, to: u32) -> Result<()> {
+        if !self.tracklist.move_track(from, to) {
+            let position = if self.tracklist.find_track_by_index(from).is_none() {
+                from
+            } else {
+                to
+            };
+
+            return Err(Error::InvalidQueuePosition { position });
+        }
+
+        self.sync_current_track();
+
+        Ok(())
+    }
+
+    /// Remove the played tracks from the queue.
+    pub fn clear_played(&mut self) -> usize {
+        let removed = self.tracklist.remove_played();
+        self.sync_current_track();
+
+        removed
+    }
+
+    /// Empty the queue.
+    pub fn clear_queue(&mut self) {
+        self.tracklist.clear();
+        self.current_track = None;
+    }
+
+    /// Keep the cached current track in step after the queue is re-keyed.
+    fn sync_current_track(&mut self) {
+        if let Some(track) = self.tracklist.current_track() {
+            self.current_track = Some(track.clone());
+        }
+    }
+
+    async fn queued_track(&self, track_id: i32) -> Option<Track> {
+        let mut track = self.service.track(track_id).await?;
+        track.status = if track.available {
+            TrackStatus::Unplayed
+        } else {
+            TrackStatus::Unplayable
+        };
+
+        Some(track)
+    }
+
     pub fn unplayed_tracks(&self) -> Vec<&Track> {
         self.tracklist.unplayed_tracks()
     }
@@ -201,6 +343,10 @@ impl PlayerState {
         self.tracklist.get_playlist()
     }
 
+    pub fn artist(&self) -> Option<&Artist> {
+        self.tracklist.get_artist()
+    }
+
     pub fn replace_list(&mut self, tracklist: TrackListValue) {
         debug!("replacing tracklist");
         self.tracklist = tracklist;
@@ -227,10 +373,31 @@ impl PlayerState {
         debug!("fetching track url");
         if let Some(track_url) = self.service.track_url(track.id as i32).await {
             debug!("attaching url information to track");
-            track.track_url = Some(track_url);
+            track.set_track_url(track_url);
         }
     }
 
+    /// Resolve the url of the current track again when it has expired. Returns the
+    /// new url, or `None` when the current one can still be used.
+    pub async fn refresh_track_url(&mut self) -> Option<String> {
+        let position = self
+            .tracklist
+            .current_track()
+            .filter(|track| track.track_url_expired())?
+            .position;
+        let track = self.tracklist.queue.get_mut(&position)?;
+
+        debug!("url of track {} expired, fetching a new one", track.id);
+        let url = self.service.track_url(track.id as i32).await?;
+        let track_url = url.url.clone();
+
+        track.set_track_url(url);
+        self.current_track = Some(track.clone());
+
+        Some(track_url)
+    }
+
+    #[instrument(skip(self))]
     pub async fn skip_track(&mut self, index: u32) -> Option<String> {
         let mut track_url = None;
 
@@ -242,8 +409,8 @@ impl PlayerState {
                 std::cmp::Ordering::Equal => {
                     if let Some(url) = self.service.track_url(t.id as i32).await {
                         t.status = TrackStatus::Playing;
-                        t.track_url = Some(url.clone());
-                        track_url = Some(url);
+                        track_url = Some(url.url.clone());
+                        t.set_track_url(url);
                         self.current_track = Some(t.clone());
                     } else {
                         t.status = TrackStatus::Unplayable;
@@ -258,8 +425,34 @@ impl PlayerState {
         track_url
     }
 
-    pub async fn search_all(&self, query: &str) -> Option<SearchResults> {
-        self.service.search(query).await
+    /// Mark the playing track as played and start the next unplayed one,
+    /// skipping tracks that cannot be streamed. Returns the url of the new track,
+    /// or `None` when nothing is left to play, leaving the playing track as it is.
+    #[instrument(skip(self))]
+    pub async fn advance(&mut self) -> Option<String> {
+        let service = &self.service;
+        let track = self
+            .tracklist
+            .advance(|track_id| service.track_url(track_id as i32))
+            .await?;
+
+        let track_url = track.track_url.clone();
+        self.current_track = Some(track);
+
+        track_url
+    }
+
+    pub async fn search_tracks(
+        &self,
+        query: &str,
+        limit: u32,
+        offset: u32,
+    ) -> Option<SearchResults> {
+        self.service.search_tracks(query, limit, offset).await
+    }
+
+    pub async fn search_all(&self, query: &str, limit: u32, offset: u32) -> Option<SearchResults> {
+        self.service.search(query, limit, offset).await
     }
 
     pub async fn favorites(&self) -> Option<Favorites> {
@@ -272,6 +465,15 @@ impl PlayerState {
     pub async fn remove_favorite_album(&self, id: &str) {
         self.service.remove_favorite_album(id).await;
     }
+    pub async fn cover_art(&self, url: &str) -> Option<Vec<u8>> {
+        self.service.cover_art(url).await
+    }
+    pub async fn add_favorite_track(&self, id: &str) {
+        self.service.add_favorite_track(id).await;
+    }
+    pub async fn remove_favorite_track(&self, id: &str) {
+        self.service.remove_favorite_track(id).await;
+    }
     pub async fn add_favorite_artist(&self, id: &str) {
         self.service.add_favorite_artist(id).await;
     }
@@ -289,6 +491,10 @@ impl PlayerState {
         self.service.artist(artist_id).await
     }
 
+    pub async fn get_track(&self, track_id: i32) -> Option<Track> {
+        self.service.track(track_id).await
+    }
+
     pub async fn get_album(&self, id: &str) -> Option<Album> {
         self.service.album(id).await
     }
@@ -301,6 +507,22 @@ impl PlayerState {
         self.service.similar_artists(id).await
     }
 
+    pub async fn get_label(&self, label_id: u32) -> Option<Label> {
+        self.service.label(label_id).await
+    }
+
+    pub async fn get_genres(&self, parent_id: Option<u32>) -> Result<Vec<Genre>> {
+        self.service.genres(parent_id).await
+    }
+
+    pub async fn get_featured_albums(
+        &self,
+        featured_type: FeaturedType,
+        genre_id: Option<u32>,
+    ) -> Result<Vec<Album>> {
+        self.service.featured_albums(featured_type, genre_id).await
+    }
+
     pub async fn get_playlist(&self, playlist_id: i64) -> Option<Playlist> {
         self.service.playlist(playlist_id).await
     }
@@ -309,6 +531,10 @@ impl PlayerState {
         self.service.artist_releases(artist_id).await
     }
 
+    pub async fn fetch_artist_tracks(&self, artist_id: i32) -> Option<Vec<Track>> {
+        self.service.artist_tracks(artist_id).await
+    }
+
     pub async fn fetch_playlist_tracks(&self, playlist_id: i64) -> Option<Vec<Track>> {
         match self.service.playlist(playlist_id).await {
             Some(results) => Some(results.tracks.values().cloned().collect::<Vec<Track>>()),
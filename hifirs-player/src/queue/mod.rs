@@ -1,8 +1,12 @@
 pub mod controls;
 
-use crate::service::{Album, Playlist, Track, TrackStatus};
+use crate::{
+    error::Error,
+    service::{Album, Artist, Playlist, Track, TrackStatus, TrackUrl},
+    Result,
+};
 use serde::{Deserialize, Serialize, Serializer};
-use std::{collections::BTreeMap, fmt::Display};
+use std::{collections::BTreeMap, fmt::Display, future::Future};
 use tracing::{debug, instrument};
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -10,6 +14,7 @@ pub enum TrackListType {
     Album,
     Playlist,
     Track,
+    Artist,
     #[default]
     Unknown,
 }
@@ -20,6 +25,7 @@ impl Display for TrackListType {
             TrackListType::Album => f.write_fmt(format_args!("album")),
             TrackListType::Playlist => f.write_fmt(format_args!("playlist")),
             TrackListType::Track => f.write_fmt(format_args!("track")),
+            TrackListType::Artist => f.write_fmt(format_args!("artist")),
             TrackListType::Unknown => f.write_fmt(format_args!("unknown")),
         }
     }
@@ -31,6 +37,7 @@ impl From<&str> for TrackListType {
             "album" => TrackListType::Album,
             "playlist" => TrackListType::Playlist,
             "track" => TrackListType::Track,
+            "artist" => TrackListType::Artist,
             _ => TrackListType::Unknown,
         }
     }
@@ -44,15 +51,55 @@ where
     vec_values.serialize(s)
 }
 
+/// Drop tracks that can't be streamed in the user's region and
+/// renumber the rest so positions stay contiguous.
+pub fn streamable_tracks(tracks: &BTreeMap<u32, Track>) -> BTreeMap<u32, Track> {
+    let mut queue = BTreeMap::new();
+    let mut position = 1;
+
+    for track in tracks.values() {
+        if !track.available {
+            debug!("skipping unstreamable track {} ({})", track.title, track.id);
+            continue;
+        }
+
+        let mut track = track.clone();
+        track.position = position;
+        queue.insert(position, track);
+
+        position += 1;
+    }
+
+    queue
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TrackListValue {
     #[serde(serialize_with = "serialize_btree")]
     pub queue: BTreeMap<u32, Track>,
     pub album: Option<Album>,
     pub playlist: Option<Playlist>,
+    pub artist: Option<Artist>,
     pub list_type: TrackListType,
 }
 
+/// How long a tracklist plays for, in seconds.
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueDuration {
+    pub total_seconds: u32,
+    pub remaining_seconds: u32,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackListSlice {
+    pub tracks: Vec<Track>,
+    pub offset: usize,
+    pub total: usize,
+    pub current_index: Option<u32>,
+}
+
 impl TrackListValue {
     #[instrument]
     pub fn new(queue: Option<&BTreeMap<u32, Track>>) -> TrackListValue {
@@ -60,18 +107,13 @@ impl TrackListValue {
             queue: queue.unwrap_or(&BTreeMap::new()).clone(),
             album: None,
             playlist: None,
+            artist: None,
             list_type: TrackListType::Unknown,
         }
     }
 
     pub fn total(&self) -> u32 {
-        if let Some(album) = &self.album {
-            album.total_tracks
-        } else if let Some(list) = &self.playlist {
-            list.tracks_count
-        } else {
-            self.queue.len() as u32
-        }
+        self.queue.len() as u32
     }
 
     #[instrument(skip(self))]
@@ -79,6 +121,7 @@ impl TrackListValue {
         self.list_type = TrackListType::Unknown;
         self.album = None;
         self.playlist = None;
+        self.artist = None;
         self.queue.clear();
     }
 
@@ -114,6 +157,17 @@ impl TrackListValue {
         self.playlist.as_ref()
     }
 
+    #[instrument(skip(self))]
+    pub fn set_artist(&mut self, artist: Artist) {
+        self.artist = Some(artist);
+        self.list_type = TrackListType::Artist;
+    }
+
+    #[instrument(skip(self))]
+    pub fn get_artist(&self) -> Option<&Artist> {
+        self.artist.as_ref()
+    }
+
     #[instrument(skip(self))]
     pub fn set_list_type(&mut self, list_type: TrackListType) {
         self.list_type = list_type;
@@ -188,6 +242,196 @@ impl TrackListValue {
             .find(|&track| track.status == TrackStatus::Playing)
     }
 
+    /// Duration of all tracks in the list.
+    pub fn total_duration(&self) -> u32 {
+        self.queue
+            .values()
+            .map(|track| track.duration_seconds)
+            .sum()
+    }
+
+    /// Duration of the unplayed tracks plus the rest of the current track.
+    pub fn remaining_duration(&self, position_seconds: u32) -> u32 {
+        let current = self.current_track().map_or(0, |track| {
+            track.duration_seconds.saturating_sub(position_seconds)
+        });
+        let unplayed = self
+            .queue
+            .values()
+            .filter(|track| track.status == TrackStatus::Unplayed)
+            .map(|track| track.duration_seconds)
+            .sum::<u32>();
+
+        current + unplayed
+    }
+
+    /// Total and remaining duration, `position_seconds` into the current track.
+    pub fn duration(&self, position_seconds: u32) -> QueueDuration {
+        QueueDuration {
+            total_seconds: self.total_duration(),
+            remaining_seconds: self.remaining_duration(position_seconds),
+        }
+    }
+
+    /// The first unplayed track after the given position, in queue order.
+    #[instrument(skip(self))]
+    pub fn next_unplayed(&self, after: u32) -> Option<&Track> {
+        self.queue
+            .range(after + 1..)
+            .map(|(_, track)| track)
+            .find(|track| track.status == TrackStatus::Unplayed)
+    }
+
+    /// Start the first unplayed track after the playing one that `track_url`
+    /// resolves, marking the tracks it can't resolve as unplayable. The playing
+    /// track is only marked as played once the next one is found. Returns the
+    /// new playing track, or `None` when nothing is left to play.
+    pub async fn advance<F, Fut>(&mut self, mut track_url: F) -> Option<Track>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Option<TrackUrl>>,
+    {
+        let previous = self.current_track().map_or(0, |track| track.position);
+        let mut after = previous;
+
+        loop {
+            let mut track = self.next_unplayed(after)?.clone();
+            after = track.position;
+
+            match track_url(track.id).await {
+                Some(url) => {
+                    self.set_track_status(previous, TrackStatus::Played);
+
+                    track.set_track_url(url);
+                    track.status = TrackStatus::Playing;
+                    self.queue.insert(track.position, track.clone());
+
+                    return Some(track);
+                }
+                None => {
+                    debug!("track {} is unplayable, skipping it", track.id);
+                    self.set_track_status(track.position, TrackStatus::Unplayable);
+                }
+            }
+        }
+    }
+
+    /// Returns a window of the queue along with the total
+    /// number of tracks and the position of the current track.
+    #[instrument(skip(self))]
+    pub fn slice(&self, offset: usize, limit: usize) -> TrackListSlice {
+        TrackListSlice {
+            tracks: self
+                .queue
+                .values()
+                .skip(offset)
+                .take(limit)
+                .cloned()
+                .collect::<Vec<Track>>(),
+            offset,
+            total: self.queue.len(),
+            current_index: self.current_track().map(|track| track.position),
+        }
+    }
+
+    /// Insert a track right after the given position, position 0 being the head
+    /// of the queue. A position past the queue adds the track to the end.
+    #[instrument(skip(self, track), fields(track_id = track.id))]
+    pub fn insert_after(&mut self, position: u32, track: Track) {
+        let mut tracks = self.queue.values().cloned().collect::<Vec<Track>>();
+        let index = if position == 0 {
+            0
+        } else {
+            tracks
+                .iter()
+                .position(|t| t.position == position)
+                .map_or(tracks.len(), |index| index + 1)
+        };
+
+        tracks.insert(index, track);
+        self.set_tracks(tracks);
+    }
+
+    /// Add a track to the end of the queue.
+    #[instrument(skip(self, track), fields(track_id = track.id))]
+    pub fn append(&mut self, track: Track) {
+        let mut tracks = self.queue.values().cloned().collect::<Vec<Track>>();
+
+        tracks.push(track);
+        self.set_tracks(tracks);
+    }
+
+    /// Remove the track at the given position, closing the gap it leaves.
+    /// The playing track can't be removed.
+    #[instrument(skip(self))]
+    pub fn remove(&mut self, position: u32) -> Result<Track> {
+        match self.queue.get(&position) {
+            Some(track) if track.status == TrackStatus::Playing => {
+                return Err(Error::RemovePlayingTrack)
+            }
+            Some(_) => {}
+            None => return Err(Error::InvalidQueuePosition { position }),
+        }
+
+        let removed = self
+            .queue
+            .remove(&position)
+            .ok_or(Error::InvalidQueuePosition { position })?;
+        let tracks = self.queue.values().cloned().collect::<Vec<Track>>();
+
+        self.set_tracks(tracks);
+
+        Ok(removed)
+    }
+
+    /// Remove every played track, keeping the playing and unplayed ones.
+    /// Returns the number of removed tracks.
+    #[instrument(skip(self))]
+    pub fn remove_played(&mut self) -> usize {
+        let before = self.queue.len();
+        let tracks = self
+            .queue
+            .values()
+            .filter(|track| track.status != TrackStatus::Played)
+            .cloned()
+            .collect::<Vec<Track>>();
+
+        self.set_tracks(tracks);
+
+        before - self.queue.len()
+    }
+
+    /// Move the track at `from` so it ends up at position `to`.
+    /// Returns false if either position is outside the queue.
+    #[instrument(skip(self))]
+    pub fn move_track(&mut self, from: u32, to: u32) -> bool {
+        let len = self.queue.len() as u32;
+
+        if from == 0 || to == 0 || from > len || to > len {
+            return false;
+        }
+
+        let mut tracks = self.queue.values().cloned().collect::<Vec<Track>>();
+        let track = tracks.remove(from as usize - 1);
+
+        tracks.insert(to as usize - 1, track);
+        self.set_tracks(tracks);
+
+        true
+    }
+
+    /// Replace the queue, keying the tracks by their order starting at 1.
+    fn set_tracks(&mut self, tracks: Vec<Track>) {
+        self.queue = tracks
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut track)| {
+                track.position = index as u32 + 1;
+                (track.position, track)
+            })
+            .collect();
+    }
+
     pub fn cursive_list(&self) -> Vec<(&str, i32)> {
         self.queue
             .values()
@@ -195,3 +439,321 @@ impl TrackListValue {
             .collect::<Vec<(&str, i32)>>()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(id: u32, status: TrackStatus) -> Track {
+        Track {
+            id,
+            number: id,
+            title: format!("Track {id}"),
+            album: None,
+            artist: None,
+            duration_seconds: 100,
+            explicit: false,
+            hires_available: false,
+            sampling_rate: 44.1,
+            bit_depth: 16,
+            status,
+            track_url: None,
+            track_url_expires_at: None,
+            available: true,
+            cover_art: None,
+            position: 0,
+            media_number: 1,
+            format: None,
+            replay_gain: Default::default(),
+        }
+    }
+
+    /// A queue with a track per status, with ids and positions counting from 1.
+    fn track_list(statuses: &[TrackStatus]) -> TrackListValue {
+        let mut track_list = TrackListValue::new(None);
+        track_list.set_tracks(
+            statuses
+                .iter()
+                .enumerate()
+                .map(|(index, status)| track(index as u32 + 1, status.clone()))
+                .collect(),
+        );
+
+        track_list
+    }
+
+    fn statuses(track_list: &TrackListValue) -> Vec<TrackStatus> {
+        track_list
+            .queue
+            .values()
+            .map(|track| track.status.clone())
+            .collect()
+    }
+
+    fn ids(track_list: &TrackListValue) -> Vec<u32> {
+        track_list.queue.values().map(|track| track.id).collect()
+    }
+
+    fn current_position(track_list: &TrackListValue) -> Option<u32> {
+        track_list.current_track().map(|track| track.position)
+    }
+
+    fn track_url(url: &str) -> Option<TrackUrl> {
+        Some(TrackUrl {
+            url: url.to_string(),
+            sampling_rate: 44.1,
+            bit_depth: 16,
+            format: "FLAC".to_string(),
+            expires_at: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn advance_skips_unplayable_tracks() {
+        use TrackStatus::*;
+        let mut track_list = track_list(&[Playing, Unplayed, Unplayed, Unplayed]);
+
+        let track = track_list
+            .advance(|id| async move {
+                if id == 3 {
+                    track_url("https://stream/3")
+                } else {
+                    None
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(track.id, 3);
+        assert_eq!(track.track_url.as_deref(), Some("https://stream/3"));
+        assert_eq!(
+            statuses(&track_list),
+            [Played, Unplayable, Playing, Unplayed]
+        );
+        assert_eq!(track_list.current_track().map(|t| t.position), Some(3));
+    }
+
+    #[tokio::test]
+    async fn advance_leaves_the_playing_track_before_an_unstreamable_tail() {
+        use TrackStatus::*;
+        let mut track_list = track_list(&[Played, Playing, Unplayed, Unplayed]);
+
+        let track = track_list.advance(|_| async { None }).await;
+
+        assert_eq!(track, None);
+        assert_eq!(
+            statuses(&track_list),
+            [Played, Playing, Unplayable, Unplayable]
+        );
+        assert_eq!(track_list.current_track().map(|t| t.position), Some(2));
+    }
+
+    #[tokio::test]
+    async fn advance_on_the_last_track_changes_nothing() {
+        use TrackStatus::*;
+        let mut track_list = track_list(&[Played, Playing]);
+
+        let track = track_list
+            .advance(|_| async { track_url("https://stream") })
+            .await;
+
+        assert_eq!(track, None);
+        assert_eq!(statuses(&track_list), [Played, Playing]);
+    }
+
+    #[test]
+    fn insert_after_the_head() {
+        use TrackStatus::*;
+        let mut track_list = track_list(&[Unplayed, Playing, Unplayed]);
+
+        track_list.insert_after(0, track(4, Unplayed));
+
+        assert_eq!(ids(&track_list), [4, 1, 2, 3]);
+        assert_eq!(
+            track_list.queue.keys().copied().collect::<Vec<_>>(),
+            [1, 2, 3, 4]
+        );
+        assert_eq!(current_position(&track_list), Some(3));
+    }
+
+    #[test]
+    fn insert_after_the_playing_track() {
+        use TrackStatus::*;
+        let mut track_list = track_list(&[Played, Playing, Unplayed]);
+
+        track_list.insert_after(2, track(4, Unplayed));
+
+        assert_eq!(ids(&track_list), [1, 2, 4, 3]);
+        assert_eq!(current_position(&track_list), Some(2));
+        assert_eq!(track_list.next_unplayed(2).map(|t| t.id), Some(4));
+    }
+
+    #[test]
+    fn insert_after_the_tail() {
+        use TrackStatus::*;
+        let mut track_list = track_list(&[Played, Playing, Unplayed]);
+
+        track_list.insert_after(3, track(4, Unplayed));
+        track_list.insert_after(10, track(5, Unplayed));
+
+        assert_eq!(ids(&track_list), [1, 2, 3, 4, 5]);
+        assert_eq!(track_list.find_track_by_index(5).map(|t| t.id), Some(5));
+        assert_eq!(current_position(&track_list), Some(2));
+    }
+
+    #[test]
+    fn remove_a_track_before_the_playing_one() {
+        use TrackStatus::*;
+        let mut track_list = track_list(&[Played, Played, Playing, Unplayed]);
+
+        let removed = track_list.remove(1).unwrap();
+
+        assert_eq!(removed.id, 1);
+        assert_eq!(ids(&track_list), [2, 3, 4]);
+        assert_eq!(
+            track_list.queue.keys().copied().collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        assert_eq!(current_position(&track_list), Some(2));
+    }
+
+    #[test]
+    fn remove_the_last_track() {
+        use TrackStatus::*;
+        let mut track_list = track_list(&[Played, Playing, Unplayed]);
+
+        let removed = track_list.remove(3).unwrap();
+
+        assert_eq!(removed.id, 3);
+        assert_eq!(ids(&track_list), [1, 2]);
+        assert_eq!(current_position(&track_list), Some(2));
+    }
+
+    #[test]
+    fn remove_the_playing_track_is_refused() {
+        use TrackStatus::*;
+        let mut track_list = track_list(&[Played, Playing, Unplayed]);
+
+        assert_eq!(track_list.remove(2), Err(Error::RemovePlayingTrack));
+        assert_eq!(ids(&track_list), [1, 2, 3]);
+        assert_eq!(current_position(&track_list), Some(2));
+    }
+
+    #[test]
+    fn remove_outside_the_queue_is_refused() {
+        use TrackStatus::*;
+        let mut track_list = track_list(&[Playing, Unplayed]);
+
+        assert_eq!(
+            track_list.remove(0),
+            Err(Error::InvalidQueuePosition { position: 0 })
+        );
+        assert_eq!(
+            track_list.remove(3),
+            Err(Error::InvalidQueuePosition { position: 3 })
+        );
+        assert_eq!(ids(&track_list), [1, 2]);
+    }
+
+    #[test]
+    fn move_a_track_up() {
+        use TrackStatus::*;
+        let mut track_list = track_list(&[Played, Playing, Unplayed, Unplayed]);
+
+        assert!(track_list.move_track(4, 1));
+
+        assert_eq!(ids(&track_list), [4, 1, 2, 3]);
+        assert_eq!(current_position(&track_list), Some(3));
+    }
+
+    #[test]
+    fn move_a_track_down() {
+        use TrackStatus::*;
+        let mut track_list = track_list(&[Played, Playing, Unplayed, Unplayed]);
+
+        assert!(track_list.move_track(1, 4));
+
+        assert_eq!(ids(&track_list), [2, 3, 4, 1]);
+        assert_eq!(current_position(&track_list), Some(1));
+    }
+
+    #[test]
+    fn move_a_track_onto_itself() {
+        use TrackStatus::*;
+        let mut track_list = track_list(&[Played, Playing, Unplayed]);
+
+        assert!(track_list.move_track(3, 3));
+
+        assert_eq!(ids(&track_list), [1, 2, 3]);
+        assert_eq!(current_position(&track_list), Some(2));
+    }
+
+    #[test]
+    fn move_the_playing_track() {
+        use TrackStatus::*;
+        let mut track_list = track_list(&[Played, Playing, Unplayed, Unplayed]);
+
+        assert!(track_list.move_track(2, 4));
+
+        assert_eq!(ids(&track_list), [1, 3, 4, 2]);
+        assert_eq!(statuses(&track_list), [Played, Unplayed, Unplayed, Playing]);
+        assert_eq!(current_position(&track_list), Some(4));
+    }
+
+    #[test]
+    fn move_outside_the_queue_is_refused() {
+        use TrackStatus::*;
+        let mut track_list = track_list(&[Playing, Unplayed, Unplayed]);
+
+        assert!(!track_list.move_track(0, 2));
+        assert!(!track_list.move_track(4, 2));
+        assert!(!track_list.move_track(2, 0));
+        assert!(!track_list.move_track(2, 4));
+
+        assert_eq!(ids(&track_list), [1, 2, 3]);
+        assert_eq!(current_position(&track_list), Some(1));
+    }
+
+    #[test]
+    fn empty_queue_has_no_duration() {
+        let track_list = TrackListValue::new(None);
+
+        assert_eq!(track_list.total_duration(), 0);
+        assert_eq!(track_list.remaining_duration(30), 0);
+        assert_eq!(track_list.duration(30), QueueDuration::default());
+    }
+
+    #[test]
+    fn remaining_duration_counts_the_rest_of_the_playing_track() {
+        use TrackStatus::*;
+        let track_list = track_list(&[Played, Played, Playing, Unplayed, Unplayable]);
+
+        assert_eq!(track_list.total_duration(), 500);
+        assert_eq!(track_list.remaining_duration(0), 200);
+        assert_eq!(track_list.remaining_duration(40), 160);
+        assert_eq!(
+            track_list.duration(40),
+            QueueDuration {
+                total_seconds: 500,
+                remaining_seconds: 160,
+            }
+        );
+    }
+
+    #[test]
+    fn remaining_duration_ignores_a_position_past_the_track() {
+        use TrackStatus::*;
+        let track_list = track_list(&[Playing, Unplayed]);
+
+        assert_eq!(track_list.remaining_duration(100), 100);
+        assert_eq!(track_list.remaining_duration(250), 100);
+    }
+
+    #[test]
+    fn remaining_duration_without_a_playing_track() {
+        use TrackStatus::*;
+        let track_list = track_list(&[Played, Unplayed, Unplayed]);
+
+        assert_eq!(track_list.remaining_duration(40), 200);
+    }
+}
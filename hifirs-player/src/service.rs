@@ -1,7 +1,17 @@
 use async_trait::async_trait;
 use hifirs_qobuz_api::client::Image;
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeMap, fmt::Debug};
+use std::{collections::BTreeMap, fmt::Debug, str::FromStr};
+
+use crate::Result;
+
+pub use hifirs_qobuz_api::client::{
+    album::{FeaturedType, ReleaseSort},
+    AudioQuality,
+};
+
+/// Urls expiring within this many seconds are resolved again before they are played.
+const TRACK_URL_EXPIRY_MARGIN_SECONDS: i64 = 60;
 
 #[async_trait]
 pub trait MusicService: Send + Sync + Debug {
@@ -11,14 +21,26 @@ pub trait MusicService: Send + Sync + Debug {
     async fn track(&self, track_id: i32) -> Option<Track>;
     async fn artist(&self, artist_id: i32) -> Option<Artist>;
     async fn artist_releases(&self, artist_id: i32) -> Option<Vec<Album>>;
+    async fn artist_tracks(&self, artist_id: i32) -> Option<Vec<Track>>;
     async fn similar_artists(&self, artist_id: i32) -> Vec<Artist>;
+    async fn label(&self, label_id: u32) -> Option<Label>;
+    async fn genres(&self, parent_id: Option<u32>) -> Result<Vec<Genre>>;
+    async fn featured_albums(
+        &self,
+        featured_type: FeaturedType,
+        genre_id: Option<u32>,
+    ) -> Result<Vec<Album>>;
     async fn playlist(&self, playlist_id: i64) -> Option<Playlist>;
-    async fn search(&self, query: &str) -> Option<SearchResults>;
-    async fn track_url(&self, track_id: i32) -> Option<String>;
+    async fn search(&self, query: &str, limit: u32, offset: u32) -> Option<SearchResults>;
+    async fn search_tracks(&self, query: &str, limit: u32, offset: u32) -> Option<SearchResults>;
+    async fn track_url(&self, track_id: i32) -> Option<TrackUrl>;
     async fn user_playlists(&self) -> Option<Vec<Playlist>>;
     async fn favorites(&self) -> Option<Favorites>;
     async fn add_favorite_album(&self, id: &str);
     async fn remove_favorite_album(&self, id: &str);
+    async fn cover_art(&self, url: &str) -> Option<Vec<u8>>;
+    async fn add_favorite_track(&self, id: &str);
+    async fn remove_favorite_track(&self, id: &str);
     async fn add_favorite_artist(&self, id: &str);
     async fn remove_favorite_artist(&self, id: &str);
     async fn add_favorite_playlist(&self, id: &str);
@@ -50,10 +72,133 @@ pub struct Track {
     pub status: TrackStatus,
     #[serde(skip)]
     pub track_url: Option<String>,
+    /// Unix timestamp after which `track_url` stops working, if it expires.
+    #[serde(skip)]
+    pub track_url_expires_at: Option<i64>,
     pub available: bool,
     pub cover_art: Option<String>,
     pub position: u32,
     pub media_number: u32,
+    pub format: Option<String>,
+    #[serde(default)]
+    pub replay_gain: ReplayGain,
+}
+
+impl Track {
+    /// The highest quality the track can be streamed in, `None` if it can't be streamed.
+    pub fn max_quality(&self) -> Option<AudioQuality> {
+        if !self.available {
+            None
+        } else if self.hires_available && self.bit_depth >= 24 {
+            if self.sampling_rate > 96.0 {
+                Some(AudioQuality::HIFI192)
+            } else {
+                Some(AudioQuality::HIFI96)
+            }
+        } else {
+            Some(AudioQuality::CD)
+        }
+    }
+
+    /// Attach a resolved stream, replacing the maximum available
+    /// format with the one actually being delivered.
+    pub fn set_track_url(&mut self, track_url: TrackUrl) {
+        // Downloaded files do not report their format up front.
+        if track_url.bit_depth > 0 {
+            self.sampling_rate = track_url.sampling_rate;
+            self.bit_depth = track_url.bit_depth;
+        }
+        self.format = Some(track_url.format);
+        self.track_url = Some(track_url.url);
+        self.track_url_expires_at = track_url.expires_at;
+    }
+
+    /// Whether the attached url has expired, or is about to.
+    pub fn track_url_expired(&self) -> bool {
+        self.track_url_expires_at.is_some_and(|expires_at| {
+            chrono::Utc::now().timestamp() + TRACK_URL_EXPIRY_MARGIN_SECONDS >= expires_at
+        })
+    }
+}
+
+/// Loudness metadata for a track, in dB relative to the ReplayGain reference level.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayGain {
+    pub track_gain: Option<f64>,
+    pub track_peak: Option<f64>,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
+}
+
+impl ReplayGain {
+    /// The linear volume to apply for the given mode. Album mode falls back
+    /// to the track gain when there is no album gain, and without any gain
+    /// metadata the volume is left untouched.
+    pub fn volume(&self, mode: ReplayGainMode) -> f64 {
+        let (gain, peak) = match mode {
+            ReplayGainMode::Off => return 1.0,
+            ReplayGainMode::Album if self.album_gain.is_some() => {
+                (self.album_gain, self.album_peak)
+            }
+            ReplayGainMode::Album | ReplayGainMode::Track => (self.track_gain, self.track_peak),
+        };
+
+        let Some(gain) = gain else {
+            return 1.0;
+        };
+
+        let volume = 10_f64.powf(gain / 20.0);
+
+        // Never raise the volume past the point where the peak would clip.
+        match peak {
+            Some(peak) if peak > 0.0 => volume.min(1.0 / peak),
+            _ => volume,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayGainMode {
+    #[default]
+    Off,
+    Track,
+    Album,
+}
+
+impl std::fmt::Display for ReplayGainMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayGainMode::Off => f.write_str("off"),
+            ReplayGainMode::Track => f.write_str("track"),
+            ReplayGainMode::Album => f.write_str("album"),
+        }
+    }
+}
+
+impl FromStr for ReplayGainMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(ReplayGainMode::Off),
+            "track" => Ok(ReplayGainMode::Track),
+            "album" => Ok(ReplayGainMode::Album),
+            _ => Err(format!(
+                "unknown replaygain mode '{s}', expected off, track or album"
+            )),
+        }
+    }
+}
+
+/// A resolved stream along with the format actually being delivered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackUrl {
+    pub url: String,
+    pub sampling_rate: f32,
+    pub bit_depth: u32,
+    pub format: String,
+    pub expires_at: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -63,6 +208,8 @@ pub struct Album {
     pub title: String,
     pub artist: Artist,
     pub release_year: u32,
+    /// The original release date, as `YYYY-MM-DD`.
+    pub release_date: String,
     pub hires_available: bool,
     pub explicit: bool,
     pub total_tracks: u32,
@@ -72,9 +219,37 @@ pub struct Album {
     pub cover_art_small: String,
 }
 
+impl Album {
+    /// Sort `albums` in the `sort` order.
+    pub fn sort(albums: &mut [Album], sort: ReleaseSort) {
+        match sort {
+            ReleaseSort::ReleaseDate => albums.sort_by(|a, b| b.release_date.cmp(&a.release_date)),
+            ReleaseSort::Title => albums.sort_by_key(|album| album.title.to_lowercase()),
+            ReleaseSort::TrackCount => albums.sort_by(|a, b| b.total_tracks.cmp(&a.total_tracks)),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Label {
+    pub id: u32,
+    pub name: String,
+    pub albums: Vec<Album>,
+}
+
+/// A genre, with the id of the genre it belongs to when it is not a top level one.
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Genre {
+    pub id: u32,
+    pub name: String,
+    pub parent_id: Option<u32>,
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResults {
     pub query: String,
+    pub total: u32,
     pub albums: Vec<Album>,
     pub tracks: Vec<Track>,
     pub artists: Vec<Artist>,
@@ -106,3 +281,83 @@ pub struct Playlist {
     pub cover_art: Option<String>,
     pub tracks: BTreeMap<u32, Track>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACK_VOLUME: f64 = 0.5011872336272722; // -6 dB
+    const ALBUM_VOLUME: f64 = 0.7079457843841379; // -3 dB
+
+    fn replay_gain() -> ReplayGain {
+        ReplayGain {
+            track_gain: Some(-6.0),
+            track_peak: Some(0.5),
+            album_gain: Some(-3.0),
+            album_peak: Some(0.5),
+        }
+    }
+
+    fn assert_volume(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn off_leaves_the_volume_untouched() {
+        assert_volume(replay_gain().volume(ReplayGainMode::Off), 1.0);
+    }
+
+    #[test]
+    fn track_mode_uses_the_track_gain() {
+        assert_volume(replay_gain().volume(ReplayGainMode::Track), TRACK_VOLUME);
+    }
+
+    #[test]
+    fn album_mode_uses_the_album_gain() {
+        assert_volume(replay_gain().volume(ReplayGainMode::Album), ALBUM_VOLUME);
+    }
+
+    #[test]
+    fn album_mode_falls_back_to_the_track_gain() {
+        let replay_gain = ReplayGain {
+            album_gain: None,
+            album_peak: None,
+            ..replay_gain()
+        };
+
+        assert_volume(replay_gain.volume(ReplayGainMode::Album), TRACK_VOLUME);
+    }
+
+    #[test]
+    fn volume_is_capped_by_the_peak() {
+        let replay_gain = ReplayGain {
+            track_gain: Some(12.0),
+            track_peak: Some(0.8),
+            ..Default::default()
+        };
+
+        assert_volume(replay_gain.volume(ReplayGainMode::Track), 1.25);
+    }
+
+    #[test]
+    fn missing_peak_does_not_cap_the_volume() {
+        let replay_gain = ReplayGain {
+            track_gain: Some(6.0),
+            track_peak: None,
+            ..Default::default()
+        };
+
+        assert_volume(
+            replay_gain.volume(ReplayGainMode::Track),
+            1.9952623149688795,
+        );
+    }
+
+    #[test]
+    fn missing_gain_leaves_the_volume_untouched() {
+        let replay_gain = ReplayGain::default();
+
+        assert_volume(replay_gain.volume(ReplayGainMode::Track), 1.0);
+        assert_volume(replay_gain.volume(ReplayGainMode::Album), 1.0);
+    }
+}
@@ -1,13 +1,166 @@
-use hifirs_qobuz_api::client::ApiConfig;
+use hifirs_qobuz_api::client::{ApiConfig, AudioQuality};
 use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
 use sqlx::{sqlite::SqliteConnectOptions, Pool, Sqlite, SqlitePool};
 use std::path::PathBuf;
 use tracing::debug;
 
-use crate::{acquire, get_one, query};
+use crate::{
+    acquire, get_one, query,
+    service::{ReplayGainMode, Track},
+};
 
 static POOL: OnceCell<Pool<Sqlite>> = OnceCell::new();
 
+/// The profile whose credentials are used. `None` is the default profile,
+/// whose credentials are stored in the config table.
+static PROFILE: OnceCell<Option<String>> = OnceCell::new();
+
+/// Name of the profile backed by the credentials in the config table.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Number of entries kept in the play history.
+const HISTORY_LIMIT: i64 = 200;
+
+/// Number of queries kept in the search history.
+pub const SEARCH_HISTORY_LIMIT: usize = 50;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub track_id: i64,
+    pub title: String,
+    pub album: Option<String>,
+    pub artist: Option<String>,
+    pub played_at: i64,
+}
+
+/// Where playback was, saved while playing so that it can be resumed later.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaybackPosition {
+    pub list_type: String,
+    pub list_id: Option<String>,
+    pub track_id: i64,
+    pub track_position: i64,
+    pub position_seconds: i64,
+}
+
+/// Settings written by `config export` and read by `config import`.
+/// Credentials are only included when asked for.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigExport {
+    pub username: Option<String>,
+    pub replaygain_mode: Option<String>,
+    #[serde(default)]
+    pub max_quality: Option<String>,
+    #[serde(default)]
+    pub audio_sink: Option<String>,
+    #[serde(default)]
+    pub audio_device: Option<String>,
+    #[serde(default)]
+    pub bit_perfect: Option<bool>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_secret: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web_password: Option<String>,
+}
+
+struct Profile {
+    username: Option<String>,
+    user_token: Option<String>,
+}
+
+fn profile() -> Option<&'static str> {
+    PROFILE.get().and_then(|profile| profile.as_deref())
+}
+
+/// Use the named profile for this run, or the saved one when no name is given.
+pub async fn select_profile(name: Option<String>) {
+    let name = match name {
+        Some(name) => Some(name),
+        None => get_config().await.and_then(|config| config.profile),
+    }
+    .filter(|name| name != DEFAULT_PROFILE);
+
+    if let Some(name) = &name {
+        create_profile(name).await;
+    }
+
+    debug!(
+        "using profile {}",
+        name.as_deref().unwrap_or(DEFAULT_PROFILE)
+    );
+    _ = PROFILE.set(name);
+}
+
+/// The name of the profile in use.
+pub fn active_profile() -> &'static str {
+    profile().unwrap_or(DEFAULT_PROFILE)
+}
+
+/// Save the profile to use when none is selected, creating it if needed.
+pub async fn set_profile(name: &str) {
+    let name = if name == DEFAULT_PROFILE {
+        None
+    } else {
+        create_profile(name).await;
+        Some(name.to_string())
+    };
+
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET profile=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            name
+        );
+    }
+}
+
+/// Names of every profile, the default one first.
+pub async fn profiles() -> Vec<String> {
+    let mut profiles = vec![DEFAULT_PROFILE.to_string()];
+
+    if let Ok(mut conn) = acquire!() {
+        let names = sqlx::query_scalar!(
+            r#"
+            SELECT name FROM profiles
+            ORDER BY name;
+            "#
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap_or_default();
+
+        profiles.extend(names);
+    }
+
+    profiles
+}
+
+async fn create_profile(name: &str) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            INSERT OR IGNORE INTO profiles (name) VALUES (?1);
+            "#,
+            conn,
+            name
+        );
+    }
+}
+
 pub async fn init() {
     let database_url = if let Ok(url) = std::env::var("DATABASE_URL") {
         PathBuf::from(url.replace("sqlite://", ""))
@@ -47,6 +200,23 @@ pub async fn init() {
 
 pub async fn set_username(username: String) {
     if let Ok(mut conn) = acquire!() {
+        if let Some(profile) = profile() {
+            sqlx::query!(
+                r#"
+            UPDATE profiles
+            SET username=?1
+            WHERE name = ?2
+            "#,
+                username,
+                profile
+            )
+            .execute(&mut *conn)
+            .await
+            .expect("database failure");
+
+            return;
+        }
+
         query!(
             r#"
             UPDATE config
@@ -59,22 +229,99 @@ pub async fn set_username(username: String) {
     }
 }
 
-pub async fn set_password(password: String) {
+pub async fn clear_username() {
     if let Ok(mut conn) = acquire!() {
-        query!(
+        if let Some(profile) = profile() {
+            query!(
+                r#"
+            UPDATE profiles
+            SET username=NULL
+            WHERE name = ?1
+            "#,
+                conn,
+                profile
+            );
+
+            return;
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE config
+            SET username=NULL
+            WHERE ROWID = 1
+            "#
+        )
+        .execute(&mut *conn)
+        .await
+        .expect("database failure");
+    }
+}
+
+/// Remove the stored password hash, the user token is all that is kept after logging in.
+pub async fn clear_password() {
+    if let Ok(mut conn) = acquire!() {
+        sqlx::query!(
             r#"
             UPDATE config
-            SET password=?1
+            SET password=NULL
             WHERE ROWID = 1
+            "#
+        )
+        .execute(&mut *conn)
+        .await
+        .expect("database failure");
+    }
+}
+
+pub async fn clear_user_token() {
+    if let Ok(mut conn) = acquire!() {
+        if let Some(profile) = profile() {
+            query!(
+                r#"
+            UPDATE profiles
+            SET user_token=NULL
+            WHERE name = ?1
             "#,
-            conn,
-            password
-        );
+                conn,
+                profile
+            );
+
+            return;
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE config
+            SET user_token=NULL
+            WHERE ROWID = 1
+            "#
+        )
+        .execute(&mut *conn)
+        .await
+        .expect("database failure");
     }
 }
 
 pub async fn set_user_token(token: &String) {
     if let Ok(mut conn) = acquire!() {
+        if let Some(profile) = profile() {
+            sqlx::query!(
+                r#"
+            UPDATE profiles
+            SET user_token=?1
+            WHERE name = ?2
+            "#,
+                token,
+                profile
+            )
+            .execute(&mut *conn)
+            .await
+            .expect("database failure");
+
+            return;
+        }
+
         query!(
             r#"
             UPDATE config
@@ -115,6 +362,265 @@ pub async fn set_active_secret(secret: &String) {
     }
 }
 
+pub async fn set_secrets_updated_at(timestamp: i64) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET secrets_updated_at=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            timestamp
+        );
+    }
+}
+
+pub async fn set_web_password(password: Option<String>) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET web_password=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            password
+        );
+    }
+}
+
+pub async fn set_replaygain_mode(mode: ReplayGainMode) {
+    if let Ok(mut conn) = acquire!() {
+        let mode = mode.to_string();
+        query!(
+            r#"
+            UPDATE config
+            SET replaygain_mode=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            mode
+        );
+    }
+}
+
+/// Cap the streaming quality, `None` removes the cap.
+pub async fn set_max_quality(quality: Option<AudioQuality>) {
+    if let Ok(mut conn) = acquire!() {
+        let quality = quality.map(|quality| quality.name());
+        query!(
+            r#"
+            UPDATE config
+            SET max_quality=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            quality
+        );
+    }
+}
+
+/// Play through this sink and device instead of the default output, `None` for
+/// the sink goes back to the default.
+pub async fn set_audio_output(sink: Option<&str>, device: Option<&str>) {
+    if let Ok(mut conn) = acquire!() {
+        sqlx::query!(
+            r#"
+            UPDATE config
+            SET audio_sink=?1, audio_device=?2
+            WHERE ROWID = 1
+            "#,
+            sink,
+            device
+        )
+        .execute(&mut *conn)
+        .await
+        .expect("database failure");
+    }
+}
+
+/// Send this User-Agent to Qobuz, `None` goes back to the default.
+pub async fn set_user_agent(user_agent: Option<&str>) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET user_agent=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            user_agent
+        );
+    }
+}
+
+/// Play tracks at their native sample rate, without resampling.
+pub async fn set_bit_perfect(enabled: bool) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET bit_perfect=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            enabled
+        );
+    }
+}
+
+/// Whether tracks are played without resampling, off unless set.
+pub async fn bit_perfect() -> bool {
+    get_config()
+        .await
+        .and_then(|config| config.bit_perfect)
+        .unwrap_or_default()
+}
+
+/// The configured ReplayGain mode, off unless set.
+pub async fn replaygain_mode() -> ReplayGainMode {
+    get_config()
+        .await
+        .and_then(|config| config.replaygain_mode)
+        .and_then(|mode| mode.parse().ok())
+        .unwrap_or_default()
+}
+
+/// Record a track that started playing, dropping the oldest entries past the limit.
+pub async fn add_history(track: &Track) {
+    if let Ok(mut conn) = acquire!() {
+        let track_id = track.id as i64;
+        let album = track.album.as_ref().map(|album| album.title.clone());
+        let artist = track.artist.as_ref().map(|artist| artist.name.clone());
+        let played_at = chrono::Utc::now().timestamp();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO history (track_id, title, album, artist, played_at)
+            VALUES (?1, ?2, ?3, ?4, ?5);
+            "#,
+            track_id,
+            track.title,
+            album,
+            artist,
+            played_at
+        )
+        .execute(&mut *conn)
+        .await
+        .expect("database failure");
+
+        let limit = HISTORY_LIMIT;
+        query!(
+            r#"
+            DELETE FROM history
+            WHERE id NOT IN (SELECT id FROM history ORDER BY id DESC LIMIT ?1);
+            "#,
+            conn,
+            limit
+        );
+    }
+}
+
+/// The most recently played tracks, newest first.
+pub async fn history(limit: i64) -> Vec<HistoryEntry> {
+    if let Ok(mut conn) = acquire!() {
+        sqlx::query_as!(
+            HistoryEntry,
+            r#"
+            SELECT id, track_id, title, album, artist, played_at FROM history
+            ORDER BY id DESC
+            LIMIT ?1;
+            "#,
+            limit
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap_or_default()
+    } else {
+        vec![]
+    }
+}
+
+/// Remember a search query, moving it to the front when it was searched before.
+pub async fn add_search(search_query: &str) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            INSERT OR REPLACE INTO search_history (search_query) VALUES (?1);
+            "#,
+            conn,
+            search_query
+        );
+
+        let limit = SEARCH_HISTORY_LIMIT as i64;
+        query!(
+            r#"
+            DELETE FROM search_history
+            WHERE id NOT IN (SELECT id FROM search_history ORDER BY id DESC LIMIT ?1);
+            "#,
+            conn,
+            limit
+        );
+    }
+}
+
+/// Previous search queries, newest first.
+pub async fn search_history() -> Vec<String> {
+    if let Ok(mut conn) = acquire!() {
+        sqlx::query_scalar!(
+            r#"
+            SELECT search_query FROM search_history
+            ORDER BY id DESC;
+            "#
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap_or_default()
+    } else {
+        vec![]
+    }
+}
+
+/// Save where playback is, replacing the previously saved position.
+pub async fn set_playback_position(position: &PlaybackPosition) {
+    if let Ok(mut conn) = acquire!() {
+        sqlx::query!(
+            r#"
+            INSERT OR REPLACE INTO playback_position
+                (id, list_type, list_id, track_id, track_position, position_seconds)
+            VALUES (1, ?1, ?2, ?3, ?4, ?5);
+            "#,
+            position.list_type,
+            position.list_id,
+            position.track_id,
+            position.track_position,
+            position.position_seconds
+        )
+        .execute(&mut *conn)
+        .await
+        .expect("database failure");
+    }
+}
+
+/// The last saved playback position, if anything has been played.
+pub async fn playback_position() -> Option<PlaybackPosition> {
+    if let Ok(mut conn) = acquire!() {
+        get_one!(
+            r#"
+            SELECT list_type, list_id, track_id, track_position, position_seconds
+            FROM playback_position
+            WHERE id = 1;
+            "#,
+            PlaybackPosition,
+            conn
+        )
+        .ok()
+    } else {
+        None
+    }
+}
+
 pub async fn create_config() {
     if let Ok(mut conn) = acquire!() {
         let rowid = 1;
@@ -128,9 +634,10 @@ pub async fn create_config() {
     }
 }
 
+/// The configuration, with the credentials of the profile in use.
 pub async fn get_config() -> Option<ApiConfig> {
     if let Ok(mut conn) = acquire!() {
-        if let Ok(conf) = get_one!(
+        if let Ok(mut conf) = get_one!(
             r#"
             SELECT * FROM config
             WHERE ROWID = 1;
@@ -138,6 +645,25 @@ pub async fn get_config() -> Option<ApiConfig> {
             ApiConfig,
             conn
         ) {
+            if let Some(name) = profile() {
+                let profile = sqlx::query_as!(
+                    Profile,
+                    r#"
+            SELECT username, user_token FROM profiles
+            WHERE name = ?1;
+            "#,
+                    name
+                )
+                .fetch_optional(&mut *conn)
+                .await
+                .ok()
+                .flatten();
+
+                conf.username = profile.as_ref().and_then(|p| p.username.clone());
+                conf.user_token = profile.and_then(|p| p.user_token);
+                conf.password = None;
+            }
+
             Some(conf)
         } else {
             None
@@ -147,6 +673,73 @@ pub async fn get_config() -> Option<ApiConfig> {
     }
 }
 
+/// The current settings, including the token, app_id, secret and
+/// web password hash when `include_secrets` is set.
+pub async fn export_config(include_secrets: bool) -> ConfigExport {
+    let config = get_config().await.unwrap_or_default();
+
+    let mut export = ConfigExport {
+        username: config.username,
+        replaygain_mode: config.replaygain_mode,
+        max_quality: config.max_quality,
+        audio_sink: config.audio_sink,
+        audio_device: config.audio_device,
+        bit_perfect: config.bit_perfect,
+        user_agent: config.user_agent,
+        ..Default::default()
+    };
+
+    if include_secrets {
+        export.user_token = config.user_token;
+        export.app_id = config.app_id;
+        export.active_secret = config.active_secret;
+        export.web_password = config.web_password;
+    }
+
+    export
+}
+
+/// Save every setting that is present in `config`, leaving the others untouched.
+pub async fn import_config(config: ConfigExport) {
+    if let Some(username) = config.username {
+        set_username(username).await;
+    }
+
+    if let Some(mode) = config.replaygain_mode.and_then(|mode| mode.parse().ok()) {
+        set_replaygain_mode(mode).await;
+    }
+
+    if let Some(quality) = config.max_quality.and_then(|quality| quality.parse().ok()) {
+        set_max_quality(Some(quality)).await;
+    }
+
+    if let Some(sink) = config.audio_sink {
+        set_audio_output(Some(&sink), config.audio_device.as_deref()).await;
+    }
+
+    if let Some(enabled) = config.bit_perfect {
+        set_bit_perfect(enabled).await;
+    }
+
+    if let Some(user_agent) = config.user_agent {
+        set_user_agent(Some(&user_agent)).await;
+    }
+
+    if let Some(token) = config.user_token {
+        set_user_token(&token).await;
+    }
+
+    if let (Some(app_id), Some(secret)) = (config.app_id, config.active_secret) {
+        set_app_id(&app_id).await;
+        set_active_secret(&secret).await;
+        set_secrets_updated_at(chrono::Utc::now().timestamp()).await;
+    }
+
+    if let Some(password) = config.web_password {
+        set_web_password(Some(password)).await;
+    }
+}
+
 pub async fn close() {
     POOL.get().unwrap().close().await;
 }
@@ -0,0 +1,116 @@
+//! Album art in the player, drawn with half blocks. Every cell shows two
+//! pixels, the upper one in the foreground color and the lower one in the
+//! background color.
+
+use cursive::{
+    theme::{Color, ColorStyle},
+    Printer, Vec2, View,
+};
+use hifirs_player::service::Track;
+use image::imageops::FilterType;
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Width of the cover in cells.
+const WIDTH: usize = 12;
+/// Height of the cover in cells, each one holding two rows of pixels.
+const HEIGHT: usize = 6;
+/// Most covers kept in memory before the cache is emptied.
+const CACHE_SIZE: usize = 32;
+
+/// Colors of the upper and lower pixel of every cell, row by row.
+pub type Pixels = Arc<Vec<Vec<(Color, Color)>>>;
+
+/// Scaled down covers by album, so they are only downloaded once.
+static CACHE: Lazy<Mutex<HashMap<String, Pixels>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Whether the terminal can show the colors of a cover. Without true color
+/// it would be drawn in the nearest of a few colors, so it is left out.
+pub fn supported() -> bool {
+    std::env::var("COLORTERM").is_ok_and(|value| matches!(value.as_str(), "truecolor" | "24bit"))
+}
+
+/// The album and url of the cover to show for a track, if it has one.
+pub fn source(track: &Track) -> Option<(String, String)> {
+    match &track.album {
+        Some(album) if !album.cover_art_small.is_empty() => {
+            Some((album.id.clone(), album.cover_art_small.clone()))
+        }
+        _ => track.cover_art.clone().map(|url| (url.clone(), url)),
+    }
+}
+
+/// The cover of an album, downloaded and scaled down the first time.
+pub async fn load(album_id: &str, url: &str) -> Option<Pixels> {
+    if let Some(pixels) = CACHE.lock().unwrap().get(album_id) {
+        return Some(pixels.clone());
+    }
+
+    let bytes = hifirs_player::cover_art(url).await?;
+    let pixels = Arc::new(decode(&bytes)?);
+
+    let mut cache = CACHE.lock().unwrap();
+    if cache.len() >= CACHE_SIZE {
+        cache.clear();
+    }
+    cache.insert(album_id.to_string(), pixels.clone());
+
+    Some(pixels)
+}
+
+fn decode(bytes: &[u8]) -> Option<Vec<Vec<(Color, Color)>>> {
+    let image = image::load_from_memory(bytes)
+        .ok()?
+        .resize_exact(WIDTH as u32, HEIGHT as u32 * 2, FilterType::Triangle)
+        .to_rgb8();
+
+    let color = |x: u32, y: u32| {
+        let [r, g, b] = image.get_pixel(x, y).0;
+        Color::Rgb(r, g, b)
+    };
+
+    Some(
+        (0..HEIGHT as u32)
+            .map(|row| {
+                (0..WIDTH as u32)
+                    .map(|x| (color(x, row * 2), color(x, row * 2 + 1)))
+                    .collect()
+            })
+            .collect(),
+    )
+}
+
+/// Shows the cover of the playing album, or an empty space until it is loaded.
+#[derive(Default)]
+pub struct CoverArt {
+    pixels: Option<Pixels>,
+}
+
+impl CoverArt {
+    pub fn set_pixels(&mut self, pixels: Option<Pixels>) {
+        self.pixels = pixels;
+    }
+}
+
+impl View for CoverArt {
+    fn draw(&self, printer: &Printer) {
+        let Some(pixels) = &self.pixels else {
+            return;
+        };
+
+        for (y, row) in pixels.iter().enumerate() {
+            for (x, (upper, lower)) in row.iter().enumerate() {
+                printer.with_color(ColorStyle::new(*upper, *lower), |printer| {
+                    printer.print((x, y), "\u{2580}");
+                });
+            }
+        }
+    }
+
+    fn required_size(&mut self, _constraint: Vec2) -> Vec2 {
+        Vec2::new(WIDTH, HEIGHT)
+    }
+}
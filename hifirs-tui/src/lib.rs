@@ -1,47 +1,51 @@
-use std::{
-    rc::Rc,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, Mutex,
 };
 
 use cursive::{
     align::HAlign,
     direction::Orientation,
-    event::{Event, Key},
+    event::{Event, EventResult, Key},
     reexports::crossbeam_channel::Sender,
     theme::{BorderStyle, ColorStyle, Effect, Palette, Style},
     utils::{markup::StyledString, Counter},
-    view::{Nameable, Position, Resizable, Scrollable, SizeConstraint},
+    view::{Nameable, Position, Resizable, Scrollable, SizeConstraint, View},
     views::{
-        Button, Dialog, EditView, HideableView, Layer, LinearLayout, MenuPopup, NamedView,
-        OnEventView, PaddedView, Panel, ProgressBar, ResizedView, ScreensView, ScrollView,
-        SelectView, TextView,
+        Button, Dialog, EditView, HideableView, Layer, LinearLayout, NamedView, OnEventView,
+        PaddedView, Panel, ProgressBar, ResizedView, ScreensView, ScrollView, SelectView, TextView,
     },
     CbSink, Cursive, CursiveRunnable, With,
 };
 use futures::executor::block_on;
-use gstreamer::{ClockTime, State as GstState};
+use gstreamer::State as GstState;
 use hifirs_player::{
     notification::Notification,
-    queue::TrackListType,
-    service::{Album, Artist, SearchResults, Track, TrackStatus},
+    queue::{QueueDuration, TrackListType, TrackListValue},
+    service::{Album, Artist, ReleaseSort, SearchResults, Track, TrackStatus},
+    sql::db::SEARCH_HISTORY_LIMIT,
 };
 use once_cell::sync::OnceCell;
 use tokio::select;
 use tokio_stream::StreamExt;
 use tracing::debug;
 
+use cover::CoverArt;
+
+mod cover;
+
 type CursiveSender = Sender<Box<dyn FnOnce(&mut Cursive) + Send>>;
 
 static SINK: OnceCell<CursiveSender> = OnceCell::new();
 
 static UNSTREAMABLE: &str = "UNSTREAMABLE";
 static ENTER_URL_OPEN: AtomicBool = AtomicBool::new(false);
+/// Show the cover of the playing album, when the terminal supports it.
+static ALBUM_ART: AtomicBool = AtomicBool::new(false);
 
 pub struct CursiveUI {
     root: CursiveRunnable,
+    mouse: bool,
 }
 
 impl CursiveUI {
@@ -49,6 +53,7 @@ impl CursiveUI {
         let mut siv = cursive::default();
 
         SINK.set(siv.cb_sink().clone()).expect("error setting sink");
+        ALBUM_ART.store(cover::supported(), Ordering::Relaxed);
 
         siv.set_theme(cursive::theme::Theme {
             shadow: false,
@@ -83,7 +88,22 @@ impl CursiveUI {
             }),
         });
 
-        Self { root: siv }
+        Self {
+            root: siv,
+            mouse: true,
+        }
+    }
+
+    /// Capture mouse events for clicking and scrolling. When disabled the
+    /// terminal keeps handling the mouse, so text can be selected as usual.
+    pub fn set_mouse(&mut self, enabled: bool) {
+        self.mouse = enabled;
+    }
+
+    /// Show the cover of the playing album next to the track. Only takes
+    /// effect in terminals with true color.
+    pub fn set_album_art(&mut self, enabled: bool) {
+        ALBUM_ART.store(enabled && cover::supported(), Ordering::Relaxed);
     }
 
     pub fn player(&self) -> LinearLayout {
@@ -145,27 +165,35 @@ impl CursiveUI {
                     .h_align(HAlign::Right)
                     .with_name("sample_rate"),
             )
-            .fixed_width(8);
+            .fixed_width(12);
 
         let counter = Counter::new(0);
         let progress = ProgressBar::new()
             .with_value(counter)
-            .with_label(|value, (_, max)| {
-                let position =
-                    ClockTime::from_seconds(value as u64).to_string().as_str()[2..7].to_string();
-                let duration =
-                    ClockTime::from_seconds(max as u64).to_string().as_str()[2..7].to_string();
-
-                format!("{position} / {duration}")
-            })
+            .with_label(progress_label)
             .with_name("progress");
 
+        if ALBUM_ART.load(Ordering::Relaxed) {
+            track_info.add_child(PaddedView::lrtb(
+                0,
+                1,
+                0,
+                0,
+                CoverArt::default().with_name("cover_art"),
+            ));
+        }
+
         track_info.add_child(track_num);
         track_info.add_child(meta);
         track_info.add_child(player_status);
 
         container.add_child(track_info);
         container.add_child(progress);
+        container.add_child(
+            TextView::new("")
+                .h_align(HAlign::Right)
+                .with_name("queue_duration"),
+        );
 
         let mut track_list: SelectView<usize> = SelectView::new();
 
@@ -220,24 +248,56 @@ impl CursiveUI {
         });
 
         self.root.add_global_callback(' ', move |_| {
-            block_on(async { hifirs_player::play_pause().await.expect("") });
+            block_on(report_error(hifirs_player::play_pause()));
         });
 
         self.root.add_global_callback('N', move |_| {
-            block_on(async { hifirs_player::next().await.expect("") });
+            block_on(report_error(hifirs_player::next()));
         });
 
         self.root.add_global_callback('P', move |_| {
-            block_on(async { hifirs_player::previous().await.expect("") });
+            block_on(report_error(hifirs_player::previous()));
         });
 
         self.root.add_global_callback('l', move |_| {
-            block_on(async { hifirs_player::jump_forward().await.expect("") });
+            block_on(report_error(hifirs_player::jump_forward()));
         });
 
         self.root.add_global_callback('h', move |_| {
-            block_on(async { hifirs_player::jump_backward().await.expect("") });
+            block_on(report_error(hifirs_player::jump_backward()));
         });
+
+        self.root.add_global_callback('C', move |_| {
+            block_on(hifirs_player::clear_played());
+        });
+
+        self.root.add_global_callback('a', move |s| {
+            let album = block_on(async {
+                let track = hifirs_player::current_track().await?;
+                hifirs_player::album_for_track(track.id as i32).await
+            });
+
+            if let Some(album) = album {
+                show_album(s, album);
+            }
+        });
+
+        // Vim style list navigation. Global callbacks only run when the focused
+        // view ignores the key, so typing in the search box is unaffected.
+        let vim_keys = [
+            (Event::Char('j'), Key::Down),
+            (Event::Char('k'), Key::Up),
+            (Event::Char('g'), Key::Home),
+            (Event::Char('G'), Key::End),
+            (Event::CtrlChar('d'), Key::PageDown),
+            (Event::CtrlChar('u'), Key::PageUp),
+        ];
+
+        for (event, key) in vim_keys {
+            self.root.add_global_callback(event, move |s| {
+                s.on_event(Event::Key(key));
+            });
+        }
     }
 
     pub async fn my_playlists(&self) -> NamedView<LinearLayout> {
@@ -286,7 +346,7 @@ impl CursiveUI {
         list_layout.with_name("user_playlist_layout")
     }
 
-    fn search(&mut self) -> LinearLayout {
+    fn search(&mut self, history: Vec<String>) -> LinearLayout {
         let mut layout = LinearLayout::new(Orientation::Vertical);
 
         let on_submit = move |s: &mut Cursive, item: &String| {
@@ -303,11 +363,24 @@ impl CursiveUI {
             .with_name("search_type")
             .wrap_with(Panel::new);
 
+        let history = Arc::new(Mutex::new(SearchHistory::new(history)));
+        let submit_history = history.clone();
+        let older_history = history.clone();
+        let newer_history = history;
+
         let search_form = EditView::new()
             .on_submit_mut(move |_, item| {
                 let item = item.to_string();
 
+                if item.trim().is_empty() {
+                    return;
+                }
+
+                submit_history.lock().unwrap().add(&item);
+
                 tokio::spawn(async move {
+                    hifirs_player::sql::db::add_search(&item).await;
+
                     let results = hifirs_player::search(&item).await;
 
                     SINK.get()
@@ -324,9 +397,19 @@ impl CursiveUI {
                         .expect("failed to send update");
                 });
             })
+            .wrap_with(OnEventView::new)
+            .on_pre_event_inner(Key::Up, move |edit, _| {
+                let query = older_history.lock().unwrap().older()?;
+                Some(EventResult::Consumed(Some(edit.set_content(query))))
+            })
+            .on_pre_event_inner(Key::Down, move |edit, _| {
+                let query = newer_history.lock().unwrap().newer()?;
+                Some(EventResult::Consumed(Some(edit.set_content(query))))
+            })
             .wrap_with(Panel::new);
 
-        let search_results: SelectView<String> = SelectView::new();
+        let search_results: SelectView<String> =
+            SelectView::new().on_select(|s, _| update_results_title(s));
 
         layout.add_child(search_form.title("search"));
         layout.add_child(search_type);
@@ -335,12 +418,15 @@ impl CursiveUI {
             Panel::new(
                 search_results
                     .with_name("search_results")
+                    .wrap_with(OnEventView::new)
+                    .on_event('t', show_selected_album)
                     .scrollable()
                     .scroll_y(true)
                     .scroll_x(true)
                     .resized(SizeConstraint::Free, SizeConstraint::Full),
             )
-            .title("results"),
+            .title("results")
+            .with_name("search_results_panel"),
         );
 
         layout
@@ -476,7 +562,7 @@ impl CursiveUI {
 
     pub async fn run(&mut self) {
         let player = self.player();
-        let search = self.search();
+        let search = self.search(hifirs_player::sql::db::search_history().await);
         let my_playlists = self.my_playlists().await;
 
         self.root
@@ -515,7 +601,20 @@ impl CursiveUI {
 
         self.menubar();
         self.global_events();
-        self.root.run();
+
+        if self.mouse {
+            self.root.run();
+        } else {
+            self.root.run_with(|| {
+                let backend = cursive::backends::crossterm::Backend::init()
+                    .expect("failed to initialize the terminal");
+
+                // The backend always captures the mouse, release it again.
+                _ = crossterm::execute!(std::io::stdout(), crossterm::event::DisableMouseCapture);
+
+                backend
+            });
+        }
     }
 
     pub async fn sink(&self) -> &CbSink {
@@ -530,6 +629,78 @@ impl Default for CursiveUI {
 }
 
 type ResultsPanel = ScrollView<NamedView<SelectView<(i32, Option<String>)>>>;
+type SearchResultsPanel = Panel<ResizedView<ScrollView<NamedView<SelectView<String>>>>>;
+
+/// Formats the selected position in a list as `12/340`.
+fn list_position<T>(list: &SelectView<T>) -> String {
+    let selected = list.selected_id().map(|index| index + 1).unwrap_or(0);
+
+    format!("{selected}/{}", list.len())
+}
+
+/// Previous search queries, cycled through with the arrow keys in the search box
+/// like a shell history. Kept to the same length and order as the saved history.
+struct SearchHistory {
+    /// Newest first.
+    queries: Vec<String>,
+    /// The query currently shown, `None` while typing a new one.
+    index: Option<usize>,
+}
+
+impl SearchHistory {
+    fn new(mut queries: Vec<String>) -> Self {
+        queries.truncate(SEARCH_HISTORY_LIMIT);
+
+        Self {
+            queries,
+            index: None,
+        }
+    }
+
+    /// Moves a query searched before to the front instead of repeating it.
+    fn add(&mut self, query: &str) {
+        self.queries.retain(|q| q != query);
+        self.queries.insert(0, query.to_string());
+        self.queries.truncate(SEARCH_HISTORY_LIMIT);
+        self.index = None;
+    }
+
+    /// The query before the one shown, staying on the oldest.
+    fn older(&mut self) -> Option<String> {
+        let index = self.index.map_or(0, |i| i + 1);
+        let query = self.queries.get(index)?.clone();
+        self.index = Some(index);
+
+        Some(query)
+    }
+
+    /// The query after the one shown, or an empty box after the newest.
+    fn newer(&mut self) -> Option<String> {
+        match self.index? {
+            0 => {
+                self.index = None;
+                Some(String::new())
+            }
+            i => {
+                self.index = Some(i - 1);
+                self.queries.get(i - 1).cloned()
+            }
+        }
+    }
+}
+
+/// Shows the selected position in the search results in the panel title.
+fn update_results_title(s: &mut Cursive) {
+    let position = s.call_on_name("search_results", |list: &mut SelectView<String>| {
+        list_position(list)
+    });
+
+    if let Some(position) = position {
+        s.call_on_name("search_results_panel", |panel: &mut SearchResultsPanel| {
+            panel.set_title(format!("results {position}"));
+        });
+    }
+}
 
 fn load_search_results(item: &str, s: &mut Cursive) {
     if let Some(mut search_results) = s.find_name::<SelectView>("search_results") {
@@ -548,10 +719,19 @@ fn load_search_results(item: &str, s: &mut Cursive) {
                         search_results.add_item(a.list_item(), id);
                     }
 
-                    search_results.set_on_submit(move |_s: &mut Cursive, item: &String| {
+                    search_results.set_on_submit(move |s: &mut Cursive, item: &String| {
                         if item != UNSTREAMABLE {
                             let item = item.clone();
-                            tokio::spawn(async move { hifirs_player::play_album(&item).await });
+                            tokio::spawn(report_error(async move {
+                                hifirs_player::play_album(&item).await
+                            }));
+
+                            s.call_on_name(
+                                "screens",
+                                |screens: &mut ScreensView<ResizedView<LinearLayout>>| {
+                                    screens.set_active_screen(0);
+                                },
+                            );
                         }
                     });
                 }
@@ -607,6 +787,8 @@ fn load_search_results(item: &str, s: &mut Cursive) {
             }
         }
     }
+
+    update_results_title(s);
 }
 
 fn submit_playlist(_s: &mut Cursive, item: u32) -> LinearLayout {
@@ -618,9 +800,11 @@ fn submit_playlist(_s: &mut Cursive, item: u32) -> LinearLayout {
     let mut playlist_items = list.get_inner_mut().get_mut();
 
     for t in &playlist_tracks {
-        let mut row = StyledString::plain(format!("{:02} ", t.position));
+        let number = format!("{:02} ", t.position);
+        let width = list_width().saturating_sub(number.chars().count());
 
-        row.append(t.list_item());
+        let mut row = StyledString::plain(number);
+        row.append(t.fitted_list_item(width));
 
         let track_id = if t.available { t.id as i32 } else { -1 };
 
@@ -643,13 +827,29 @@ fn submit_playlist(_s: &mut Cursive, item: u32) -> LinearLayout {
         submit_track(s, item.clone());
     });
 
+    playlist_items.set_on_select(|s, _| {
+        let position = s.call_on_name(
+            "playlist_items",
+            |list: &mut SelectView<(i32, Option<String>)>| list_position(list),
+        );
+
+        if let Some(position) = position {
+            s.call_on_name("playlist_position", |view: &mut TextView| {
+                view.set_content(format!("track {position}"));
+            });
+        }
+    });
+
+    let position = list_position(&playlist_items);
+
     let meta = LinearLayout::horizontal()
         .child(Button::new("play", move |_s| {
             tokio::spawn(async move { hifirs_player::play_playlist(item as i64).await });
         }))
         .child(
-            TextView::new(format!("total tracks: {}", playlist_tracks.len()))
+            TextView::new(format!("track {position}"))
                 .h_align(HAlign::Right)
+                .with_name("playlist_position")
                 .full_width(),
         );
 
@@ -660,39 +860,138 @@ fn submit_playlist(_s: &mut Cursive, item: u32) -> LinearLayout {
 }
 
 fn submit_artist(s: &mut Cursive, item: i32) {
-    let artist_albums = block_on(async { hifirs_player::artist_albums(item).await });
+    let artist_albums =
+        block_on(async { hifirs_player::artist_albums(item, ReleaseSort::default()).await });
 
     if !artist_albums.is_empty() {
-        let mut tree = cursive::menu::Tree::new();
+        let mut albums: SelectView<String> = SelectView::new();
 
         for a in artist_albums {
-            if !a.available {
-                continue;
+            if a.available {
+                albums.add_item(a.list_item(), a.id.clone());
+            }
+        }
+
+        albums.set_on_submit(move |s: &mut Cursive, id: &String| {
+            if let Some(album) = block_on(hifirs_player::find_album(id)) {
+                show_album(s, album);
             }
+        });
 
-            tree.add_leaf(a.list_item(), move |s: &mut Cursive| {
-                let id = a.id.clone();
-                tokio::spawn(async move { hifirs_player::play_album(&id).await });
+        let panel = Panel::new(
+            albums
+                .scrollable()
+                .scroll_y(true)
+                .resized(SizeConstraint::Full, SizeConstraint::Free),
+        )
+        .title("albums");
 
-                s.call_on_name(
-                    "screens",
-                    |screens: &mut ScreensView<ResizedView<LinearLayout>>| {
-                        screens.set_active_screen(0);
-                    },
-                );
-            });
+        push_browse_layer(s, panel);
+    }
+}
+
+/// Number of layers opened while browsing, such as the albums of an artist
+/// and then the tracks of one of them.
+static BROWSE_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Open a layer on top of the ones browsed so far. Esc goes back to the
+/// previous one.
+fn push_browse_layer<V: View>(s: &mut Cursive, view: V) {
+    BROWSE_DEPTH.fetch_add(1, Ordering::Relaxed);
+
+    let view = OnEventView::new(view).on_event(Event::Key(Key::Esc), |s| {
+        let open = BROWSE_DEPTH.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |depth| {
+            depth.checked_sub(1)
+        });
+
+        if open.is_ok() {
+            s.screen_mut().pop_layer();
         }
+    });
 
-        let album_list: MenuPopup = MenuPopup::new(Rc::new(tree));
+    s.screen_mut().add_layer(view);
+}
 
-        let events = album_list
-            .scrollable()
-            .resized(SizeConstraint::Full, SizeConstraint::Free);
+/// Close every browsed layer, once something was chosen to play.
+fn close_browse_layers(s: &mut Cursive) {
+    for _ in 0..BROWSE_DEPTH.swap(0, Ordering::Relaxed) {
+        s.screen_mut().pop_layer();
+    }
+}
+
+/// Opens the track list of the album selected in the search results, so
+/// playback can start from any of its tracks.
+fn show_selected_album(s: &mut Cursive) {
+    let search_type = s
+        .find_name::<SelectView>("search_type")
+        .and_then(|view| view.selection());
+
+    if search_type.as_deref().map(String::as_str) != Some("Albums") {
+        return;
+    }
+
+    let Some(id) = s
+        .find_name::<SelectView>("search_results")
+        .and_then(|view| view.selection())
+    else {
+        return;
+    };
+
+    if id.as_str() == UNSTREAMABLE {
+        return;
+    }
 
-        s.screen_mut().add_layer(events);
+    if let Some(album) = block_on(hifirs_player::find_album(&id)) {
+        show_album(s, album);
     }
 }
 
+/// Lists the tracks of an album, playing the album from the selected track.
+fn show_album(s: &mut Cursive, album: Album) {
+    let mut tracks: SelectView<u32> = SelectView::new();
+
+    for t in album.tracks.values() {
+        let number = format!("{:02} ", t.position);
+        let width = list_width().saturating_sub(number.chars().count());
+
+        let mut row = StyledString::plain(number);
+        row.append(t.fitted_list_item(width));
+
+        tracks.add_item(row, t.position);
+    }
+
+    let album_id = album.id.clone();
+    tracks.set_on_submit(move |s: &mut Cursive, position: &u32| {
+        let id = album_id.clone();
+        let position = *position;
+
+        close_browse_layers(s);
+
+        tokio::spawn(async move {
+            if hifirs_player::play_album(&id).await.is_ok() {
+                report_error(hifirs_player::skip(position, true)).await;
+            }
+        });
+
+        s.call_on_name(
+            "screens",
+            |screens: &mut ScreensView<ResizedView<LinearLayout>>| {
+                screens.set_active_screen(0);
+            },
+        );
+    });
+
+    let panel = Panel::new(
+        tracks
+            .scrollable()
+            .scroll_y(true)
+            .resized(SizeConstraint::Full, SizeConstraint::Free),
+    )
+    .title(format!("{} by {}", album.title, album.artist.name));
+
+    push_browse_layer(s, panel);
+}
+
 fn submit_track(s: &mut Cursive, item: (i32, Option<String>)) {
     if item.0 == -1 {
         return;
@@ -768,6 +1067,9 @@ fn set_current_track(s: &mut Cursive, track: &Track, lt: &TrackListType) {
             TrackListType::Track => {
                 track_num.set_content(format!("{:03}", track.number));
             }
+            TrackListType::Artist => {
+                track_num.set_content(format!("{:03}", track.position));
+            }
             TrackListType::Unknown => {
                 track_num.set_content(format!("{:03}", track.position));
             }
@@ -787,9 +1089,203 @@ fn set_current_track(s: &mut Cursive, track: &Track, lt: &TrackListType) {
         s.find_name::<TextView>("bit_depth"),
         s.find_name::<TextView>("sample_rate"),
     ) {
-        bit_depth.set_content(format!("{} bits", track.bit_depth));
-        sample_rate.set_content(format!("{} kHz", track.sampling_rate));
+        match track.format.as_deref() {
+            Some("MP3") => bit_depth.set_content("MP3"),
+            Some(format) => bit_depth.set_content(format!("{}-bit {}", track.bit_depth, format)),
+            None => bit_depth.set_content(format!("{} bits", track.bit_depth)),
+        }
+        sample_rate.set_content(sample_rate_label(
+            track.sampling_rate,
+            hifirs_player::output_rate(),
+        ));
+    }
+}
+
+/// Lists the unplayed tracks of `list`, followed by the played ones.
+fn fill_track_list(s: &mut Cursive, list: &TrackListValue) {
+    if let Some(mut list_view) = s.find_name::<ScrollView<SelectView<usize>>>("current_track_list")
+    {
+        list_view.get_inner_mut().clear();
+
+        list.unplayed_tracks().iter().for_each(|i| {
+            list_view.get_inner_mut().add_item(
+                i.track_list_item(list.list_type(), false),
+                i.position as usize,
+            );
+        });
+
+        list.played_tracks().iter().for_each(|i| {
+            list_view.get_inner_mut().add_item(
+                i.track_list_item(list.list_type(), true),
+                i.position as usize,
+            );
+        });
+    }
+}
+
+fn set_playing_track(s: &mut Cursive, list: &TrackListValue) {
+    if let Some(track) = list
+        .queue
+        .values()
+        .find(|t| t.status == TrackStatus::Playing)
+    {
+        set_current_track(s, track, list.list_type());
+    }
+}
+
+/// Shows a playlist or an artist's tracks, titled by the playlist or artist name.
+fn show_track_list(s: &mut Cursive, list: &TrackListValue, title: Option<&str>) {
+    fill_track_list(s, list);
+
+    if let (Some(title), Some(mut entity_title), Some(mut total_tracks)) = (
+        title,
+        s.find_name::<TextView>("entity_title"),
+        s.find_name::<TextView>("total_tracks"),
+    ) {
+        entity_title.set_content(title);
+        total_tracks.set_content(format!("{:03}", list.total()));
     }
+
+    set_playing_track(s, list);
+}
+
+/// The sample rate of a track, followed by the rate of the output when it differs.
+fn sample_rate_label(sampling_rate: f32, output_rate: Option<u32>) -> String {
+    match output_rate.map(|rate| rate as f32 / 1000.0) {
+        Some(output_rate) if sampling_rate > 0.0 && output_rate != sampling_rate => {
+            format!("{sampling_rate} kHz \u{2192} {output_rate} kHz")
+        }
+        _ => format!("{sampling_rate} kHz"),
+    }
+}
+
+/// Logs a failed player action and broadcasts it so it can be displayed.
+async fn report_error(action: impl std::future::Future<Output = hifirs_player::Result<()>>) {
+    if let Err(error) = action.await {
+        debug!(?error);
+        hifirs_player::broadcast_error(error).await;
+    }
+}
+
+/// Show the cover of the album the track belongs to, loading it in the background.
+fn update_cover_art(track: Option<&Track>) {
+    if !ALBUM_ART.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let source = track.and_then(cover::source);
+
+    tokio::spawn(async move {
+        let pixels = match source {
+            Some((album_id, url)) => cover::load(&album_id, &url).await,
+            None => None,
+        };
+
+        SINK.get()
+            .unwrap()
+            .send(Box::new(move |s| {
+                s.call_on_name("cover_art", |view: &mut CoverArt| {
+                    view.set_pixels(pixels);
+                });
+            }))
+            .expect("failed to send update");
+    });
+}
+
+/// Show how much of the queue is left, below the progress bar.
+fn set_queue_duration(s: &mut Cursive, duration: QueueDuration) {
+    s.call_on_name("queue_duration", |view: &mut TextView| {
+        if duration.total_seconds == 0 {
+            view.set_content("");
+        } else {
+            view.set_content(format!(
+                "{} left of {}",
+                format_hours(duration.remaining_seconds as u64),
+                format_hours(duration.total_seconds as u64)
+            ));
+        }
+    });
+}
+
+/// Label of the progress bar while playing, `position / duration`.
+fn progress_label(value: usize, (_, max): (usize, usize)) -> String {
+    let position = format_duration(value as u64);
+    let duration = format_duration(max as u64);
+
+    format!("{position} / {duration}")
+}
+
+/// Formats a duration as `mm:ss`, or `h:mm:ss` once it reaches an hour.
+fn format_duration(seconds: u64) -> String {
+    let hours = seconds / 3600;
+    let minutes = (seconds % 3600) / 60;
+    let seconds = seconds % 60;
+
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes:02}:{seconds:02}")
+    }
+}
+
+/// Formats a duration as `h:mm:ss`, for lengths that may span hours.
+fn format_hours(seconds: u64) -> String {
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+/// Columns around a list taken by its panel border and scrollbar.
+const LIST_MARGIN: usize = 4;
+/// Fewest characters of a title shown, however narrow the terminal.
+const MIN_TITLE_WIDTH: usize = 12;
+/// Fewest characters of an artist name shown, however narrow the terminal.
+const MIN_ARTIST_WIDTH: usize = 8;
+
+/// Columns a list item can fill in the current terminal.
+fn list_width() -> usize {
+    crossterm::terminal::size()
+        .map_or(80, |(columns, _)| columns as usize)
+        .saturating_sub(LIST_MARGIN)
+}
+
+/// Widths to cut a title and an artist name to, so that the `reserved` columns
+/// for the rest of the item, like the duration and flags, fit in `width`. The
+/// artist gets up to a third of the room and the title what is left of it.
+fn column_widths(width: usize, reserved: usize, title: &str, artist: &str) -> (usize, usize) {
+    let room = width.saturating_sub(reserved);
+    let title_len = title.chars().count();
+    let artist_len = artist.chars().count();
+
+    if title_len + artist_len <= room {
+        return (title_len, artist_len);
+    }
+
+    let artist_width = artist_len.min((room / 3).max(MIN_ARTIST_WIDTH));
+    let title_width = room.saturating_sub(artist_width).max(MIN_TITLE_WIDTH);
+
+    (title_width, artist_width)
+}
+
+/// Shortens `text` to at most `width` characters, ending it with an ellipsis
+/// when cut, so that durations and flags after it stay visible.
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+
+    let mut truncated = text
+        .chars()
+        .take(width.saturating_sub(1))
+        .collect::<String>()
+        .trim_end()
+        .to_string();
+    truncated.push('…');
+
+    truncated
 }
 
 fn get_state_icon(state: GstState) -> String {
@@ -810,6 +1306,13 @@ fn get_state_icon(state: GstState) -> String {
     }
 }
 
+/// Close the TUI, as if the user quit it.
+pub fn quit() {
+    if let Some(sink) = SINK.get() {
+        _ = sink.send(Box::new(|s| s.quit()));
+    }
+}
+
 pub async fn receive_notifications() {
     let mut receiver = hifirs_player::notify_receiver();
 
@@ -837,7 +1340,11 @@ pub async fn receive_notifications() {
                             .unwrap()
                             .send(Box::new(move |s| {
                                 if let Some(mut view) = s.find_name::<TextView>("player_status") {
-                                    view.set_content(get_state_icon(status));
+                                    // Keep showing the buffering progress until it is done.
+                                    if !hifirs_player::is_buffering() {
+                                        view.set_content(get_state_icon(status));
+                                    }
+
                                     match status {
                                         GstState::Ready => {
                                             s.call_on_name("progress", |progress: &mut ProgressBar| {
@@ -856,42 +1363,36 @@ pub async fn receive_notifications() {
                             .expect("failed to send update");
                     }
                     Notification::Position { clock } => {
+                        let duration = hifirs_player::queue_duration().await;
+
                         SINK.get()
                             .unwrap()
                             .send(Box::new(move |s| {
                                 if let Some(mut progress) = s.find_name::<ProgressBar>("progress") {
                                     progress.set_value(clock.seconds() as usize);
                                 }
+
+                                set_queue_duration(s, duration);
                             }))
                             .expect("failed to send update");
                     }
                     Notification::CurrentTrackList { list } => {
+                        let duration = hifirs_player::queue_duration().await;
+
+                        SINK.get()
+                            .unwrap()
+                            .send(Box::new(move |s| set_queue_duration(s, duration)))
+                            .expect("failed to send update");
+
+                        update_cover_art(list.current_track());
+
                         match list.list_type() {
                             TrackListType::Album => {
                                 SINK.get()
                                     .unwrap()
                                     .send(Box::new(move |s| {
-                                        if let Some(mut list_view) = s
-                                            .find_name::<ScrollView<SelectView<usize>>>(
-                                                "current_track_list",
-                                            )
-                                        {
-                                            list_view.get_inner_mut().clear();
+                                        fill_track_list(s, &list);
 
-                                            list.unplayed_tracks().iter().for_each(|i| {
-                                                list_view.get_inner_mut().add_item(
-                                                    i.track_list_item(list.list_type(), false),
-                                                    i.position as usize,
-                                                );
-                                            });
-
-                                            list.played_tracks().iter().for_each(|i| {
-                                                list_view.get_inner_mut().add_item(
-                                                    i.track_list_item(list.list_type(), true),
-                                                    i.position as usize,
-                                                );
-                                            });
-                                        }
                                         if let (
                                             Some(album),
                                             Some(mut entity_title),
@@ -913,12 +1414,7 @@ pub async fn receive_notifications() {
                                                 .set_content(format!("{:03}", album.total_tracks));
                                         }
 
-                                        for t in list.queue.values() {
-                                            if t.status == TrackStatus::Playing {
-                                                set_current_track(s, t, list.list_type());
-                                                break;
-                                            }
-                                        }
+                                        set_playing_track(s, &list);
                                     }))
                                     .expect("failed to send update");
                             }
@@ -926,51 +1422,16 @@ pub async fn receive_notifications() {
                                 SINK.get()
                                     .unwrap()
                                     .send(Box::new(move |s| {
-                                        if let Some(mut list_view) = s
-                                            .find_name::<ScrollView<SelectView<usize>>>(
-                                                "current_track_list",
-                                            )
-                                        {
-                                            list_view.get_inner_mut().clear();
-
-                                            list.unplayed_tracks().iter().for_each(|i| {
-                                                list_view.get_inner_mut().add_item(
-                                                    i.track_list_item(list.list_type(), false),
-                                                    i.position as usize,
-                                                );
-                                            });
-
-                                            list.played_tracks().iter().for_each(|i| {
-                                                list_view.get_inner_mut().add_item(
-                                                    i.track_list_item(list.list_type(), true),
-                                                    i.position as usize,
-                                                );
-                                            });
-                                        }
+                                        let playlist = list.get_playlist();
 
-                                        if let (
-                                            Some(playlist),
-                                            Some(mut entity_title),
-                                            Some(mut total_tracks),
-                                        ) = (
-                                            list.get_playlist(),
-                                            s.find_name::<TextView>("entity_title"),
-                                            s.find_name::<TextView>("total_tracks"),
-                                        ) {
-                                            if let Some(first) = playlist.tracks.first_key_value() {
-                                                set_current_track(s, first.1, list.list_type());
-                                            }
-
-                                            entity_title.set_content(&playlist.title);
-                                            total_tracks.set_content(format!("{:03}", list.total()));
+                                        if let Some(first) =
+                                            playlist.and_then(|p| p.tracks.first_key_value())
+                                        {
+                                            set_current_track(s, first.1, list.list_type());
                                         }
 
-                                        for t in list.queue.values() {
-                                            if t.status == TrackStatus::Playing {
-                                                set_current_track(s, t, list.list_type());
-                                                break;
-                                            }
-                                        }
+                                        let title = playlist.map(|p| p.title.as_str());
+                                        show_track_list(s, &list, title);
                                     }))
                                     .expect("failed to send update");
                             }
@@ -997,12 +1458,16 @@ pub async fn receive_notifications() {
                                             total_tracks.set_content("001");
                                         }
 
-                                        for t in list.queue.values() {
-                                            if t.status == TrackStatus::Playing {
-                                                set_current_track(s, t, list.list_type());
-                                                break;
-                                            }
-                                        }
+                                        set_playing_track(s, &list);
+                                    }))
+                                    .expect("failed to send update");
+                            }
+                            TrackListType::Artist => {
+                                SINK.get()
+                                    .unwrap()
+                                    .send(Box::new(move |s| {
+                                        let artist = list.get_artist().map(|a| a.name.as_str());
+                                        show_track_list(s, &list, artist);
                                     }))
                                     .expect("failed to send update");
                             }
@@ -1024,11 +1489,43 @@ pub async fn receive_notifications() {
                                         view.set_content(get_state_icon(target_state));
                                     }
                                 });
+                                s.call_on_name("progress", |progress: &mut ProgressBar| {
+                                    if is_buffering {
+                                        progress.set_label(move |_, _| {
+                                            format!("Buffering… {percent}%")
+                                        });
+                                    } else {
+                                        progress.set_label(progress_label);
+                                    }
+                                });
+                            }))
+                            .expect("failed to send update");
+                    }
+                    Notification::Error { error } => {
+                        SINK.get()
+                            .unwrap()
+                            .send(Box::new(move |s| {
+                                s.add_layer(Dialog::info(error.to_string()));
                             }))
                             .expect("failed to send update");
                     }
-                    Notification::Error { error: _ } => {}
                     Notification::Volume{ volume: _ } => {}
+                    Notification::SleepTimer { remaining_seconds: _ } => {}
+                    Notification::OutputRate { rate } => {
+                        if let Some(track) = hifirs_player::current_track().await {
+                            SINK.get()
+                                .unwrap()
+                                .send(Box::new(move |s| {
+                                    s.call_on_name("sample_rate", |view: &mut TextView| {
+                                        view.set_content(sample_rate_label(
+                                            track.sampling_rate,
+                                            Some(rate),
+                                        ));
+                                    });
+                                }))
+                                .expect("failed to send update");
+                        }
+                    }
                 }
             }
         }
@@ -1036,31 +1533,44 @@ pub async fn receive_notifications() {
 }
 
 pub trait CursiveFormat {
-    fn list_item(&self) -> StyledString;
+    /// The item as shown in lists, fit to the width of the terminal.
+    fn list_item(&self) -> StyledString {
+        self.fitted_list_item(list_width())
+    }
+    /// The item as shown in lists, fit to `width` columns.
+    fn fitted_list_item(&self, width: usize) -> StyledString;
     fn track_list_item(&self, _list_type: &TrackListType, _inactive: bool) -> StyledString {
         StyledString::new()
     }
 }
 
 impl CursiveFormat for Track {
-    fn list_item(&self) -> StyledString {
+    fn fitted_list_item(&self, width: usize) -> StyledString {
         let mut style = Style::none();
 
         if !self.available {
             style = style.combine(Effect::Dim).combine(Effect::Strikethrough);
         }
 
-        let mut title = StyledString::styled(self.title.trim(), style.combine(Effect::Bold));
+        let duration = format_duration(self.duration_seconds as u64);
+        let artist = self.artist.as_ref().map(|artist| artist.name.as_str());
+        let track_title = self.title.trim();
 
-        if let Some(artist) = &self.artist {
+        // " by ", then a space on each side of the duration and two flags.
+        let reserved = artist.map_or(0, |_| 4) + duration.chars().count() + 4;
+        let (title_width, artist_width) =
+            column_widths(width, reserved, track_title, artist.unwrap_or_default());
+
+        let mut title = StyledString::styled(
+            truncate(track_title, title_width),
+            style.combine(Effect::Bold),
+        );
+
+        if let Some(artist) = artist {
             title.append_styled(" by ", style);
-            title.append_styled(&artist.name, style);
+            title.append_styled(truncate(artist, artist_width), style);
         }
 
-        let duration = ClockTime::from_seconds(self.duration_seconds as u64)
-            .to_string()
-            .as_str()[2..7]
-            .to_string();
         title.append_plain(" ");
         title.append_styled(duration, style.combine(Effect::Dim));
         title.append_plain(" ");
@@ -1089,18 +1599,21 @@ impl CursiveFormat for Track {
             TrackListType::Album => self.number,
             TrackListType::Playlist => self.position,
             TrackListType::Track => self.number,
+            TrackListType::Artist => self.position,
             TrackListType::Unknown => self.position,
         };
 
-        let mut item = StyledString::styled(format!("{:02} ", num), style);
-        item.append_styled(self.title.trim(), style.combine(Effect::Simple));
-        item.append_plain(" ");
-
-        let duration = ClockTime::from_seconds(self.duration_seconds as u64)
-            .to_string()
-            .as_str()[2..7]
-            .to_string();
+        let number = format!("{:02} ", num);
+        let duration = format_duration(self.duration_seconds as u64);
+        let reserved = number.chars().count() + duration.chars().count() + 1;
+        let (title_width, _) = column_widths(list_width(), reserved, self.title.trim(), "");
 
+        let mut item = StyledString::styled(number, style);
+        item.append_styled(
+            truncate(self.title.trim(), title_width),
+            style.combine(Effect::Simple),
+        );
+        item.append_plain(" ");
         item.append_styled(duration, style.combine(Effect::Dim));
 
         item
@@ -1108,20 +1621,30 @@ impl CursiveFormat for Track {
 }
 
 impl CursiveFormat for Album {
-    fn list_item(&self) -> StyledString {
+    fn fitted_list_item(&self, width: usize) -> StyledString {
         let mut style = Style::none();
 
         if !self.available {
             style = style.combine(Effect::Dim).combine(Effect::Strikethrough);
         }
 
-        let mut title = StyledString::styled(self.title.as_str(), style.combine(Effect::Bold));
+        let release_year = self.release_year.to_string();
+
+        // " by ", then a space on each side of the year and two flags.
+        let reserved = 4 + release_year.chars().count() + 4;
+        let (title_width, artist_width) =
+            column_widths(width, reserved, &self.title, &self.artist.name);
+
+        let mut title = StyledString::styled(
+            truncate(&self.title, title_width),
+            style.combine(Effect::Bold),
+        );
 
         title.append_styled(" by ", style);
-        title.append_styled(self.artist.name.as_str(), style);
+        title.append_styled(truncate(&self.artist.name, artist_width), style);
         title.append_styled(" ", style);
 
-        title.append_styled(self.release_year.to_string(), style.combine(Effect::Dim));
+        title.append_styled(release_year, style.combine(Effect::Dim));
         title.append_plain(" ");
 
         if self.explicit {
@@ -1137,7 +1660,206 @@ impl CursiveFormat for Album {
 }
 
 impl CursiveFormat for Artist {
-    fn list_item(&self) -> StyledString {
-        StyledString::plain(self.name.as_str())
+    fn fitted_list_item(&self, width: usize) -> StyledString {
+        StyledString::plain(truncate(&self.name, width.max(MIN_ARTIST_WIDTH)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    const LONG_TITLE: &str =
+        "Symphony No. 9 in D Minor, Op. 125 \"Choral\": IV. Finale. Presto – Allegro assai";
+    const LONG_ARTIST: &str = "Berliner Philharmoniker & Herbert von Karajan";
+
+    fn artist(name: &str) -> Artist {
+        Artist {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn track(title: &str, artist_name: &str) -> Track {
+        Track {
+            id: 1,
+            number: 4,
+            title: title.to_string(),
+            album: None,
+            artist: Some(artist(artist_name)),
+            duration_seconds: 225,
+            explicit: true,
+            hires_available: true,
+            sampling_rate: 96.0,
+            bit_depth: 24,
+            status: TrackStatus::Unplayed,
+            track_url: None,
+            track_url_expires_at: None,
+            available: true,
+            cover_art: None,
+            position: 4,
+            media_number: 1,
+            format: None,
+            replay_gain: Default::default(),
+        }
+    }
+
+    fn album(title: &str, artist_name: &str) -> Album {
+        Album {
+            id: "1".to_string(),
+            title: title.to_string(),
+            artist: artist(artist_name),
+            release_year: 1963,
+            release_date: "1963-01-01".to_string(),
+            hires_available: true,
+            explicit: true,
+            total_tracks: 4,
+            tracks: BTreeMap::new(),
+            available: true,
+            cover_art: String::new(),
+            cover_art_small: String::new(),
+        }
+    }
+
+    fn width(item: &StyledString) -> usize {
+        item.source().chars().count()
+    }
+
+    #[test]
+    fn track_fits_in_40_columns() {
+        let item = track(LONG_TITLE, LONG_ARTIST).fitted_list_item(40);
+
+        assert!(width(&item) <= 40, "{:?}", item.source());
+        assert!(item.source().ends_with(" 03:45 e*"), "{:?}", item.source());
+        assert!(item.source().contains('…'));
+    }
+
+    #[test]
+    fn album_fits_in_40_columns() {
+        let item = album(LONG_TITLE, LONG_ARTIST).fitted_list_item(40);
+
+        assert!(width(&item) <= 40, "{:?}", item.source());
+        assert!(item.source().ends_with(" 1963 e*"), "{:?}", item.source());
+    }
+
+    #[test]
+    fn track_is_not_cut_in_200_columns() {
+        let item = track(LONG_TITLE, LONG_ARTIST).fitted_list_item(200);
+
+        assert_eq!(
+            item.source(),
+            format!("{LONG_TITLE} by {LONG_ARTIST} 03:45 e*")
+        );
+    }
+
+    #[test]
+    fn album_is_not_cut_in_200_columns() {
+        let item = album(LONG_TITLE, LONG_ARTIST).fitted_list_item(200);
+
+        assert_eq!(
+            item.source(),
+            format!("{LONG_TITLE} by {LONG_ARTIST} 1963 e*")
+        );
+    }
+
+    #[test]
+    fn short_artist_leaves_room_for_the_title() {
+        let (title, artist) = column_widths(40, 12, LONG_TITLE, "Muse");
+
+        assert_eq!(artist, 4);
+        assert_eq!(title, 24);
+    }
+
+    #[test]
+    fn formats_durations_under_an_hour() {
+        assert_eq!(format_duration(0), "00:00");
+        assert_eq!(format_duration(3599), "59:59");
+    }
+
+    #[test]
+    fn formats_durations_from_an_hour() {
+        assert_eq!(format_duration(3600), "1:00:00");
+        assert_eq!(format_duration(2 * 3600 + 61), "2:01:01");
+        assert_eq!(format_duration(10 * 3600), "10:00:00");
+        assert_eq!(format_duration(10 * 3600 + 59 * 60 + 59), "10:59:59");
+    }
+
+    #[test]
+    fn labels_the_progress_of_long_tracks() {
+        assert_eq!(progress_label(1800, (0, 3600)), "30:00 / 1:00:00");
+        assert_eq!(progress_label(3600, (0, 2 * 3600)), "1:00:00 / 2:00:00");
+        assert_eq!(
+            progress_label(9 * 3600 + 1, (0, 10 * 3600)),
+            "9:00:01 / 10:00:00"
+        );
+    }
+
+    #[test]
+    fn columns_keep_a_minimum_width() {
+        let (title, artist) = column_widths(10, 12, LONG_TITLE, LONG_ARTIST);
+
+        assert_eq!(title, MIN_TITLE_WIDTH);
+        assert_eq!(artist, MIN_ARTIST_WIDTH);
+    }
+
+    fn search_history(queries: &[&str]) -> SearchHistory {
+        SearchHistory::new(queries.iter().map(|q| q.to_string()).collect())
+    }
+
+    #[test]
+    fn search_history_walks_back_to_the_oldest_query() {
+        let mut history = search_history(&["newest", "oldest"]);
+
+        assert_eq!(history.older().as_deref(), Some("newest"));
+        assert_eq!(history.older().as_deref(), Some("oldest"));
+        assert_eq!(history.older(), None);
+        assert_eq!(history.newer().as_deref(), Some("newest"));
+    }
+
+    #[test]
+    fn search_history_walks_forward_to_an_empty_box() {
+        let mut history = search_history(&["newest", "oldest"]);
+
+        assert_eq!(history.newer(), None);
+
+        history.older();
+        history.older();
+
+        assert_eq!(history.newer().as_deref(), Some("newest"));
+        assert_eq!(history.newer().as_deref(), Some(""));
+        assert_eq!(history.newer(), None);
+        assert_eq!(history.older().as_deref(), Some("newest"));
+    }
+
+    #[test]
+    fn search_history_moves_repeated_queries_to_the_front() {
+        let mut history = search_history(&["miles", "coltrane"]);
+
+        history.older();
+        history.add("coltrane");
+        history.add("coltrane");
+
+        assert_eq!(history.queries, ["coltrane", "miles"]);
+        assert_eq!(history.older().as_deref(), Some("coltrane"));
+    }
+
+    #[test]
+    fn search_history_is_capped() {
+        let queries = (0..SEARCH_HISTORY_LIMIT + 5)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>();
+        let mut history = SearchHistory::new(queries);
+
+        assert_eq!(history.queries.len(), SEARCH_HISTORY_LIMIT);
+
+        history.add("new");
+
+        assert_eq!(history.queries.len(), SEARCH_HISTORY_LIMIT);
+        assert_eq!(history.queries[0], "new");
+        assert_eq!(
+            history.queries.last().map(String::as_str),
+            Some((SEARCH_HISTORY_LIMIT - 2).to_string().as_str())
+        );
     }
 }
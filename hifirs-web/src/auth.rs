@@ -0,0 +1,214 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose, Engine as _};
+use ring::{
+    digest, pbkdf2,
+    rand::{SecureRandom, SystemRandom},
+};
+use std::{
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+};
+
+const ALGORITHM: pbkdf2::Algorithm = pbkdf2::PBKDF2_HMAC_SHA256;
+/// Name of the hash format, the first part of a stored hash.
+const SCHEME: &str = "pbkdf2-sha256";
+/// PBKDF2 rounds, so guessing the password from a stored hash is slow.
+const ITERATIONS: u32 = 600_000;
+const SALT_LENGTH: usize = 16;
+const HASH_LENGTH: usize = digest::SHA256_OUTPUT_LEN;
+
+/// Hash a password for storage, as `pbkdf2-sha256$<iterations>$<salt>$<hash>`
+/// with a random salt.
+pub fn hash_password(password: &str) -> String {
+    let mut salt = [0u8; SALT_LENGTH];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .expect("failed to generate a salt");
+
+    let mut hash = [0u8; HASH_LENGTH];
+    pbkdf2::derive(
+        ALGORITHM,
+        NonZeroU32::new(ITERATIONS).expect("iterations are not zero"),
+        &salt,
+        password.as_bytes(),
+        &mut hash,
+    );
+
+    format!(
+        "{SCHEME}${ITERATIONS}${}${}",
+        general_purpose::STANDARD_NO_PAD.encode(salt),
+        general_purpose::STANDARD_NO_PAD.encode(hash)
+    )
+}
+
+/// Whether a stored hash was written by `hash_password`. Hashes saved by older
+/// versions are not, and never match.
+pub fn is_password_hash(stored: &str) -> bool {
+    PasswordHash::parse(stored).is_some()
+}
+
+/// Check a password against a stored hash. The comparison takes the same time
+/// no matter how much of the hash matches.
+pub fn verify_password(stored: &str, password: &str) -> bool {
+    PasswordHash::parse(stored).is_some_and(|stored| {
+        pbkdf2::verify(
+            ALGORITHM,
+            stored.iterations,
+            &stored.salt,
+            password.as_bytes(),
+            &stored.hash,
+        )
+        .is_ok()
+    })
+}
+
+struct PasswordHash {
+    iterations: NonZeroU32,
+    salt: Vec<u8>,
+    hash: Vec<u8>,
+}
+
+impl PasswordHash {
+    fn parse(stored: &str) -> Option<Self> {
+        let mut parts = stored.split('$');
+
+        if parts.next()? != SCHEME {
+            return None;
+        }
+
+        let iterations = parts.next()?.parse().ok()?;
+        let salt = general_purpose::STANDARD_NO_PAD
+            .decode(parts.next()?)
+            .ok()?;
+        let hash = general_purpose::STANDARD_NO_PAD
+            .decode(parts.next()?)
+            .ok()?;
+
+        if parts.next().is_some() || hash.len() != HASH_LENGTH {
+            return None;
+        }
+
+        Some(Self {
+            iterations,
+            salt,
+            hash,
+        })
+    }
+}
+
+/// The stored password hash, along with a digest of the last password that
+/// matched it. Checking the hash is deliberately slow and the browser sends
+/// the password with every request, so a repeated password is compared
+/// against the digest instead.
+pub struct WebPassword {
+    hash: String,
+    accepted: Mutex<Option<digest::Digest>>,
+}
+
+impl WebPassword {
+    pub fn new(hash: String) -> Self {
+        Self {
+            hash,
+            accepted: Mutex::new(None),
+        }
+    }
+
+    async fn verify(&self, password: String) -> bool {
+        let password_digest = digest::digest(&digest::SHA256, password.as_bytes());
+
+        let accepted = self
+            .accepted
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|accepted| constant_time_eq(accepted.as_ref(), password_digest.as_ref()));
+
+        if accepted {
+            return true;
+        }
+
+        let hash = self.hash.clone();
+        let verified = tokio::task::spawn_blocking(move || verify_password(&hash, &password))
+            .await
+            .unwrap_or(false);
+
+        if verified {
+            *self.accepted.lock().unwrap() = Some(password_digest);
+        }
+
+        verified
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Rejects requests without a Basic auth password matching the stored hash.
+/// The username is ignored.
+pub async fn require_password(
+    State(web_password): State<Arc<WebPassword>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let password = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| general_purpose::STANDARD.decode(encoded).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .and_then(|credentials| {
+            credentials
+                .split_once(':')
+                .map(|(_, password)| password.to_string())
+        });
+
+    let authorized = match password {
+        Some(password) => web_password.verify(password).await,
+        None => false,
+    };
+
+    if authorized {
+        next.run(request).await
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"hifi-rs\"")],
+        )
+            .into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_the_hashed_password_only() {
+        let hash = hash_password("secret");
+
+        assert!(is_password_hash(&hash));
+        assert!(verify_password(&hash, "secret"));
+        assert!(!verify_password(&hash, "Secret"));
+        assert!(!verify_password(&hash, ""));
+    }
+
+    #[test]
+    fn salts_every_hash() {
+        assert_ne!(hash_password("secret"), hash_password("secret"));
+    }
+
+    #[test]
+    fn rejects_md5_hashes_from_older_versions() {
+        let md5 = "5ebe2294ecd0e0f08eab7690d2a6ee69";
+
+        assert!(!is_password_hash(md5));
+        assert!(!verify_password(md5, "secret"));
+    }
+}
@@ -1,7 +1,11 @@
 use assets::static_handler;
 use axum::{
     extract::State,
-    response::{sse::Event, Sse},
+    http::HeaderValue,
+    response::{
+        sse::{Event, KeepAlive},
+        Sse,
+    },
     routing::get,
     Router,
 };
@@ -9,27 +13,70 @@ use futures::stream::Stream;
 use hifirs_player::notification::Notification;
 use leptos::html::*;
 use leptos::*;
-use routes::{album, artist, favorites, now_playing, playlist, queue, search};
-use std::{convert::Infallible, sync::Arc};
-use tokio::sync::broadcast::{self, Sender};
+use routes::{
+    album, artist, favorites, featured, genre, history, label, now_playing, playlist, queue,
+    search, sleep_timer, track,
+};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    net::TcpListener,
+    sync::broadcast::{self, Sender},
+};
 use tokio_stream::wrappers::BroadcastStream;
 use tokio_stream::StreamExt as _;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 
 mod assets;
+pub mod auth;
 mod components;
 mod icons;
 mod page;
 mod routes;
+mod schema;
 mod view;
 
 pub fn is_htmx_request(headers: &axum::http::HeaderMap) -> bool {
     headers.get("HX-Request").is_some() && headers.get("HX-Boosted").is_none()
 }
 
-pub async fn init(address: String) {
-    println!("Lisening on {address}");
-    let router = create_router().await;
-    let listener = tokio::net::TcpListener::bind(address).await.unwrap();
+/// Logs a failed player action and broadcasts it to connected clients.
+pub async fn report_error(error: hifirs_player::error::Error) {
+    tracing::error!("{error}");
+    hifirs_player::broadcast_error(error).await;
+}
+
+/// Bind the web server to the given address.
+pub async fn bind(address: SocketAddr) -> std::io::Result<TcpListener> {
+    let listener = TcpListener::bind(address).await?;
+    println!("Listening on http://{}", listener.local_addr()?);
+
+    Ok(listener)
+}
+
+pub async fn init(listener: TcpListener, allowed_origins: Vec<String>, keep_alive: Duration) {
+    let address = listener
+        .local_addr()
+        .map_or("the web server".to_string(), |address| address.to_string());
+
+    let web_password = hifirs_player::sql::db::get_config()
+        .await
+        .and_then(|config| config.web_password);
+
+    match web_password.as_deref() {
+        None => {
+            tracing::warn!(
+                "no web password set, anyone who can reach {address} can control playback"
+            );
+        }
+        Some(hash) if !auth::is_password_hash(hash) => {
+            tracing::warn!(
+                "the web password was saved by an older version and is no longer accepted, set it again with `hifi-rs config web-password`"
+            );
+        }
+        Some(_) => {}
+    }
+
+    let router = create_router(web_password, &allowed_origins, keep_alive).await;
     axum::serve(listener, router)
         .with_graceful_shutdown(async {
             let mut broadcast_receiver = hifirs_player::notify_receiver();
@@ -46,9 +93,16 @@ pub async fn init(address: String) {
         .unwrap();
 }
 
-async fn create_router() -> Router {
+async fn create_router(
+    web_password: Option<String>,
+    allowed_origins: &[String],
+    keep_alive: Duration,
+) -> Router {
     let (tx, _rx) = broadcast::channel::<ServerSentEvent>(100);
-    let shared_state = Arc::new(AppState { tx: tx.clone() });
+    let shared_state = Arc::new(AppState {
+        tx: tx.clone(),
+        keep_alive,
+    });
     tokio::spawn(background_task(tx));
 
     let router = axum::Router::new()
@@ -58,82 +112,174 @@ async fn create_router() -> Router {
         .merge(artist::routes())
         .merge(playlist::routes())
         .merge(favorites::routes())
+        .merge(featured::routes())
+        .merge(genre::routes())
+        .merge(history::routes())
+        .merge(label::routes())
         .merge(queue::routes())
+        .merge(track::routes())
+        .merge(sleep_timer::routes())
         .route("/sse", get(sse_handler))
+        .route("/api/events", get(events_handler))
         .route("/assets/{*file}", get(static_handler));
 
-    router.with_state(shared_state)
+    #[cfg(feature = "metrics")]
+    let router = router.merge(routes::metrics::routes());
+
+    let router = router.with_state(shared_state);
+
+    let router = match web_password {
+        Some(password_hash) => router.layer(axum::middleware::from_fn_with_state(
+            Arc::new(auth::WebPassword::new(password_hash)),
+            auth::require_password,
+        )),
+        None => router,
+    };
+
+    match cors_layer(allowed_origins) {
+        Some(cors) => router.layer(cors),
+        None => router,
+    }
+}
+
+/// Without any allowed origins only the embedded UI can call the API.
+/// A `*` entry allows every origin.
+fn cors_layer(allowed_origins: &[String]) -> Option<CorsLayer> {
+    if allowed_origins.is_empty() {
+        return None;
+    }
+
+    let allow_origin = if allowed_origins.iter().any(|origin| origin == "*") {
+        tracing::warn!("allowing cross-origin requests from any origin");
+        AllowOrigin::any()
+    } else {
+        let origins = allowed_origins
+            .iter()
+            .filter_map(|origin| match HeaderValue::from_str(origin) {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    tracing::warn!("ignoring invalid allowed origin: {origin}");
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        AllowOrigin::list(origins)
+    };
+
+    Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods(Any)
+            .allow_headers(Any),
+    )
 }
 
 async fn background_task(tx: Sender<ServerSentEvent>) {
     let mut receiver = hifirs_player::notify_receiver();
 
     loop {
-        if let Ok(notification) = receiver.recv().await {
-            if let Notification::Status { status } = &notification {
+        let notification = match receiver.recv().await {
+            Ok(notification) => notification,
+            Err(_) if receiver.is_closed() => break,
+            Err(_) => continue,
+        };
+
+        if let Notification::Status { status } = &notification {
+            let event = ServerSentEvent {
+                event_name: "status".into(),
+                event_data: if status == &gstreamer::State::Playing {
+                    "playing".into()
+                } else {
+                    "paused".into()
+                },
+            };
+            _ = tx.send(event);
+        }
+
+        match notification {
+            Notification::Buffering {
+                is_buffering,
+                percent,
+                target_state: _,
+            } => {
                 let event = ServerSentEvent {
-                    event_name: "status".into(),
-                    event_data: if status == &gstreamer::State::Playing {
-                        "playing".into()
+                    event_name: "buffering".into(),
+                    event_data: if is_buffering {
+                        format!("Buffering… {percent}%")
                     } else {
-                        "paused".into()
+                        String::default()
                     },
                 };
                 _ = tx.send(event);
             }
+            Notification::Status { status } => {
+                let message_data = match status {
+                    gstreamer::State::VoidPending => "pause",
+                    gstreamer::State::Null => "pause",
+                    gstreamer::State::Ready => "pause",
+                    gstreamer::State::Paused => "pause",
+                    gstreamer::State::Playing => "play",
+                };
 
-            match notification {
-                Notification::Buffering {
-                    is_buffering: _,
-                    percent: _,
-                    target_state: _,
-                } => {}
-                Notification::Status { status } => {
-                    let message_data = match status {
-                        gstreamer::State::VoidPending => "pause",
-                        gstreamer::State::Null => "pause",
-                        gstreamer::State::Ready => "pause",
-                        gstreamer::State::Paused => "pause",
-                        gstreamer::State::Playing => "play",
-                    };
-
-                    let event = ServerSentEvent {
-                        event_name: "status".into(),
-                        event_data: message_data.into(),
-                    };
-                    _ = tx.send(event);
-                }
-                Notification::Position { clock } => {
-                    let event = ServerSentEvent {
-                        event_name: "position".into(),
-                        event_data: clock.seconds().to_string(),
-                    };
-                    _ = tx.send(event);
-                }
-                Notification::CurrentTrackList { list } => {
-                    let serialized = serde_json::to_string(&list).unwrap_or("".into());
-
-                    let event = ServerSentEvent {
-                        event_name: "tracklist".into(),
-                        event_data: serialized,
-                    };
-                    _ = tx.send(event);
-                }
-                Notification::Quit => (),
-                Notification::Loading {
-                    is_loading: _,
-                    target_state: _,
-                } => {}
-                Notification::Error { error: _ } => (),
-                Notification::Volume { volume } => {
-                    let event = ServerSentEvent {
-                        event_name: "volume".into(),
-                        event_data: volume.to_string(),
-                    };
-                    _ = tx.send(event);
-                }
-            };
-        }
+                let event = ServerSentEvent {
+                    event_name: "status".into(),
+                    event_data: message_data.into(),
+                };
+                _ = tx.send(event);
+            }
+            Notification::Position { clock } => {
+                let event = ServerSentEvent {
+                    event_name: "position".into(),
+                    event_data: clock.seconds().to_string(),
+                };
+                _ = tx.send(event);
+            }
+            Notification::CurrentTrackList { list } => {
+                let serialized = serde_json::to_string(&list).unwrap_or("".into());
+
+                let event = ServerSentEvent {
+                    event_name: "tracklist".into(),
+                    event_data: serialized,
+                };
+                _ = tx.send(event);
+            }
+            Notification::Quit => break,
+            Notification::Loading {
+                is_loading: _,
+                target_state: _,
+            } => {}
+            Notification::Error { error } => {
+                let event = ServerSentEvent {
+                    event_name: "error".into(),
+                    event_data: error.to_string(),
+                };
+                _ = tx.send(event);
+            }
+            Notification::Volume { volume } => {
+                let event = ServerSentEvent {
+                    event_name: "volume".into(),
+                    event_data: volume.to_string(),
+                };
+                _ = tx.send(event);
+            }
+            Notification::SleepTimer { remaining_seconds } => {
+                let event = ServerSentEvent {
+                    event_name: "sleep-timer".into(),
+                    event_data: remaining_seconds
+                        .map(|seconds| seconds.to_string())
+                        .unwrap_or("off".into()),
+                };
+                _ = tx.send(event);
+            }
+            Notification::OutputRate { rate } => {
+                let event = ServerSentEvent {
+                    event_name: "output-rate".into(),
+                    event_data: output_rate_label(rate),
+                };
+                _ = tx.send(event);
+            }
+        };
     }
 }
 
@@ -141,6 +287,11 @@ async fn sse_handler(
     State(state): State<Arc<AppState>>,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
     let rx = state.tx.subscribe();
+    tracing::debug!(
+        "sse client connected, {} subscribers",
+        state.tx.receiver_count()
+    );
+
     let stream = BroadcastStream::new(rx).filter_map(|result| match result {
         Ok(event) => Some(Ok(Event::default()
             .event(event.event_name)
@@ -148,11 +299,42 @@ async fn sse_handler(
         Err(_) => None,
     });
 
-    Sse::new(stream)
+    // Periodic comments keep proxies from closing an idle stream and
+    // make sure a dead connection is noticed once a write fails.
+    Sse::new(stream).keep_alive(KeepAlive::new().interval(state.keep_alive))
+}
+
+/// Streams the player notifications as JSON for other clients, named after the
+/// kind of notification, for example `status` or `position`. Read-only, use the
+/// other API routes to control the player.
+async fn events_handler(
+    State(state): State<Arc<AppState>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = hifirs_player::notify_receiver()
+        .filter_map(|notification| notification_event(&notification).map(Ok));
+
+    Sse::new(stream).keep_alive(KeepAlive::new().interval(state.keep_alive))
+}
+
+/// The output sample rate as shown on the now playing page.
+pub(crate) fn output_rate_label(rate: u32) -> String {
+    format!("Output {} kHz", rate as f32 / 1000.0)
+}
+
+fn notification_event(notification: &Notification) -> Option<Event> {
+    let value = serde_json::to_value(notification).ok()?;
+    let name = match &value {
+        serde_json::Value::Object(fields) => fields.keys().next()?.clone(),
+        serde_json::Value::String(name) => name.clone(),
+        _ => return None,
+    };
+
+    Some(Event::default().event(name).data(value.to_string()))
 }
 
 pub struct AppState {
     pub tx: Sender<ServerSentEvent>,
+    pub keep_alive: Duration,
 }
 
 #[derive(Clone)]
@@ -160,3 +342,75 @@ pub struct ServerSentEvent {
     event_name: String,
     event_data: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const STREAMS: usize = 50;
+
+    fn state() -> Arc<AppState> {
+        let (tx, _) = broadcast::channel(16);
+
+        Arc::new(AppState {
+            tx,
+            keep_alive: Duration::from_millis(10),
+        })
+    }
+
+    #[tokio::test]
+    async fn dropped_streams_unsubscribe() {
+        let state = state();
+        let before = state.tx.receiver_count();
+
+        let mut streams = Vec::new();
+        for _ in 0..STREAMS {
+            streams.push(sse_handler(State(state.clone())).await);
+        }
+        assert_eq!(state.tx.receiver_count(), before + STREAMS);
+
+        drop(streams);
+        assert_eq!(state.tx.receiver_count(), before);
+    }
+
+    #[tokio::test]
+    async fn closed_connections_unsubscribe() {
+        let state = state();
+        let before = state.tx.receiver_count();
+
+        let app = Router::new()
+            .route("/sse", get(sse_handler))
+            .with_state(state.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let mut connections = Vec::new();
+        for _ in 0..STREAMS {
+            let mut connection = tokio::net::TcpStream::connect(address).await.unwrap();
+            connection
+                .write_all(b"GET /sse HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .await
+                .unwrap();
+
+            // The headers are only sent once the handler has subscribed.
+            let mut buffer = [0; 512];
+            assert!(connection.read(&mut buffer).await.unwrap() > 0);
+
+            connections.push(connection);
+        }
+        assert_eq!(state.tx.receiver_count(), before + STREAMS);
+
+        drop(connections);
+
+        // A closed connection is noticed once a keep alive fails to send.
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while state.tx.receiver_count() != before {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the streams of closed connections were not dropped");
+    }
+}
@@ -85,6 +85,12 @@ pub fn page(children: Children, active_page: Page) -> impl IntoView {
             >
                 <div class="overflow-auto h-full">{children()}</div>
 
+                <div
+                    id="error-toast"
+                    class="fixed inset-x-4 bottom-20 text-center text-red-500 empty:hidden"
+                    sse-swap="error"
+                ></div>
+
                 <Navigation active_page=active_page />
             </body>
         </html>
@@ -1,11 +1,13 @@
 use axum::{
     extract::Path,
+    http::StatusCode,
     response::IntoResponse,
     routing::{get, put},
     Router,
 };
 use hifirs_player::service::{Album, Track};
 use leptos::{component, prelude::*, IntoView};
+use serde::Serialize;
 use std::sync::Arc;
 use tokio::join;
 
@@ -30,6 +32,58 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/album/{id}/unset-favorite", put(unset_favorite))
         .route("/album/{id}/play", put(play))
         .route("/album/{id}/play/{track_position}", put(play_track))
+        .route("/api/albums/{id}", get(album_detail))
+        .route("/api/albums/{id}/favorite/toggle", put(toggle_favorite))
+}
+
+/// An album along with what each of its tracks can be streamed in.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AlbumDetail {
+    album: Album,
+    tracks: Vec<TrackAvailability>,
+}
+
+/// Whether and how well a track can be streamed.
+///
+/// - `maxQuality`: one of `cd`, `hires96` or `hires192`, `null` when the track
+///   can't be streamed.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TrackAvailability {
+    id: u32,
+    position: u32,
+    available: bool,
+    hires_available: bool,
+    max_quality: Option<&'static str>,
+}
+
+impl From<&Track> for TrackAvailability {
+    fn from(track: &Track) -> Self {
+        Self {
+            id: track.id,
+            position: track.position,
+            available: track.available,
+            hires_available: track.available && track.hires_available,
+            max_quality: track.max_quality().map(|quality| quality.name()),
+        }
+    }
+}
+
+async fn album_detail(Path(id): Path<String>) -> impl IntoResponse {
+    match hifirs_player::find_album(&id).await {
+        Some(album) => {
+            let detail = AlbumDetail {
+                tracks: album.tracks.values().map(|track| track.into()).collect(),
+                album,
+            };
+
+            serde_json::to_string(&detail)
+                .unwrap_or("Error".into())
+                .into_response()
+        }
+        None => (StatusCode::NOT_FOUND, format!("album {id} not found")).into_response(),
+    }
 }
 
 async fn suggestions(Path(id): Path<String>) -> impl IntoResponse {
@@ -53,6 +107,11 @@ async fn unset_favorite(Path(id): Path<String>) -> impl IntoResponse {
     render(html! { <ToggleFavorite id=id is_favorite=false /> })
 }
 
+async fn toggle_favorite(Path(id): Path<String>) -> impl IntoResponse {
+    let is_favorite = hifirs_player::toggle_favorite_album(&id).await;
+    serde_json::json!({ "isFavorite": is_favorite }).to_string()
+}
+
 async fn play(Path(id): Path<String>) -> impl IntoResponse {
     _ = hifirs_player::play_album(&id).await;
 }
@@ -183,3 +242,87 @@ fn album(
         </div>
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hifirs_player::service::TrackStatus;
+
+    fn track(available: bool, hires_available: bool, bit_depth: u32, sampling_rate: f32) -> Track {
+        Track {
+            id: 19512574,
+            number: 1,
+            title: "So What".to_string(),
+            album: None,
+            artist: None,
+            duration_seconds: 562,
+            explicit: false,
+            hires_available,
+            sampling_rate,
+            bit_depth,
+            status: TrackStatus::Unplayed,
+            track_url: None,
+            track_url_expires_at: None,
+            available,
+            cover_art: None,
+            position: 3,
+            media_number: 1,
+            format: None,
+            replay_gain: Default::default(),
+        }
+    }
+
+    #[test]
+    fn streamable_cd_quality_track() {
+        let availability = TrackAvailability::from(&track(true, false, 16, 44.1));
+
+        assert_eq!(availability.id, 19512574);
+        assert_eq!(availability.position, 3);
+        assert!(availability.available);
+        assert!(!availability.hires_available);
+        assert_eq!(availability.max_quality, Some("cd"));
+    }
+
+    #[test]
+    fn hires_track() {
+        let hires96 = TrackAvailability::from(&track(true, true, 24, 96.0));
+        let hires192 = TrackAvailability::from(&track(true, true, 24, 192.0));
+
+        assert!(hires96.hires_available);
+        assert_eq!(hires96.max_quality, Some("hires96"));
+        assert!(hires192.hires_available);
+        assert_eq!(hires192.max_quality, Some("hires192"));
+    }
+
+    #[test]
+    fn hires_flag_without_a_hires_bit_depth_is_cd_quality() {
+        let availability = TrackAvailability::from(&track(true, true, 16, 44.1));
+
+        assert_eq!(availability.max_quality, Some("cd"));
+    }
+
+    #[test]
+    fn track_that_is_not_streamable() {
+        let availability = TrackAvailability::from(&track(false, true, 24, 192.0));
+
+        assert!(!availability.available);
+        assert!(!availability.hires_available);
+        assert_eq!(availability.max_quality, None);
+    }
+
+    #[test]
+    fn serializes_in_camel_case() {
+        let availability = TrackAvailability::from(&track(false, false, 16, 44.1));
+
+        assert_eq!(
+            serde_json::to_value(&availability).unwrap(),
+            serde_json::json!({
+                "id": 19512574,
+                "position": 3,
+                "available": false,
+                "hiresAvailable": false,
+                "maxQuality": null,
+            })
+        );
+    }
+}
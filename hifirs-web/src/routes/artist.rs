@@ -1,11 +1,12 @@
 use axum::{
-    extract::Path,
+    extract::{Path, Query},
     response::IntoResponse,
     routing::{get, put},
     Router,
 };
-use hifirs_player::service::{Album, Artist};
+use hifirs_player::service::{Album, Artist, ReleaseSort};
 use leptos::prelude::*;
+use serde::Deserialize;
 use std::sync::Arc;
 use tokio::join;
 
@@ -15,7 +16,9 @@ use crate::{
         ToggleFavorite,
     },
     html,
+    icons::Play,
     page::Page,
+    report_error,
     view::render,
     AppState,
 };
@@ -25,6 +28,37 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/artist/{id}", get(index))
         .route("/artist/{id}/set-favorite", put(set_favorite))
         .route("/artist/{id}/unset-favorite", put(unset_favorite))
+        .route("/artist/{id}/play", put(play))
+        .route("/api/artists/{id}/releases", get(artist_releases))
+        .route("/api/artists/{id}/tracks", get(artist_tracks))
+        .route("/api/artists/{id}/similar", get(similar_artists))
+}
+
+async fn similar_artists(Path(id): Path<i32>) -> impl IntoResponse {
+    let artists = hifirs_player::similar_artists(id).await;
+
+    serde_json::to_string(&artists).unwrap_or("Error".into())
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct ReleasesParameters {
+    #[serde(default)]
+    sort: ReleaseSort,
+}
+
+async fn artist_releases(
+    Path(id): Path<i32>,
+    Query(parameters): Query<ReleasesParameters>,
+) -> impl IntoResponse {
+    let albums = hifirs_player::artist_albums(id, parameters.sort).await;
+
+    serde_json::to_string(&albums).unwrap_or("Error".into())
+}
+
+async fn artist_tracks(Path(id): Path<i32>) -> impl IntoResponse {
+    let tracks = hifirs_player::artist_tracks(id).await;
+
+    serde_json::to_string(&tracks).unwrap_or("Error".into())
 }
 
 async fn set_favorite(Path(id): Path<String>) -> impl IntoResponse {
@@ -35,10 +69,16 @@ async fn unset_favorite(Path(id): Path<String>) -> impl IntoResponse {
     hifirs_player::remove_favorite_artist(&id).await;
 }
 
+async fn play(Path(id): Path<i32>) -> impl IntoResponse {
+    if let Err(error) = hifirs_player::play_artist(id).await {
+        report_error(error).await;
+    }
+}
+
 async fn index(Path(id): Path<i32>) -> impl IntoResponse {
     let (artist, albums, similar_artists, favorites) = join!(
         hifirs_player::artist(id),
-        hifirs_player::artist_albums(id),
+        hifirs_player::artist_albums(id, ReleaseSort::default()),
         hifirs_player::similar_artists(id),
         hifirs_player::favorites()
     );
@@ -72,7 +112,20 @@ fn artist(
             <div class="flex gap-4 justify-between items-center p-4">
                 <h1 class="text-2xl">{artist.name}</h1>
 
-                <ToggleFavorite id=artist.id.to_string() is_favorite=is_favorite />
+                <div class="flex gap-4 items-center">
+                    <button
+                        class="flex gap-2 justify-center items-center py-2 px-4 bg-blue-500 rounded"
+                        hx-swap="none"
+                        hx-put=format!("{}/play", artist.id)
+                    >
+                        <span class="size-6">
+                            <Play />
+                        </span>
+                        <span>Play</span>
+                    </button>
+
+                    <ToggleFavorite id=artist.id.to_string() is_favorite=is_favorite />
+                </div>
             </div>
             <div class="flex flex-col gap-4">
                 <ListAlbums albums=albums sort=crate::components::list::AlbumSort::ReleaseYear />
@@ -0,0 +1,26 @@
+use axum::{extract::Query, http::StatusCode, response::IntoResponse, routing::get, Router};
+use hifirs_player::service::FeaturedType;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::AppState;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/api/featured", get(featured))
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct FeaturedParameters {
+    #[serde(rename = "type", default)]
+    featured_type: FeaturedType,
+    genre_id: Option<u32>,
+}
+
+async fn featured(Query(parameters): Query<FeaturedParameters>) -> impl IntoResponse {
+    match hifirs_player::featured_albums(parameters.featured_type, parameters.genre_id).await {
+        Ok(albums) => serde_json::to_string(&albums)
+            .unwrap_or("Error".into())
+            .into_response(),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response(),
+    }
+}
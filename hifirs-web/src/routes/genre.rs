@@ -0,0 +1,40 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::AppState;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/genres", get(genres))
+        .route("/api/genres/{id}/albums", get(genre_albums))
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct GenreParameters {
+    parent_id: Option<u32>,
+}
+
+async fn genres(Query(parameters): Query<GenreParameters>) -> impl IntoResponse {
+    match hifirs_player::genres(parameters.parent_id).await {
+        Ok(genres) => serde_json::to_string(&genres)
+            .unwrap_or("Error".into())
+            .into_response(),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response(),
+    }
+}
+
+async fn genre_albums(Path(id): Path<u32>) -> impl IntoResponse {
+    match hifirs_player::genre_albums(id).await {
+        Ok(albums) => serde_json::to_string(&albums)
+            .unwrap_or("Error".into())
+            .into_response(),
+        Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response(),
+    }
+}
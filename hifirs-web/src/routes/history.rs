@@ -0,0 +1,21 @@
+use axum::{extract::Query, response::IntoResponse, routing::get, Router};
+use hifirs_player::sql::db;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::AppState;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/api/history", get(history))
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct HistoryParameters {
+    limit: Option<i64>,
+}
+
+async fn history(Query(parameters): Query<HistoryParameters>) -> impl IntoResponse {
+    let history = db::history(parameters.limit.unwrap_or(50)).await;
+
+    serde_json::to_string(&history).unwrap_or("Error".into())
+}
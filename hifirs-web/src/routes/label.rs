@@ -0,0 +1,17 @@
+use axum::{extract::Path, http::StatusCode, response::IntoResponse, routing::get, Router};
+use std::sync::Arc;
+
+use crate::AppState;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/api/labels/{id}", get(label))
+}
+
+async fn label(Path(id): Path<u32>) -> impl IntoResponse {
+    match hifirs_player::label(id).await {
+        Some(label) => serde_json::to_string(&label)
+            .unwrap_or("Error".into())
+            .into_response(),
+        None => (StatusCode::NOT_FOUND, format!("label {id} not found")).into_response(),
+    }
+}
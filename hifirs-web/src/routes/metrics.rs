@@ -0,0 +1,68 @@
+use axum::{http::header, response::IntoResponse, routing::get, Router};
+use std::{fmt::Write, sync::Arc};
+
+use crate::AppState;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/metrics", get(metrics))
+}
+
+/// Counters and the player state in the Prometheus text format.
+async fn metrics() -> impl IntoResponse {
+    let (api_requests, api_errors) = hifirs_player::api_request_counts();
+    let queue_length = hifirs_player::current_tracklist().await.total();
+    let current_state = hifirs_player::current_state();
+
+    let mut body = String::new();
+
+    metric(
+        &mut body,
+        "hifirs_tracks_played_total",
+        "counter",
+        "Tracks started since startup.",
+        hifirs_player::tracks_played(),
+    );
+    metric(
+        &mut body,
+        "hifirs_api_requests_total",
+        "counter",
+        "Requests sent to the Qobuz API since startup.",
+        api_requests,
+    );
+    metric(
+        &mut body,
+        "hifirs_api_errors_total",
+        "counter",
+        "Requests to the Qobuz API that failed since startup.",
+        api_errors,
+    );
+    metric(
+        &mut body,
+        "hifirs_queue_length",
+        "gauge",
+        "Tracks in the queue.",
+        queue_length,
+    );
+
+    _ = writeln!(body, "# HELP hifirs_playback_state Current playback state.");
+    _ = writeln!(body, "# TYPE hifirs_playback_state gauge");
+
+    for (state, name) in [
+        (gstreamer::State::Playing, "playing"),
+        (gstreamer::State::Paused, "paused"),
+    ] {
+        _ = writeln!(
+            body,
+            "hifirs_playback_state{{state=\"{name}\"}} {}",
+            u8::from(current_state == state)
+        );
+    }
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+fn metric(body: &mut String, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+    _ = writeln!(body, "# HELP {name} {help}");
+    _ = writeln!(body, "# TYPE {name} {kind}");
+    _ = writeln!(body, "{name} {value}");
+}
@@ -1,7 +1,15 @@
 pub mod album;
 pub mod artist;
 pub mod favorites;
+pub mod featured;
+pub mod genre;
+pub mod history;
+pub mod label;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod now_playing;
 pub mod playlist;
 pub mod queue;
 pub mod search;
+pub mod sleep_timer;
+pub mod track;
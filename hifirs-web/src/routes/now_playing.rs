@@ -3,7 +3,10 @@ use axum::{
     routing::{get, post, put},
     Router,
 };
-use hifirs_player::queue::{TrackListType, TrackListValue};
+use hifirs_player::{
+    queue::{TrackListType, TrackListValue},
+    service::Track,
+};
 use leptos::{component, prelude::*, IntoView};
 use std::sync::Arc;
 
@@ -12,6 +15,8 @@ use crate::{
     html,
     icons::{Backward, Forward, Pause, Play},
     page::Page,
+    report_error,
+    schema::SchemaV1,
     view::render,
     AppState,
 };
@@ -28,6 +33,49 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/api/previous", put(previous))
         .route("/api/next", put(next))
         .route("/api/volume", post(set_volume))
+        .route("/api/v1/state", get(state_v1))
+        .route("/api/now-playing", get(now_playing))
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NowPlaying {
+    track: Option<Track>,
+    status: &'static str,
+    position_seconds: u64,
+    duration_seconds: u32,
+}
+
+async fn now_playing() -> impl IntoResponse {
+    let track = hifirs_player::current_track().await;
+    let status = match hifirs_player::current_state() {
+        gstreamer::State::Playing => "playing",
+        gstreamer::State::Paused => "paused",
+        _ => "stopped",
+    };
+
+    let now_playing = NowPlaying {
+        duration_seconds: track.as_ref().map_or(0, |track| track.duration_seconds),
+        position_seconds: hifirs_player::position().map_or(0, |position| position.seconds()),
+        status,
+        track,
+    };
+
+    serde_json::to_string(&now_playing).unwrap_or("Error".into())
+}
+
+async fn state_v1() -> impl IntoResponse {
+    let current_tracklist = hifirs_player::current_tracklist().await;
+    let position_seconds = hifirs_player::position().map_or(0, |position| position.seconds());
+
+    let state = SchemaV1::new(
+        &current_tracklist,
+        hifirs_player::current_state(),
+        position_seconds,
+        hifirs_player::volume(),
+    );
+
+    serde_json::to_string(&state).unwrap_or("Error".into())
 }
 
 #[derive(serde::Deserialize, Clone, Copy)]
@@ -110,23 +158,33 @@ fn play_pause(play: bool) -> impl IntoView {
 async fn play() -> impl IntoResponse {
     match hifirs_player::play().await {
         Ok(_) => render(html! { <PlayPause play=true /> }),
-        Err(_) => render(html! { <PlayPause play=false /> }),
+        Err(error) => {
+            report_error(error).await;
+            render(html! { <PlayPause play=false /> })
+        }
     }
 }
 
 async fn pause() -> impl IntoResponse {
     match hifirs_player::pause().await {
         Ok(_) => render(html! { <PlayPause play=false /> }),
-        Err(_) => render(html! { <PlayPause play=true /> }),
+        Err(error) => {
+            report_error(error).await;
+            render(html! { <PlayPause play=true /> })
+        }
     }
 }
 
 async fn previous() -> impl IntoResponse {
-    _ = hifirs_player::previous().await;
+    if let Err(error) = hifirs_player::previous().await {
+        report_error(error).await;
+    }
 }
 
 async fn next() -> impl IntoResponse {
-    _ = hifirs_player::next().await;
+    if let Err(error) = hifirs_player::next().await {
+        report_error(error).await;
+    }
 }
 
 async fn index() -> impl IntoResponse {
@@ -175,12 +233,10 @@ async fn progress_partial() -> impl IntoResponse {
 
 #[component]
 fn progress(position_seconds: Option<u64>, duration_seconds: Option<u32>) -> impl IntoView {
-    let position = position_seconds.map_or("00:00".to_string(), mseconds_to_mm_ss);
-    let duration = duration_seconds.map_or("00:00".to_string(), seconds_to_mm_ss);
+    let position = position_seconds.map_or("00:00".to_string(), format_mseconds);
+    let duration = duration_seconds.map_or("00:00".to_string(), format_seconds);
 
-    let progress = position_seconds
-        .and_then(|position| duration_seconds.map(|duration| position as u32 * 100 / duration))
-        .unwrap_or(0);
+    let progress = progress_value(position_seconds, duration_seconds);
 
     html! {
         <div class="grid h-2 rounded-full overflow-clip">
@@ -198,6 +254,19 @@ fn progress(position_seconds: Option<u64>, duration_seconds: Option<u32>) -> imp
     }
 }
 
+/// How far into the track the position in milliseconds is, in thousandths of
+/// a percent so the bar moves smoothly on long tracks. Never past the end, as
+/// the duration is rounded down to the second.
+fn progress_value(position_mseconds: Option<u64>, duration_seconds: Option<u32>) -> u32 {
+    position_mseconds
+        .and_then(|position| {
+            duration_seconds
+                .filter(|duration| *duration > 0)
+                .map(|duration| (position * 100 / duration as u64).min(100_000) as u32)
+        })
+        .unwrap_or(0)
+}
+
 #[component]
 pub fn now_playing(
     current_tracklist: TrackListValue,
@@ -229,6 +298,13 @@ pub fn now_playing(
             album.map(|album| album.title.clone()),
             album.map(|album| format!("/album/{}", album.id.clone())),
         ),
+        TrackListType::Artist => match current_tracklist.get_artist() {
+            Some(artist) => (
+                Some(artist.name.clone()),
+                Some(format!("/artist/{}", artist.id)),
+            ),
+            None => (None, None),
+        },
         TrackListType::Unknown => (None, None),
     };
 
@@ -260,6 +336,8 @@ pub fn now_playing(
     );
 
     let number_of_tracks = current_tracklist.queue.len();
+    let album_link = current_track.map(|track| format!("/track/{}/album", track.id));
+    let output_rate = hifirs_player::output_rate().map(crate::output_rate_label);
 
     html! {
         <div
@@ -303,7 +381,9 @@ pub fn now_playing(
 
                 <div class="flex flex-col gap-y-4 w-full">
                     <div class="flex gap-2 justify-between items-center">
-                        <span class="text-lg truncate">{title}</span>
+                        <a class="text-lg truncate" href=album_link>
+                            {title}
+                        </a>
                         <Info explicit=explicit hires_available=hires_available />
                     </div>
 
@@ -313,6 +393,11 @@ pub fn now_playing(
                             duration_seconds=duration_seconds
                         />
                     </div>
+
+                    <div class="text-center text-gray-400 empty:hidden" sse-swap="buffering"></div>
+                    <div class="text-center text-gray-500 empty:hidden" sse-swap="output-rate">
+                        {output_rate}
+                    </div>
                 </div>
 
                 <div class="flex flex-col gap-4">
@@ -344,16 +429,77 @@ pub fn now_playing(
     }
 }
 
-fn seconds_to_mm_ss<T: Into<u64>>(seconds: T) -> String {
+fn format_seconds<T: Into<u64>>(seconds: T) -> String {
     let seconds = seconds.into();
-    let minutes = seconds / 60;
+    let hours = seconds / 3600;
+    let minutes = (seconds % 3600) / 60;
     let seconds = seconds % 60;
-    format!("{:02}:{:02}", minutes, seconds)
+
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
 }
 
-fn mseconds_to_mm_ss<T: Into<u64>>(seconds: T) -> String {
-    let seconds = seconds.into() / 1000;
-    let minutes = seconds / 60;
-    let seconds = seconds % 60;
-    format!("{:02}:{:02}", minutes, seconds)
+fn format_mseconds<T: Into<u64>>(mseconds: T) -> String {
+    format_seconds(mseconds.into() / 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u32 = 3600;
+
+    #[test]
+    fn formats_seconds_under_an_hour() {
+        assert_eq!(format_seconds(0u32), "00:00");
+        assert_eq!(format_seconds(HOUR - 1), "59:59");
+    }
+
+    #[test]
+    fn formats_seconds_from_an_hour() {
+        assert_eq!(format_seconds(HOUR), "1:00:00");
+        assert_eq!(format_seconds(2 * HOUR + 61), "2:01:01");
+        assert_eq!(format_seconds(10 * HOUR), "10:00:00");
+        assert_eq!(format_seconds(10 * HOUR + 59 * 60 + 59), "10:59:59");
+    }
+
+    #[test]
+    fn formats_milliseconds_from_an_hour() {
+        assert_eq!(format_mseconds(HOUR as u64 * 1000), "1:00:00");
+        assert_eq!(format_mseconds(2 * HOUR as u64 * 1000 + 999), "2:00:00");
+        assert_eq!(format_mseconds(10 * HOUR as u64 * 1000), "10:00:00");
+    }
+
+    #[test]
+    fn progress_of_long_tracks() {
+        for hours in [1, 2, 10] {
+            let duration = hours * HOUR;
+            let mseconds = |seconds: u32| Some(seconds as u64 * 1000);
+
+            assert_eq!(progress_value(mseconds(0), Some(duration)), 0);
+            assert_eq!(
+                progress_value(mseconds(duration / 4), Some(duration)),
+                25_000
+            );
+            assert_eq!(
+                progress_value(mseconds(duration / 2), Some(duration)),
+                50_000
+            );
+            assert_eq!(progress_value(mseconds(duration), Some(duration)), 100_000);
+        }
+
+        // One second into ten hours is still shown.
+        assert_eq!(progress_value(Some(1000), Some(10 * HOUR)), 2);
+    }
+
+    #[test]
+    fn progress_stays_within_the_track() {
+        assert_eq!(progress_value(Some(3_600_999), Some(HOUR)), 100_000);
+        assert_eq!(progress_value(Some(1000), Some(0)), 0);
+        assert_eq!(progress_value(None, Some(HOUR)), 0);
+        assert_eq!(progress_value(Some(1000), None), 0);
+    }
 }
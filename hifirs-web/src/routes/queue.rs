@@ -1,15 +1,19 @@
 use axum::{
-    extract::Path,
-    response::IntoResponse,
+    extract::{Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Response},
     routing::{get, put},
     Router,
 };
 use hifirs_player::{
-    queue::{TrackListType, TrackListValue},
+    error::Error,
+    queue::{QueueDuration, TrackListType, TrackListValue},
     service::TrackStatus,
 };
 use leptos::{component, prelude::*, IntoView};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use tokio::join;
 
 use crate::{
     components::{
@@ -18,6 +22,7 @@ use crate::{
     },
     html,
     page::Page,
+    report_error,
     view::render,
     AppState,
 };
@@ -27,10 +32,104 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/queue", get(index))
         .route("/queue/list", get(queue_partial))
         .route("/queue/skip-to/{track_number}", put(skip_to))
+        .route("/api/queue/skip-to-track/{track_id}", put(skip_to_track_id))
+        .route("/api/queue/add-next/{track_id}", put(add_next))
+        .route("/api/queue/append/{track_id}", put(add_to_queue))
+        .route("/api/queue/remove/{position}", put(remove_from_queue))
+        .route("/api/queue/clear", put(clear_queue))
+        .route("/api/queue/clear-played", put(clear_played))
+        .route("/api/queue/move/{from}/{to}", put(move_track))
+        .route("/api/queue", get(current_queue))
+        .route("/api/queue/slice", get(slice))
+}
+
+/// The queue along with how long it plays for.
+#[derive(Serialize)]
+struct CurrentQueue {
+    #[serde(flatten)]
+    tracklist: TrackListValue,
+    duration: QueueDuration,
+}
+
+async fn current_queue() -> impl IntoResponse {
+    let (tracklist, duration) = join!(
+        hifirs_player::current_tracklist(),
+        hifirs_player::queue_duration()
+    );
+
+    serde_json::to_string(&CurrentQueue {
+        tracklist,
+        duration,
+    })
+    .unwrap_or("Error".into())
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct SliceParameters {
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+async fn slice(Query(parameters): Query<SliceParameters>) -> impl IntoResponse {
+    let current_tracklist = hifirs_player::current_tracklist().await;
+    let slice = current_tracklist.slice(
+        parameters.offset.unwrap_or(0),
+        parameters.limit.unwrap_or(50),
+    );
+
+    serde_json::to_string(&slice).unwrap_or("Error".into())
 }
 
 async fn skip_to(Path(track_number): Path<u32>) -> impl IntoResponse {
-    _ = hifirs_player::skip(track_number, true).await;
+    if let Err(error) = hifirs_player::skip(track_number, true).await {
+        report_error(error).await;
+    }
+}
+
+async fn skip_to_track_id(Path(track_id): Path<u32>) -> impl IntoResponse {
+    queue_response(hifirs_player::skip_to_track_id(track_id).await).await
+}
+
+async fn add_next(Path(track_id): Path<i32>) -> impl IntoResponse {
+    queue_response(hifirs_player::add_next(track_id).await).await
+}
+
+async fn add_to_queue(Path(track_id): Path<i32>) -> impl IntoResponse {
+    queue_response(hifirs_player::add_to_queue(track_id).await).await
+}
+
+async fn remove_from_queue(Path(position): Path<u32>) -> impl IntoResponse {
+    queue_response(hifirs_player::remove_from_queue(position).await).await
+}
+
+async fn move_track(Path((from, to)): Path<(u32, u32)>) -> impl IntoResponse {
+    queue_response(hifirs_player::move_track(from, to).await).await
+}
+
+async fn clear_queue() -> impl IntoResponse {
+    queue_response(hifirs_player::clear_queue().await).await
+}
+
+async fn clear_played() -> impl IntoResponse {
+    hifirs_player::clear_played().await;
+    StatusCode::OK
+}
+
+/// Maps the result of a queue action to a response, reporting unexpected errors.
+async fn queue_response(result: hifirs_player::Result<()>) -> Response {
+    match result {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(error @ (Error::TrackNotFound { .. } | Error::TrackNotInQueue { .. })) => {
+            (StatusCode::NOT_FOUND, error.to_string()).into_response()
+        }
+        Err(error @ (Error::InvalidQueuePosition { .. } | Error::RemovePlayingTrack)) => {
+            (StatusCode::BAD_REQUEST, error.to_string()).into_response()
+        }
+        Err(error) => {
+            report_error(error).await;
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
 }
 
 async fn index() -> impl IntoResponse {
@@ -53,6 +152,9 @@ pub fn queue(current_tracklist: TrackListValue) -> impl IntoView {
             .playlist
             .map(|playlist| playlist.title),
         TrackListType::Track => album.map(|album| album.title.clone()),
+        TrackListType::Artist => current_tracklist
+            .get_artist()
+            .map(|artist| artist.name.clone()),
         TrackListType::Unknown => None,
     };
 
@@ -112,7 +214,9 @@ pub fn queue_list(current_tracklist: TrackListValue) -> impl IntoView {
                                         Some(
                                             html! { <span class="text-gray-400">{track.number}</span> },
                                         )
-                                    } else if list_type == TrackListType::Playlist {
+                                    } else if list_type == TrackListType::Playlist
+                                        || list_type == TrackListType::Artist
+                                    {
                                         Some(
                                             html! {
                                                 <span class="text-gray-400">{track.position}</span>
@@ -1,12 +1,13 @@
 use axum::{
     extract::{Path, Query},
+    http::StatusCode,
     response::IntoResponse,
     routing::get,
     Form, Router,
 };
 use hifirs_player::service::SearchResults;
 use leptos::{component, prelude::*, IntoView};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 use crate::{
@@ -21,8 +22,87 @@ use crate::{
     AppState,
 };
 
+/// Qobuz does not return more than this many items per page.
+const MAX_SEARCH_LIMIT: u32 = 500;
+/// Qobuz takes the offset as a signed 32 bit number.
+const MAX_SEARCH_OFFSET: u32 = i32::MAX as u32;
+
 pub fn routes() -> Router<Arc<AppState>> {
-    Router::new().route("/search/{tab}", get(index).post(search))
+    Router::new()
+        .route("/search/{tab}", get(index).post(search))
+        .route("/api/search", get(search_api))
+}
+
+#[derive(Deserialize, Clone)]
+struct SearchApiParameters {
+    query: String,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    #[serde(rename = "type", default)]
+    search_type: SearchType,
+}
+
+/// What to search for, `all` unless given.
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum SearchType {
+    #[default]
+    All,
+    Tracks,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchPage {
+    results: SearchResults,
+    total: u32,
+    offset: u32,
+    limit: u32,
+    has_more: bool,
+}
+
+async fn search_api(Query(parameters): Query<SearchApiParameters>) -> impl IntoResponse {
+    let limit = parameters.limit.unwrap_or(20);
+    let offset = parameters.offset.unwrap_or(0);
+
+    if let Err(message) = check_page(limit, offset) {
+        return (StatusCode::BAD_REQUEST, message).into_response();
+    }
+
+    let results = match parameters.search_type {
+        SearchType::All => hifirs_player::search_page(&parameters.query, limit, offset).await,
+        SearchType::Tracks => {
+            hifirs_player::search_tracks_page(&parameters.query, limit, offset).await
+        }
+    };
+    let total = results.total;
+
+    let page = SearchPage {
+        results,
+        total,
+        offset,
+        limit,
+        has_more: has_more(offset, limit, total),
+    };
+
+    serde_json::to_string(&page)
+        .unwrap_or("Error".into())
+        .into_response()
+}
+
+fn check_page(limit: u32, offset: u32) -> Result<(), String> {
+    if limit > MAX_SEARCH_LIMIT {
+        Err(format!("limit must not exceed {MAX_SEARCH_LIMIT}"))
+    } else if offset > MAX_SEARCH_OFFSET {
+        Err(format!("offset must not exceed {MAX_SEARCH_OFFSET}"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Whether there are results after the page at `offset`.
+fn has_more(offset: u32, limit: u32, total: u32) -> bool {
+    offset.saturating_add(limit) < total
 }
 
 #[derive(Deserialize, Clone)]
@@ -40,10 +120,7 @@ async fn index(
         Some(query) => hifirs_player::search(query).await,
         None => SearchResults {
             query: query.clone().unwrap_or("".into()),
-            albums: vec![],
-            tracks: vec![],
-            artists: vec![],
-            playlists: vec![],
+            ..Default::default()
         },
     };
 
@@ -63,10 +140,7 @@ async fn search(Path(tab): Path<Tab>, Form(query): Form<SearchParameters>) -> im
         Some(query) => hifirs_player::search(query).await,
         None => SearchResults {
             query: query.clone().unwrap_or("".into()),
-            albums: vec![],
-            tracks: vec![],
-            artists: vec![],
-            playlists: vec![],
+            ..Default::default()
         },
     };
 
@@ -186,3 +260,29 @@ fn search(search_results: SearchResults, tab: Tab) -> impl IntoView {
         </div>
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn has_more_pages() {
+        assert!(has_more(0, 20, 21));
+        assert!(!has_more(0, 20, 20));
+        assert!(!has_more(40, 20, 45));
+    }
+
+    #[test]
+    fn has_more_does_not_overflow() {
+        assert!(!has_more(u32::MAX, 20, 100));
+        assert!(!has_more(u32::MAX, u32::MAX, u32::MAX));
+    }
+
+    #[test]
+    fn rejects_pages_qobuz_can_not_serve() {
+        assert!(check_page(20, 0).is_ok());
+        assert!(check_page(MAX_SEARCH_LIMIT, MAX_SEARCH_OFFSET).is_ok());
+        assert!(check_page(MAX_SEARCH_LIMIT + 1, 0).is_err());
+        assert!(check_page(20, u32::MAX).is_err());
+    }
+}
@@ -0,0 +1,50 @@
+use axum::{extract::Query, http::StatusCode, response::IntoResponse, routing::get, Router};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::AppState;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route(
+        "/api/sleep-timer",
+        get(sleep_timer)
+            .put(set_sleep_timer)
+            .delete(cancel_sleep_timer),
+    )
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SleepTimer {
+    remaining_seconds: Option<u64>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct SleepTimerParameters {
+    minutes: u64,
+    finish_track: Option<bool>,
+}
+
+async fn sleep_timer() -> impl IntoResponse {
+    let sleep_timer = SleepTimer {
+        remaining_seconds: hifirs_player::sleep_timer_remaining().map(|r| r.as_secs()),
+    };
+
+    serde_json::to_string(&sleep_timer).unwrap_or("Error".into())
+}
+
+async fn set_sleep_timer(Query(parameters): Query<SleepTimerParameters>) -> impl IntoResponse {
+    match hifirs_player::set_sleep_timer(
+        parameters.minutes,
+        parameters.finish_track.unwrap_or(false),
+    )
+    .await
+    {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(error) => (StatusCode::BAD_REQUEST, error.to_string()).into_response(),
+    }
+}
+
+async fn cancel_sleep_timer() -> impl IntoResponse {
+    hifirs_player::cancel_sleep_timer().await;
+}
@@ -0,0 +1,57 @@
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Redirect},
+    routing::{get, put},
+    Router,
+};
+use std::sync::Arc;
+
+use crate::AppState;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/track/{id}/album", get(open_album))
+        .route("/api/tracks/{id}", get(track))
+        .route("/api/tracks/{id}/album", get(album))
+        .route("/api/tracks/{id}/favorite/toggle", put(toggle_favorite))
+}
+
+async fn track(Path(id): Path<i32>) -> impl IntoResponse {
+    match hifirs_player::track(id).await {
+        Some(track) => serde_json::to_string(&track)
+            .unwrap_or("Error".into())
+            .into_response(),
+        None => (StatusCode::NOT_FOUND, format!("track {id} not found")).into_response(),
+    }
+}
+
+async fn album(Path(id): Path<i32>) -> impl IntoResponse {
+    match hifirs_player::album_for_track(id).await {
+        Some(album) => serde_json::to_string(&album)
+            .unwrap_or("Error".into())
+            .into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            format!("no album found for track {id}"),
+        )
+            .into_response(),
+    }
+}
+
+/// Show the album page of the album a track belongs to.
+async fn open_album(Path(id): Path<i32>) -> impl IntoResponse {
+    match hifirs_player::album_for_track(id).await {
+        Some(album) => Redirect::to(&format!("/album/{}", album.id)).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            format!("no album found for track {id}"),
+        )
+            .into_response(),
+    }
+}
+
+async fn toggle_favorite(Path(id): Path<u32>) -> impl IntoResponse {
+    let is_favorite = hifirs_player::toggle_favorite_track(id).await;
+    serde_json::json!({ "isFavorite": is_favorite }).to_string()
+}
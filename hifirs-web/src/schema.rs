@@ -0,0 +1,130 @@
+//! Stable JSON representation of the player state for external tools.
+//!
+//! These types are decoupled from the internal player types so that refactors
+//! do not change the public shape. Fields may be added, but renaming, removing
+//! or changing the meaning of a field requires a new schema version.
+
+use hifirs_player::{
+    queue::{TrackListType, TrackListValue},
+    service::{Track, TrackStatus},
+};
+use serde::Serialize;
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Version 1 of the player state.
+///
+/// - `version`: always `1`.
+/// - `status`: one of `playing`, `paused` or `stopped`.
+/// - `positionSeconds`: position in the current track.
+/// - `volume`: between `0.0` and `1.0`.
+/// - `currentTrack`: the playing track, if any.
+/// - `queue`: the loaded tracklist.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaV1 {
+    pub version: u32,
+    pub status: &'static str,
+    pub position_seconds: u64,
+    pub volume: f64,
+    pub current_track: Option<TrackV1>,
+    pub queue: QueueV1,
+}
+
+/// A tracklist in version 1 of the schema.
+///
+/// - `type`: one of `album`, `playlist`, `track`, `artist` or `unknown`.
+/// - `title`: the album or playlist title, or the artist name, if any.
+/// - `tracks`: the tracks in playback order.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueV1 {
+    #[serde(rename = "type")]
+    pub list_type: &'static str,
+    pub title: Option<String>,
+    pub tracks: Vec<TrackV1>,
+}
+
+/// A track in version 1 of the schema.
+///
+/// - `status`: one of `played`, `playing`, `unplayed` or `unplayable`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackV1 {
+    pub id: u32,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration_seconds: u32,
+    pub position: u32,
+    pub status: &'static str,
+}
+
+impl SchemaV1 {
+    pub fn new(
+        tracklist: &TrackListValue,
+        status: gstreamer::State,
+        position_seconds: u64,
+        volume: f64,
+    ) -> Self {
+        let status = match status {
+            gstreamer::State::Playing => "playing",
+            gstreamer::State::Paused => "paused",
+            _ => "stopped",
+        };
+
+        Self {
+            version: SCHEMA_VERSION,
+            status,
+            position_seconds,
+            volume,
+            current_track: tracklist.current_track().map(|track| track.into()),
+            queue: tracklist.into(),
+        }
+    }
+}
+
+impl From<&TrackListValue> for QueueV1 {
+    fn from(tracklist: &TrackListValue) -> Self {
+        let list_type = match tracklist.list_type() {
+            TrackListType::Album => "album",
+            TrackListType::Playlist => "playlist",
+            TrackListType::Track => "track",
+            TrackListType::Artist => "artist",
+            TrackListType::Unknown => "unknown",
+        };
+
+        let title = match tracklist.list_type() {
+            TrackListType::Playlist => tracklist.get_playlist().map(|p| p.title.clone()),
+            TrackListType::Artist => tracklist.get_artist().map(|a| a.name.clone()),
+            _ => tracklist.get_album().map(|a| a.title.clone()),
+        };
+
+        Self {
+            list_type,
+            title,
+            tracks: tracklist.queue.values().map(|track| track.into()).collect(),
+        }
+    }
+}
+
+impl From<&Track> for TrackV1 {
+    fn from(track: &Track) -> Self {
+        let status = match track.status {
+            TrackStatus::Played => "played",
+            TrackStatus::Playing => "playing",
+            TrackStatus::Unplayed => "unplayed",
+            TrackStatus::Unplayable => "unplayable",
+        };
+
+        Self {
+            id: track.id,
+            title: track.title.clone(),
+            artist: track.artist.as_ref().map(|a| a.name.clone()),
+            album: track.album.as_ref().map(|a| a.title.clone()),
+            duration_seconds: track.duration_seconds,
+            position: track.position,
+            status,
+        }
+    }
+}
@@ -1,21 +1,35 @@
-use clap::{Parser, Subcommand};
-use dialoguer::{Input, Password};
+use clap::{Parser, Subcommand, ValueEnum};
+use dialoguer::{Input, Password, Select};
 use hifirs_player::mpris;
+use hifirs_player::notification::Notification;
+use hifirs_player::service::ReplayGainMode;
 use hifirs_player::sql::db;
-use hifirs_qobuz_api::client::api::OutputFormat;
+use hifirs_qobuz_api::client::{
+    album::{Album, ReleaseSort},
+    api::{Client as QobuzClient, OutputFormat, DEFAULT_USER_AGENT, MAX_SEARCH_LIMIT},
+    AudioQuality,
+};
+use serde::Serialize;
 use snafu::prelude::*;
+use std::{
+    io::{BufWriter, IsTerminal, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 use tokio::task::JoinHandle;
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::{fmt, prelude::*};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
-struct Cli {
+pub struct Cli {
     /// Provide a username. (overrides any database value)
-    #[clap(short, long)]
+    #[clap(short, long, env = "QOBUZ_EMAIL")]
     pub username: Option<String>,
 
-    #[clap(short, long)]
+    #[clap(short, long, env = "QOBUZ_PASSWORD", hide_env_values = true)]
     /// Provide a password. (overrides any database value)
     pub password: Option<String>,
 
@@ -23,27 +37,200 @@ struct Cli {
     /// Disable the TUI interface.
     pub disable_tui: bool,
 
+    #[clap(long, default_value_t = false)]
+    /// Leave the mouse to the terminal instead of using it in the TUI, so text can be selected.
+    pub no_mouse: bool,
+
+    #[clap(long, default_value_t = false)]
+    /// Do not show the cover of the playing album in the TUI.
+    pub no_album_art: bool,
+
     #[clap(short, long, default_value_t = false)]
     /// Start web server with websocket API and embedded UI.
     pub web: bool,
 
     #[clap(long, default_value = "0.0.0.0:9888")]
     /// Specify a different interface and port for the web server to listen on.
-    pub interface: String,
+    /// Use 127.0.0.1:9888 to only allow connections from this machine.
+    pub interface: SocketAddr,
+
+    #[clap(long = "allowed-origin")]
+    /// Allow another site to call the web API. Can be repeated, use '*' to allow any origin.
+    pub allowed_origins: Vec<String>,
+
+    #[clap(long, default_value_t = 30)]
+    /// Seconds between keep-alive messages sent to connected web clients.
+    pub keep_alive: u64,
+
+    #[clap(long, global = true, value_enum)]
+    /// Quality to stream at. Lower qualities are used when a track is not available in it.
+    pub quality: Option<AudioQuality>,
+
+    #[clap(long, global = true, env = "HIFIRS_REFRESH_INTERVAL", value_parser = clap::value_parser!(u64).range(50..=5000))]
+    /// Milliseconds between updates of the playback position. Lower values make the
+    /// progress bar smoother, higher values use less power. [default: 250]
+    pub refresh_interval: Option<u64>,
+
+    #[clap(long, global = true, env = "HIFIRS_DOWNLOAD_DIR")]
+    /// Play tracks downloaded to this directory from disk instead of streaming them.
+    pub download_dir: Option<PathBuf>,
+
+    #[clap(long, global = true, env = "HIFIRS_AUDIO_SINK")]
+    /// GStreamer audio sink to play through, e.g. alsasink. See list-devices. (overrides any database value)
+    pub audio_sink: Option<String>,
+
+    #[clap(long, global = true, env = "HIFIRS_AUDIO_DEVICE", requires = "audio_sink")]
+    /// Device for the audio sink to play on, e.g. hw:1,0 for a USB DAC with alsasink.
+    pub audio_device: Option<String>,
+
+    #[clap(long, global = true, env = "HIFIRS_PROFILE")]
+    /// Use the credentials of another profile for this run. (overrides the saved profile)
+    pub profile: Option<String>,
+
+    #[clap(long, global = true, default_value_t = false)]
+    /// Print all output, including errors, as JSON and never prompt for input.
+    pub json: bool,
+
+    #[clap(long, global = true, value_enum, default_value_t)]
+    /// Layout of JSON output: pretty to read it, compact to pipe it into other tools.
+    pub format: JsonFormat,
+
+    #[clap(short, long, global = true, action = clap::ArgAction::Count)]
+    /// Log more. Repeat for more detail: -v info, -vv debug, -vvv trace. (overrides HIFIRS_LOG)
+    pub verbose: u8,
+
+    #[clap(short, long, global = true, conflicts_with = "verbose")]
+    /// Do not log anything, not even errors. (overrides HIFIRS_LOG)
+    pub quiet: bool,
 
     #[clap(subcommand)]
     pub command: Commands,
 }
 
 #[derive(Subcommand)]
-enum Commands {
+pub enum Commands {
     /// Open the player
     Open {},
+    /// Search for an album and play it
+    Play {
+        #[clap(value_parser)]
+        query: String,
+        /// Play the first search result instead of asking which album to play.
+        #[clap(long, visible_alias = "yes")]
+        first: bool,
+        /// Play the album only if a single result matches the query exactly, fail otherwise.
+        #[clap(long, conflicts_with = "first")]
+        exact: bool,
+        /// Number of albums to choose from.
+        #[clap(long, short, default_value_t = 20, value_parser = clap::value_parser!(i32).range(1..))]
+        limit: i32,
+        /// Number of albums shown at once when choosing, by default as many as fit the terminal.
+        #[clap(long, env = "HIFIRS_LIST_LENGTH", value_parser = clap::value_parser!(u64).range(1..))]
+        list_length: Option<u64>,
+    },
+    /// Continue playing where you left off
+    Resume {},
     /// Set configuration options
     Config {
         #[clap(subcommand)]
         command: ConfigCommands,
     },
+    /// Query the Qobuz API directly
+    Api {
+        #[clap(subcommand)]
+        command: ApiCommands,
+    },
+    /// List your favorite albums, artists and tracks
+    Favorites {
+        #[clap(value_enum, default_value_t = FavoriteKind::All)]
+        kind: FavoriteKind,
+        #[clap(short, long = "output", value_enum)]
+        output_format: Option<OutputFormat>,
+    },
+    /// Add an album, artist or playlist to your favorites
+    Favorite {
+        #[clap(value_enum)]
+        kind: ItemKind,
+        #[clap(value_parser)]
+        id: String,
+    },
+    /// Remove an album, artist or playlist from your favorites
+    Unfavorite {
+        #[clap(value_enum)]
+        kind: ItemKind,
+        #[clap(value_parser)]
+        id: String,
+    },
+    /// List recently played tracks, newest first
+    History {
+        #[clap(long, short, default_value_t = 50)]
+        limit: i64,
+        #[clap(short, long = "output", value_enum)]
+        output_format: Option<OutputFormat>,
+    },
+    /// Download the audio file of a track. Writes to stdout when no file is given,
+    /// so it can be piped into other tools.
+    Download {
+        #[clap(value_parser)]
+        track_id: i32,
+        /// File to write to. Use - for stdout. When this is a directory, the track is saved
+        /// under the name used to find it again with --download-dir.
+        #[clap(short = 'f', long)]
+        file: Option<PathBuf>,
+    },
+    /// List the audio outputs, with the sink and device to configure to use them.
+    ListDevices {
+        #[clap(short, long = "output", value_enum)]
+        output_format: Option<OutputFormat>,
+    },
+    /// Download the tracks of a playlist into a folder named after it, together with
+    /// an M3U file that keeps the playlist order. The tracks are saved under the names
+    /// used to find them again with --download-dir.
+    DownloadPlaylist {
+        #[clap(value_parser)]
+        playlist_id: i64,
+        /// Folder to create the playlist folder in.
+        #[clap(short, long, default_value = ".")]
+        dir: PathBuf,
+        /// Only download tracks that are missing or incomplete in the playlist folder.
+        #[clap(long, default_value_t = false)]
+        sync: bool,
+    },
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum JsonFormat {
+    #[default]
+    Pretty,
+    Compact,
+}
+
+/// Set from `--format` before any command runs.
+static COMPACT_JSON: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FavoriteKind {
+    Albums,
+    Artists,
+    Tracks,
+    All,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ItemKind {
+    Album,
+    Artist,
+    Playlist,
+}
+
+impl std::fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ItemKind::Album => f.write_str("album"),
+            ItemKind::Artist => f.write_str("artist"),
+            ItemKind::Playlist => f.write_str("playlist"),
+        }
+    }
 }
 
 #[derive(Subcommand)]
@@ -52,7 +239,7 @@ pub enum ApiCommands {
     Search {
         #[clap(value_parser)]
         query: String,
-        #[clap(long, short)]
+        #[clap(long, short, value_parser = clap::value_parser!(i32).range(1..))]
         limit: Option<i32>,
         #[clap(short, long = "output", value_enum)]
         output_format: Option<OutputFormat>,
@@ -61,8 +248,21 @@ pub enum ApiCommands {
     SearchAlbums {
         #[clap(value_parser)]
         query: String,
-        #[clap(long, short)]
+        #[clap(long, short, value_parser = clap::value_parser!(i32).range(1..))]
+        limit: Option<i32>,
+        #[clap(long)]
+        offset: Option<i32>,
+        #[clap(short, long = "output", value_enum)]
+        output_format: Option<OutputFormat>,
+    },
+    /// Search for tracks in the Qobuz database
+    SearchTracks {
+        #[clap(value_parser)]
+        query: String,
+        #[clap(long, short, value_parser = clap::value_parser!(i32).range(1..))]
         limit: Option<i32>,
+        #[clap(long)]
+        offset: Option<i32>,
         #[clap(short, long = "output", value_enum)]
         output_format: Option<OutputFormat>,
     },
@@ -70,7 +270,7 @@ pub enum ApiCommands {
     SearchArtists {
         #[clap(value_parser)]
         query: String,
-        #[clap(long, short)]
+        #[clap(long, short, value_parser = clap::value_parser!(i32).range(1..))]
         limit: Option<i32>,
         #[clap(short, long = "output", value_enum)]
         output_format: Option<OutputFormat>,
@@ -84,6 +284,18 @@ pub enum ApiCommands {
     Artist {
         #[clap(value_parser)]
         id: i32,
+        /// Order of the albums, newest first unless set.
+        #[clap(long, value_enum, default_value_t)]
+        sort: ReleaseSort,
+        #[clap(short, long = "output", value_enum)]
+        output_format: Option<OutputFormat>,
+    },
+    /// Retrieve the most popular tracks for an artist.
+    ArtistTracks {
+        #[clap(value_parser)]
+        id: i32,
+        #[clap(long, short)]
+        limit: Option<i32>,
         #[clap(short, long = "output", value_enum)]
         output_format: Option<OutputFormat>,
     },
@@ -93,6 +305,13 @@ pub enum ApiCommands {
         #[clap(short, long = "output", value_enum)]
         output_format: Option<OutputFormat>,
     },
+    /// Find the tracks with an ISRC, one for each release it appears on.
+    Isrc {
+        #[clap(value_parser)]
+        isrc: String,
+        #[clap(short, long = "output", value_enum)]
+        output_format: Option<OutputFormat>,
+    },
     /// Retrieve information about a specific playlist.
     Playlist {
         #[clap(value_parser)]
@@ -107,9 +326,74 @@ pub enum ConfigCommands {
     /// Save username to database.
     #[clap(value_parser)]
     Username {},
-    /// Save password to database.
+    /// Log in with your password. Only the resulting token is saved.
     #[clap(value_parser)]
     Password {},
+    /// Remove the saved token and any saved password, keeping the rest of the configuration.
+    #[clap(value_parser)]
+    Logout {
+        /// Also forget the saved username.
+        #[clap(long)]
+        username: bool,
+    },
+    /// Switch to a profile with its own credentials, creating it if needed.
+    /// Lists the profiles when no name is given.
+    Profile {
+        #[clap(value_parser)]
+        name: Option<String>,
+    },
+    /// Require a password to use the web interface. Leave empty to disable.
+    #[clap(value_parser)]
+    WebPassword {},
+    /// Play through this GStreamer audio sink and device. See list-devices.
+    /// Leave empty to use the default output.
+    AudioOutput {
+        #[clap(value_parser)]
+        sink: Option<String>,
+        #[clap(value_parser, requires = "sink")]
+        device: Option<String>,
+    },
+    /// Play tracks at their native sample rate instead of resampling them to the
+    /// output's rate: on or off. Playback fails when the output can not play the rate.
+    BitPerfect {
+        #[clap(value_parser = clap::builder::BoolishValueParser::new())]
+        enabled: bool,
+    },
+    /// Send this User-Agent to Qobuz, for example to mimic an official client.
+    /// Leave empty to use the default.
+    UserAgent {
+        #[clap(value_parser)]
+        user_agent: Option<String>,
+    },
+    /// Never stream above this quality. Leave empty to remove the limit.
+    MaxQuality {
+        #[clap(value_enum)]
+        quality: Option<AudioQuality>,
+    },
+    /// Show the current configuration. Credentials are only shown as set or unset.
+    Show {},
+    /// Write the configuration to a JSON file.
+    Export {
+        #[clap(value_parser)]
+        path: PathBuf,
+        /// Include the token, app_id, secret and web password hash.
+        #[clap(long)]
+        include_secrets: bool,
+    },
+    /// Read the configuration from a file written by export.
+    Import {
+        #[clap(value_parser)]
+        path: PathBuf,
+    },
+    /// Scrape the app_id and secrets from the web player and test them, printing
+    /// what was found at each step.
+    #[clap(hide = true)]
+    Debug {},
+    /// Normalize loudness using ReplayGain: off, track or album.
+    ReplayGain {
+        #[clap(value_parser)]
+        mode: ReplayGainMode,
+    },
 }
 
 #[derive(Debug, Snafu)]
@@ -120,6 +404,17 @@ pub enum Error {
     PlayerError { error: String },
     #[snafu(display("{error}"))]
     TerminalError { error: String },
+    #[snafu(display("{error}"))]
+    WebError { error: String },
+    #[snafu(display("{command} is interactive and cannot be used with --json"))]
+    Interactive { command: String },
+}
+
+impl Error {
+    /// The error as a JSON object, for use with `--json`.
+    pub fn to_json(&self) -> String {
+        serde_json::json!({ "error": self.to_string() }).to_string()
+    }
 }
 
 impl From<hifirs_qobuz_api::Error> for Error {
@@ -138,13 +433,91 @@ impl From<hifirs_player::error::Error> for Error {
     }
 }
 
+fn output<T: Serialize>(
+    value: &T,
+    output_format: Option<OutputFormat>,
+    json: bool,
+) -> Result<(), Error> {
+    let output_format = if json {
+        OutputFormat::Json
+    } else {
+        output_format.unwrap_or(OutputFormat::Json)
+    };
+
+    match output_format {
+        OutputFormat::Json => {
+            let json = if COMPACT_JSON.load(Ordering::Relaxed) {
+                serde_json::to_string(value)
+            } else {
+                serde_json::to_string_pretty(value)
+            }
+            .map_err(|error| Error::ClientError {
+                error: error.to_string(),
+            })?;
+
+            println!("{json}");
+            Ok(())
+        }
+        OutputFormat::Tsv => Err(Error::ClientError {
+            error: "tsv output is not supported for this command".to_string(),
+        }),
+        OutputFormat::Jsonl => Err(Error::ClientError {
+            error: "jsonl output is not supported for this command".to_string(),
+        }),
+    }
+}
+
+/// Print search results like `output`, or one result per line with `--output jsonl`.
+fn output_results<T: Serialize, I: Serialize>(
+    results: &T,
+    items: &[I],
+    output_format: Option<OutputFormat>,
+    json: bool,
+) -> Result<(), Error> {
+    if !matches!(output_format, Some(OutputFormat::Jsonl)) {
+        return output(results, output_format, json);
+    }
+
+    let mut writer = BufWriter::new(std::io::stdout().lock());
+
+    for item in items {
+        serde_json::to_writer(&mut writer, item)
+            .map_err(std::io::Error::from)
+            .and_then(|()| writeln!(writer))
+            .map_err(|error| Error::ClientError {
+                error: error.to_string(),
+            })?;
+    }
+
+    writer.flush().map_err(|error| Error::ClientError {
+        error: error.to_string(),
+    })
+}
+
 async fn setup_player(
     web: bool,
-    interface: String,
+    interface: SocketAddr,
+    allowed_origins: Vec<String>,
+    keep_alive: Duration,
     username: Option<&str>,
     password: Option<&str>,
+    audio_output: (Option<String>, Option<String>),
 ) -> Result<Vec<JoinHandle<()>>, Error> {
+    let listener = if web {
+        let listener = hifirs_web::bind(interface)
+            .await
+            .map_err(|error| Error::WebError {
+                error: format!("failed to listen on {interface}: {error}"),
+            })?;
+
+        Some(listener)
+    } else {
+        None
+    };
+
     hifirs_player::init(username, password).await?;
+    use_audio_output(audio_output).await;
+    hifirs_player::set_bit_perfect(db::bit_perfect().await);
 
     let mut handles: Vec<JoinHandle<()>> = Vec::new();
 
@@ -157,10 +530,10 @@ async fn setup_player(
         }));
     }
 
-    if web {
-        handles.push(tokio::spawn(
-            async move { hifirs_web::init(interface).await },
-        ));
+    if let Some(listener) = listener {
+        handles.push(tokio::spawn(async move {
+            hifirs_web::init(listener, allowed_origins, keep_alive).await
+        }));
     }
 
     handles.push(tokio::spawn(async {
@@ -173,7 +546,444 @@ async fn setup_player(
     Ok(handles)
 }
 
-pub async fn run() -> Result<(), Error> {
+/// Ask for a password and log in, saving only the token. Asks for the
+/// username as well when none is given or saved.
+async fn prompt_login(username: Option<String>) -> Result<(), Error> {
+    let username = match username {
+        Some(username) => username,
+        None => match db::get_config().await.and_then(|config| config.username) {
+            Some(username) => username,
+            None => {
+                let username: String = Input::new()
+                    .with_prompt("Enter your username / email")
+                    .interact_text()
+                    .map_err(|error| Error::TerminalError {
+                        error: error.to_string(),
+                    })?;
+
+                db::set_username(username.clone()).await;
+                username
+            }
+        },
+    };
+
+    let password = Password::new()
+        .with_prompt("Enter your password (hidden)")
+        .interact()
+        .map_err(|error| Error::TerminalError {
+            error: error.to_string(),
+        })?;
+
+    let md5_pw = format!("{:x}", md5::compute(password));
+
+    let client = hifirs_player::qobuz::login(&username, &md5_pw).await?;
+
+    if client.signed_in() {
+        Ok(())
+    } else {
+        Err(Error::ClientError {
+            error: "login failed".into(),
+        })
+    }
+}
+
+/// Make sure a working token is saved before starting the player, asking for
+/// the password when there is none or it has expired.
+async fn ensure_signed_in(
+    username: Option<&str>,
+    password: Option<&str>,
+    json: bool,
+) -> Result<(), Error> {
+    let client = hifirs_player::qobuz::make_client(username, password).await?;
+
+    if client.signed_in() && hifirs_player::qobuz::verify_token(&client).await {
+        return Ok(());
+    }
+
+    if json {
+        return Err(Error::Interactive {
+            command: "logging in".into(),
+        });
+    }
+
+    prompt_login(username.map(|u| u.to_string())).await
+}
+
+/// Whether the album, artist or playlist is already in the user's favorites.
+async fn is_favorite(client: &QobuzClient, kind: ItemKind, id: &str) -> Result<bool, Error> {
+    match kind {
+        ItemKind::Album => {
+            let favorites = client.favorites(1000).await?;
+            Ok(favorites.albums.items.iter().any(|album| album.id == id))
+        }
+        ItemKind::Artist => {
+            let favorites = client.favorites(1000).await?;
+            Ok(favorites
+                .artists
+                .items
+                .iter()
+                .any(|artist| artist.id.to_string() == id))
+        }
+        ItemKind::Playlist => {
+            let playlists = client.user_playlists().await?;
+            Ok(playlists
+                .playlists
+                .items
+                .iter()
+                .any(|playlist| playlist.id.to_string() == id))
+        }
+    }
+}
+
+/// Add or remove a favorite, doing nothing when it is already in the wanted state.
+async fn set_favorite(
+    client: &QobuzClient,
+    kind: ItemKind,
+    id: &str,
+    favorite: bool,
+    json: bool,
+) -> Result<(), Error> {
+    let changed = if is_favorite(client, kind, id).await? == favorite {
+        false
+    } else {
+        match (kind, favorite) {
+            (ItemKind::Album, true) => client.add_favorite_album(id).await?,
+            (ItemKind::Album, false) => client.remove_favorite_album(id).await?,
+            (ItemKind::Artist, true) => client.add_favorite_artist(id).await?,
+            (ItemKind::Artist, false) => client.remove_favorite_artist(id).await?,
+            (ItemKind::Playlist, true) => client.add_favorite_playlist(id).await?,
+            (ItemKind::Playlist, false) => client.remove_favorite_playlist(id).await?,
+        };
+
+        true
+    };
+
+    if json {
+        return output(
+            &serde_json::json!({
+                "kind": kind.to_string(),
+                "id": id,
+                "favorite": favorite,
+                "changed": changed,
+            }),
+            None,
+            json,
+        );
+    }
+
+    match (changed, favorite) {
+        (true, true) => println!("Added {kind} {id} to favorites."),
+        (true, false) => println!("Removed {kind} {id} from favorites."),
+        (false, true) => println!("The {kind} {id} is already a favorite."),
+        (false, false) => println!("The {kind} {id} is not a favorite."),
+    }
+
+    Ok(())
+}
+
+/// Resolves when the process is asked to stop, as systemd and Docker do with SIGTERM.
+#[cfg(unix)]
+async fn terminate_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut terminate) => {
+            terminate.recv().await;
+        }
+        Err(error) => {
+            debug!("failed to listen for sigterm: {error}");
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn terminate_signal() {
+    std::future::pending::<()>().await;
+}
+
+/// Runs the TUI, or without it waits for ctrl-c, SIGTERM or the player to quit,
+/// then shuts the player down.
+async fn run_player(
+    mut handles: Vec<JoinHandle<()>>,
+    disable_tui: bool,
+    no_mouse: bool,
+    no_album_art: bool,
+) -> Result<(), Error> {
+    let mut player_quit = false;
+
+    if !disable_tui {
+        let mut tui = hifirs_tui::CursiveUI::new();
+        tui.set_mouse(!no_mouse);
+        tui.set_album_art(!no_album_art);
+        handles.push(tokio::spawn(async {
+            hifirs_tui::receive_notifications().await
+        }));
+
+        // The TUI reads ctrl-c as a key press, so only SIGTERM is left to handle.
+        let terminate = tokio::spawn(async {
+            terminate_signal().await;
+            debug!("sigterm received, closing the tui");
+            hifirs_tui::quit();
+        });
+
+        tui.run().await;
+        terminate.abort();
+        debug!("tui exited, quitting");
+    } else {
+        debug!("waiting for ctrlc, sigterm or quit");
+        let mut notifications = hifirs_player::notify_receiver();
+
+        // The player can also be told to quit from elsewhere, for example over MPRIS.
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => {
+                result.expect("error waiting for ctrlc");
+                debug!("ctrlc received, quitting");
+            }
+            _ = terminate_signal() => {
+                debug!("sigterm received, quitting");
+            }
+            _ = async move {
+                while let Ok(notification) = notifications.recv().await {
+                    if matches!(notification, Notification::Quit) {
+                        break;
+                    }
+                }
+            } => {
+                debug!("player quit");
+                player_quit = true;
+            }
+        }
+    }
+
+    if !player_quit {
+        hifirs_player::quit().await?;
+    }
+
+    for h in handles {
+        match h.await {
+            Ok(_) => debug!("task exited"),
+            Err(error) => debug!("task error {error}"),
+        };
+    }
+
+    Ok(())
+}
+
+/// Play through the sink and device from the command line, or else the configured ones.
+/// Keeps the default output, with a warning, when they are not available.
+async fn use_audio_output((sink, device): (Option<String>, Option<String>)) {
+    let (sink, device) = match sink {
+        Some(sink) => (Some(sink), device),
+        None => db::get_config()
+            .await
+            .map(|config| (config.audio_sink, config.audio_device))
+            .unwrap_or_default(),
+    };
+
+    if let Some(sink) = sink {
+        if let Err(error) = hifirs_player::set_audio_output(&sink, device.as_deref()) {
+            warn!("{error}, using the default output");
+            eprintln!("{error}, using the default output.");
+        }
+    }
+}
+
+/// Clamp a search limit to the most results Qobuz returns, warning when it is lowered.
+fn search_limit(limit: i32) -> i32 {
+    if limit > MAX_SEARCH_LIMIT {
+        eprintln!(
+            "Limit {limit} is above the maximum of {MAX_SEARCH_LIMIT}, using {MAX_SEARCH_LIMIT}."
+        );
+        MAX_SEARCH_LIMIT
+    } else {
+        limit
+    }
+}
+
+/// Draw a progress bar for a download on stderr, overwriting the previous one.
+fn print_progress(done: u64, total: Option<u64>) {
+    const WIDTH: u64 = 30;
+    const MB: f64 = 1024.0 * 1024.0;
+
+    let mut stderr = std::io::stderr();
+
+    let _ = match total {
+        Some(total) if total > 0 => {
+            let filled = (done * WIDTH / total).min(WIDTH) as usize;
+            write!(
+                stderr,
+                "\r[{}{}] {:>3}% {:.1}/{:.1} MB",
+                "#".repeat(filled),
+                " ".repeat(WIDTH as usize - filled),
+                done * 100 / total,
+                done as f64 / MB,
+                total as f64 / MB,
+            )
+        }
+        _ => write!(stderr, "\r{:.1} MB", done as f64 / MB),
+    };
+    let _ = stderr.flush();
+}
+
+/// Download `url` into a new file at `path`, drawing a progress bar on stderr when
+/// `progress` is set and stderr is a terminal.
+async fn download_to_file(
+    client: &QobuzClient,
+    url: &str,
+    path: &Path,
+    progress: bool,
+) -> Result<u64, Error> {
+    let mut writer = std::fs::File::create(path)
+        .map(BufWriter::new)
+        .map_err(|error| Error::ClientError {
+            error: format!("failed to create {}: {error}", path.display()),
+        })?;
+    let result = if progress && std::io::stderr().is_terminal() {
+        let result = client
+            .download_with_progress(url, &mut writer, print_progress)
+            .await;
+        eprintln!();
+        result
+    } else {
+        client.download(url, &mut writer).await
+    };
+
+    // Do not leave a truncated file behind that looks like a finished download.
+    match result {
+        Ok(bytes) => Ok(bytes),
+        Err(error) => {
+            drop(writer);
+            let _ = std::fs::remove_file(path);
+            Err(error.into())
+        }
+    }
+}
+
+/// Whether `path` already holds the whole file at `url`. Files of unknown size are
+/// downloaded again, as there is no way to tell if they are complete.
+async fn is_complete(client: &QobuzClient, url: &str, path: &Path) -> Result<bool, Error> {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return Ok(false);
+    };
+
+    Ok(client.download_size(url).await? == Some(metadata.len()))
+}
+
+/// Show only the start of a secret, enough to tell secrets apart.
+fn mask(secret: &str) -> String {
+    let start = secret.chars().take(4).collect::<String>();
+
+    format!("{start}{}", "*".repeat(secret.chars().count().saturating_sub(4)))
+}
+
+/// Replace the characters that are not allowed in file names on common file systems.
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Pick the album to play from the search results, asking the user unless
+/// `first` or `exact` is set.
+fn select_album(
+    query: &str,
+    albums: Vec<Album>,
+    first: bool,
+    exact: bool,
+    list_length: Option<u64>,
+) -> Result<Album, Error> {
+    if albums.is_empty() {
+        return Err(Error::ClientError {
+            error: format!("no albums found for '{query}'"),
+        });
+    }
+
+    if first {
+        return Ok(albums.into_iter().next().expect("albums is not empty"));
+    }
+
+    if exact {
+        let query = query.to_lowercase();
+        let mut matches = albums.into_iter().filter(|album| {
+            let title = album.title.to_lowercase();
+            let artist = album.artist.name.to_lowercase();
+
+            query == title
+                || query == format!("{title} {artist}")
+                || query == format!("{artist} {title}")
+        });
+
+        return match (matches.next(), matches.next()) {
+            (Some(album), None) => Ok(album),
+            (None, _) => Err(Error::ClientError {
+                error: format!("no album matches '{query}' exactly"),
+            }),
+            (Some(_), Some(_)) => Err(Error::ClientError {
+                error: format!("more than one album matches '{query}'"),
+            }),
+        };
+    }
+
+    let items = albums
+        .iter()
+        .map(|album| format!("{} - {}", album.title, album.artist.name))
+        .collect::<Vec<String>>();
+
+    let mut select = Select::new()
+        .with_prompt("Choose an album")
+        .items(&items)
+        .default(0);
+
+    if let Some(list_length) = list_length {
+        select = select.max_length(list_length as usize);
+    }
+
+    let selected = select
+        .interact()
+        .map_err(|error| Error::TerminalError {
+            error: error.to_string(),
+        })?;
+
+    Ok(albums
+        .into_iter()
+        .nth(selected)
+        .expect("selection is in range"))
+}
+
+/// Crates whose logs are shown by `--verbose`, so dependencies stay quiet.
+const LOG_TARGETS: [&str; 5] = [
+    "hifi_rs",
+    "hifirs_player",
+    "hifirs_qobuz_api",
+    "hifirs_tui",
+    "hifirs_web",
+];
+
+fn log_filter(verbose: u8, quiet: bool) -> EnvFilter {
+    let level = match (quiet, verbose) {
+        (true, _) => return EnvFilter::new("off"),
+        (false, 0) => return EnvFilter::from_env("HIFIRS_LOG"),
+        (false, 1) => "info",
+        (false, 2) => "debug",
+        _ => "trace",
+    };
+
+    let directives = LOG_TARGETS
+        .iter()
+        .map(|target| format!("{target}={level}"))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    EnvFilter::new(format!("error,{directives}"))
+}
+
+pub async fn run(cli: Cli) -> Result<(), Error> {
     tracing_subscriber::registry()
         .with(
             fmt::layer()
@@ -181,58 +991,131 @@ pub async fn run() -> Result<(), Error> {
                 .with_file(false)
                 .with_writer(std::io::stderr),
         )
-        .with(EnvFilter::from_env("HIFIRS_LOG"))
+        .with(log_filter(cli.verbose, cli.quiet))
         .init();
 
-    // PARSE CLI ARGS
-    let cli = Cli::parse();
+    let json = cli.json;
+    COMPACT_JSON.store(cli.format == JsonFormat::Compact, Ordering::Relaxed);
 
     // INIT DB
     db::init().await;
+    db::select_profile(cli.profile.clone()).await;
+
+    if let Some(quality) = cli.quality {
+        hifirs_player::qobuz::set_quality(quality);
+    }
+
+    if let Some(refresh_interval) = cli.refresh_interval {
+        hifirs_player::set_refresh_interval(Duration::from_millis(refresh_interval));
+    }
+
+    if let Some(dir) = cli.download_dir.clone() {
+        hifirs_player::qobuz::set_download_dir(dir);
+    }
 
     // CLI COMMANDS
     match cli.command {
         Commands::Open {} => {
-            let mut handles = setup_player(
+            if json && !cli.disable_tui {
+                return Err(Error::Interactive {
+                    command: "open without --disable-tui".into(),
+                });
+            }
+
+            ensure_signed_in(cli.username.as_deref(), cli.password.as_deref(), json).await?;
+
+            let handles = setup_player(
                 cli.web,
                 cli.interface,
+                cli.allowed_origins,
+                Duration::from_secs(cli.keep_alive.max(1)),
                 cli.username.as_deref(),
                 cli.password.as_deref(),
+                (cli.audio_sink, cli.audio_device),
             )
             .await?;
 
-            if !(cli.disable_tui) {
-                let mut tui = hifirs_tui::CursiveUI::new();
-                handles.push(tokio::spawn(async {
-                    hifirs_tui::receive_notifications().await
-                }));
-                tui.run().await;
-                debug!("tui exited, quitting");
-                hifirs_player::quit().await?;
-                for h in handles {
-                    match h.await {
-                        Ok(_) => debug!("task exited"),
-                        Err(error) => debug!("task error {error}"),
-                    };
-                }
-            } else {
-                debug!("waiting for ctrlc");
-                tokio::signal::ctrl_c()
-                    .await
-                    .expect("error waiting for ctrlc");
-                debug!("ctrlc received, quitting");
-                hifirs_player::quit().await?;
-                for h in handles {
-                    match h.await {
-                        Ok(_) => debug!("task exited"),
-                        Err(error) => debug!("task error {error}"),
-                    };
-                }
-            };
+            run_player(handles, cli.disable_tui, cli.no_mouse, cli.no_album_art).await
+        }
+        Commands::Play {
+            query,
+            first,
+            exact,
+            limit,
+            list_length,
+        } => {
+            if json && !cli.disable_tui {
+                return Err(Error::Interactive {
+                    command: "play without --disable-tui".into(),
+                });
+            }
 
-            Ok(())
+            if json && !first && !exact {
+                return Err(Error::Interactive {
+                    command: "play without --first or --exact".into(),
+                });
+            }
+
+            ensure_signed_in(cli.username.as_deref(), cli.password.as_deref(), json).await?;
+
+            let client =
+                hifirs_player::qobuz::make_client(cli.username.as_deref(), cli.password.as_deref())
+                    .await?;
+
+            let results = client
+                .search_albums(&query, Some(search_limit(limit)), None)
+                .await?;
+            let album = select_album(&query, results.albums.items, first, exact, list_length)?;
+
+            let handles = setup_player(
+                cli.web,
+                cli.interface,
+                cli.allowed_origins,
+                Duration::from_secs(cli.keep_alive.max(1)),
+                cli.username.as_deref(),
+                cli.password.as_deref(),
+                (cli.audio_sink, cli.audio_device),
+            )
+            .await?;
+
+            hifirs_player::play_album(&album.id).await?;
+
+            run_player(handles, cli.disable_tui, cli.no_mouse, cli.no_album_art).await
+        }
+        Commands::Resume {} => {
+            if json && !cli.disable_tui {
+                return Err(Error::Interactive {
+                    command: "resume without --disable-tui".into(),
+                });
+            }
+
+            ensure_signed_in(cli.username.as_deref(), cli.password.as_deref(), json).await?;
+
+            let handles = setup_player(
+                cli.web,
+                cli.interface,
+                cli.allowed_origins,
+                Duration::from_secs(cli.keep_alive.max(1)),
+                cli.username.as_deref(),
+                cli.password.as_deref(),
+                (cli.audio_sink, cli.audio_device),
+            )
+            .await?;
+
+            hifirs_player::resume().await?;
+
+            run_player(handles, cli.disable_tui, cli.no_mouse, cli.no_album_art).await
         }
         Commands::Config { command } => match command {
+            ConfigCommands::Username {}
+            | ConfigCommands::Password {}
+            | ConfigCommands::WebPassword {}
+                if json =>
+            {
+                Err(Error::Interactive {
+                    command: "config".into(),
+                })
+            }
             ConfigCommands::Username {} => {
                 if let Ok(username) = Input::new()
                     .with_prompt("Enter your username / email")
@@ -245,20 +1128,608 @@ pub async fn run() -> Result<(), Error> {
                 Ok(())
             }
             ConfigCommands::Password {} => {
+                prompt_login(cli.username).await?;
+
+                println!("Logged in.");
+                Ok(())
+            }
+            ConfigCommands::Logout { username } => {
+                let config = db::get_config().await;
+                let mut removed = vec![];
+
+                if config.as_ref().is_some_and(|c| c.user_token.is_some()) {
+                    db::clear_user_token().await;
+                    removed.push("token");
+                }
+
+                if config.as_ref().is_some_and(|c| c.password.is_some()) {
+                    db::clear_password().await;
+                    removed.push("password");
+                }
+
+                if username && config.as_ref().is_some_and(|c| c.username.is_some()) {
+                    db::clear_username().await;
+                    removed.push("username");
+                }
+
+                if json {
+                    output(&serde_json::json!({ "removed": removed }), None, json)
+                } else {
+                    if removed.is_empty() {
+                        println!("Not logged in, nothing removed.");
+                    } else {
+                        println!("Logged out, removed {}.", removed.join(", "));
+                    }
+                    Ok(())
+                }
+            }
+            ConfigCommands::Profile { name: Some(name) } => {
+                db::set_profile(&name).await;
+
+                if json {
+                    output(&serde_json::json!({ "profile": name }), None, json)
+                } else {
+                    println!("Switched to profile {name}.");
+                    Ok(())
+                }
+            }
+            ConfigCommands::Profile { name: None } => {
+                let profiles = db::profiles().await;
+                let active = db::active_profile();
+
+                if json {
+                    output(
+                        &serde_json::json!({ "active": active, "profiles": profiles }),
+                        None,
+                        json,
+                    )
+                } else {
+                    for profile in profiles {
+                        let marker = if profile == active { "*" } else { " " };
+                        println!("{marker} {profile}");
+                    }
+                    Ok(())
+                }
+            }
+            ConfigCommands::WebPassword {} => {
                 if let Ok(password) = Password::new()
-                    .with_prompt("Enter your password (hidden)")
+                    .with_prompt("Enter a password for the web interface (hidden)")
+                    .allow_empty_password(true)
                     .interact()
                 {
-                    let md5_pw = format!("{:x}", md5::compute(password));
+                    if password.is_empty() {
+                        db::set_web_password(None).await;
 
-                    debug!("saving password to database: {}", md5_pw);
+                        println!("Web password removed.");
+                    } else {
+                        let hash = hifirs_web::auth::hash_password(&password);
 
-                    db::set_password(md5_pw).await;
+                        db::set_web_password(Some(hash)).await;
 
-                    println!("Password saved.");
+                        println!("Web password saved.");
+                    }
                 }
                 Ok(())
             }
+            ConfigCommands::AudioOutput { sink, device } => {
+                db::set_audio_output(sink.as_deref(), device.as_deref()).await;
+
+                if json {
+                    output(
+                        &serde_json::json!({ "audioSink": sink, "audioDevice": device }),
+                        None,
+                        json,
+                    )
+                } else {
+                    match (sink, device) {
+                        (Some(sink), Some(device)) => {
+                            println!("Audio output set to {sink} on {device}.")
+                        }
+                        (Some(sink), None) => println!("Audio output set to {sink}."),
+                        _ => println!("Audio output set to the default."),
+                    }
+                    Ok(())
+                }
+            }
+            ConfigCommands::BitPerfect { enabled } => {
+                db::set_bit_perfect(enabled).await;
+
+                if json {
+                    output(&serde_json::json!({ "bitPerfect": enabled }), None, json)
+                } else {
+                    if enabled {
+                        println!("Bit-perfect playback turned on.");
+                    } else {
+                        println!("Bit-perfect playback turned off.");
+                    }
+                    Ok(())
+                }
+            }
+            ConfigCommands::Debug {} => {
+                let mut client = hifirs_qobuz_api::client::api::new(None, None, None).await?;
+                if let Some(user_agent) = db::get_config().await.and_then(|c| c.user_agent) {
+                    client.set_user_agent(&user_agent)?;
+                }
+
+                let result = match client.refresh().await {
+                    Ok(()) => client.test_secrets().await,
+                    Err(error) => Err(error),
+                };
+                let app_id = client.get_app_id();
+                let secrets_found = client.secret_count();
+                let active_secret = client.get_active_secret().map(|secret| mask(&secret));
+                let error = result.err().map(|error| error.to_string());
+
+                if json {
+                    output(
+                        &serde_json::json!({
+                            "appId": app_id,
+                            "secretsFound": secrets_found,
+                            "activeSecret": active_secret,
+                            "error": error,
+                        }),
+                        None,
+                        json,
+                    )
+                } else {
+                    println!("{:<20}{}", "app_id", app_id.as_deref().unwrap_or("not found"));
+                    println!("{:<20}{secrets_found}", "secrets found");
+                    println!(
+                        "{:<20}{}",
+                        "working secret",
+                        active_secret.as_deref().unwrap_or("none")
+                    );
+                    if let Some(error) = error {
+                        println!("{:<20}{error}", "error");
+                    }
+                    Ok(())
+                }
+            }
+            ConfigCommands::UserAgent { user_agent } => {
+                db::set_user_agent(user_agent.as_deref()).await;
+
+                if json {
+                    output(&serde_json::json!({ "userAgent": user_agent }), None, json)
+                } else {
+                    match user_agent {
+                        Some(user_agent) => println!("User-Agent set to {user_agent}."),
+                        None => println!("User-Agent set to the default."),
+                    }
+                    Ok(())
+                }
+            }
+            ConfigCommands::MaxQuality { quality } => {
+                db::set_max_quality(quality).await;
+
+                if json {
+                    output(
+                        &serde_json::json!({ "maxQuality": quality.map(|q| q.name()) }),
+                        None,
+                        json,
+                    )
+                } else {
+                    match quality {
+                        Some(quality) => println!("Maximum quality set to {}.", quality.name()),
+                        None => println!("Maximum quality removed."),
+                    }
+                    Ok(())
+                }
+            }
+            ConfigCommands::Show {} => {
+                let config = db::get_config().await.unwrap_or_default();
+                let set = |value: &Option<String>| if value.is_some() { "set" } else { "unset" };
+                let replaygain_mode = config
+                    .replaygain_mode
+                    .as_deref()
+                    .and_then(|mode| mode.parse::<ReplayGainMode>().ok())
+                    .unwrap_or_default();
+                let secrets_updated_at = config
+                    .secrets_updated_at
+                    .and_then(|timestamp| chrono::DateTime::from_timestamp(timestamp, 0))
+                    .map(|date| date.to_rfc3339());
+                let bit_perfect = if config.bit_perfect.unwrap_or_default() {
+                    "on"
+                } else {
+                    "off"
+                };
+
+                let settings = [
+                    ("profile", Some(db::active_profile().to_string())),
+                    ("username", config.username.clone()),
+                    ("token", Some(set(&config.user_token).to_string())),
+                    ("password", Some(set(&config.password).to_string())),
+                    ("app_id", config.app_id.clone()),
+                    ("secret", Some(set(&config.active_secret).to_string())),
+                    ("secrets_updated_at", secrets_updated_at),
+                    ("web_password", Some(set(&config.web_password).to_string())),
+                    ("replaygain", Some(replaygain_mode.to_string())),
+                    (
+                        "quality",
+                        Some(hifirs_player::qobuz::quality().name().to_string()),
+                    ),
+                    ("max_quality", config.max_quality.clone()),
+                    ("audio_sink", config.audio_sink.clone()),
+                    ("audio_device", config.audio_device.clone()),
+                    ("bit_perfect", Some(bit_perfect.to_string())),
+                    (
+                        "user_agent",
+                        Some(
+                            config
+                                .user_agent
+                                .clone()
+                                .unwrap_or(DEFAULT_USER_AGENT.to_string()),
+                        ),
+                    ),
+                ];
+
+                if json {
+                    let settings = settings
+                        .into_iter()
+                        .map(|(name, value)| (name.to_string(), serde_json::json!(value)))
+                        .collect::<serde_json::Map<_, _>>();
+                    output(&settings, None, json)
+                } else {
+                    for (name, value) in settings {
+                        println!("{name:<20}{}", value.as_deref().unwrap_or("unset"));
+                    }
+                    Ok(())
+                }
+            }
+            ConfigCommands::Export {
+                path,
+                include_secrets,
+            } => {
+                let config = db::export_config(include_secrets).await;
+                let contents =
+                    serde_json::to_string_pretty(&config).map_err(|error| Error::ClientError {
+                        error: error.to_string(),
+                    })?;
+
+                std::fs::write(&path, contents).map_err(|error| Error::ClientError {
+                    error: format!("failed to write {}: {error}", path.display()),
+                })?;
+
+                if json {
+                    output(&serde_json::json!({ "path": path }), None, json)
+                } else {
+                    println!("Configuration exported to {}.", path.display());
+                    Ok(())
+                }
+            }
+            ConfigCommands::Import { path } => {
+                let contents =
+                    std::fs::read_to_string(&path).map_err(|error| Error::ClientError {
+                        error: format!("failed to read {}: {error}", path.display()),
+                    })?;
+                let config =
+                    serde_json::from_str(&contents).map_err(|error| Error::ClientError {
+                        error: format!("{} is not a valid configuration: {error}", path.display()),
+                    })?;
+
+                db::import_config(config).await;
+
+                if json {
+                    output(&serde_json::json!({ "path": path }), None, json)
+                } else {
+                    println!("Configuration imported from {}.", path.display());
+                    Ok(())
+                }
+            }
+            ConfigCommands::ReplayGain { mode } => {
+                db::set_replaygain_mode(mode).await;
+
+                if json {
+                    output(
+                        &serde_json::json!({ "replaygainMode": mode.to_string() }),
+                        None,
+                        json,
+                    )
+                } else {
+                    println!("ReplayGain set to {mode}.");
+                    Ok(())
+                }
+            }
         },
+        Commands::Favorites {
+            kind,
+            output_format,
+        } => {
+            let client =
+                hifirs_player::qobuz::make_client(cli.username.as_deref(), cli.password.as_deref())
+                    .await?;
+
+            let favorites = client.favorites(1000).await?;
+
+            match kind {
+                FavoriteKind::Albums => output(&favorites.albums.items, output_format, json),
+                FavoriteKind::Artists => output(&favorites.artists.items, output_format, json),
+                FavoriteKind::Tracks => output(&favorites.tracks.items, output_format, json),
+                FavoriteKind::All => output(&favorites, output_format, json),
+            }
+        }
+        Commands::Favorite { kind, id } => {
+            let client =
+                hifirs_player::qobuz::make_client(cli.username.as_deref(), cli.password.as_deref())
+                    .await?;
+
+            set_favorite(&client, kind, &id, true, json).await
+        }
+        Commands::Unfavorite { kind, id } => {
+            let client =
+                hifirs_player::qobuz::make_client(cli.username.as_deref(), cli.password.as_deref())
+                    .await?;
+
+            set_favorite(&client, kind, &id, false, json).await
+        }
+        Commands::History {
+            limit,
+            output_format,
+        } => {
+            let history = db::history(limit).await;
+            output(&history, output_format, json)
+        }
+        Commands::Download { track_id, file } => {
+            let client =
+                hifirs_player::qobuz::make_client(cli.username.as_deref(), cli.password.as_deref())
+                    .await?;
+            let track_url = client
+                .track_url_with_fallback(track_id, hifirs_player::qobuz::quality())
+                .await?;
+
+            match file.filter(|path| path != Path::new("-")) {
+                Some(path) => {
+                    let path = if path.is_dir() {
+                        path.join(hifirs_player::qobuz::download_file_name(
+                            track_id,
+                            &track_url.mime_type,
+                        ))
+                    } else {
+                        path
+                    };
+                    let bytes = download_to_file(&client, &track_url.url, &path, !json).await?;
+
+                    if json {
+                        output(
+                            &serde_json::json!({ "path": path, "bytes": bytes }),
+                            None,
+                            json,
+                        )
+                    } else {
+                        println!("Downloaded track {track_id} to {}.", path.display());
+                        Ok(())
+                    }
+                }
+                // Nothing else may be printed to stdout here, it would end up in the audio.
+                None => {
+                    let mut writer = BufWriter::new(std::io::stdout());
+                    client.download(&track_url.url, &mut writer).await?;
+
+                    Ok(())
+                }
+            }
+        }
+        Commands::ListDevices { output_format } => {
+            let devices = hifirs_player::output::devices();
+            output(&devices, output_format, json)
+        }
+        Commands::DownloadPlaylist {
+            playlist_id,
+            dir,
+            sync,
+        } => {
+            let client =
+                hifirs_player::qobuz::make_client(cli.username.as_deref(), cli.password.as_deref())
+                    .await?;
+            let playlist = client.playlist(playlist_id).await?;
+            let tracks = playlist.tracks.map(|t| t.items).unwrap_or_default();
+
+            let folder = dir.join(sanitize_file_name(&playlist.name));
+            std::fs::create_dir_all(&folder).map_err(|error| Error::ClientError {
+                error: format!("failed to create {}: {error}", folder.display()),
+            })?;
+
+            let mut entries = vec![];
+            let mut skipped = vec![];
+            let mut failed = vec![];
+            let mut downloaded = 0;
+            let mut up_to_date = 0;
+
+            for track in tracks {
+                if !track.downloadable {
+                    if !json {
+                        eprintln!("Skipping {}, it is not downloadable.", track.title);
+                    }
+                    skipped.push(track.id);
+                    continue;
+                }
+
+                let track_url = match client
+                    .track_url_with_fallback(track.id, hifirs_player::qobuz::quality())
+                    .await
+                {
+                    Ok(track_url) => track_url,
+                    Err(error) => {
+                        error!("failed to get a url for track {}: {error}", track.id);
+                        if !json {
+                            eprintln!("Skipping {}, it is not available: {error}", track.title);
+                        }
+                        failed.push(track.id);
+                        continue;
+                    }
+                };
+
+                // Named like any other download, so offline playback finds it when
+                // the playlist folder is used as the download directory.
+                let file_name =
+                    hifirs_player::qobuz::download_file_name(track.id, &track_url.mime_type);
+                let path = folder.join(&file_name);
+
+                if sync && is_complete(&client, &track_url.url, &path).await? {
+                    up_to_date += 1;
+                } else {
+                    if !json {
+                        eprintln!("Downloading {} to {file_name}", track.title);
+                    }
+
+                    if let Err(error) =
+                        download_to_file(&client, &track_url.url, &path, !json).await
+                    {
+                        error!("failed to download track {}: {error}", track.id);
+                        if !json {
+                            eprintln!("Skipping {}, the download failed: {error}", track.title);
+                        }
+                        failed.push(track.id);
+                        continue;
+                    }
+
+                    downloaded += 1;
+                }
+
+                let artist = track
+                    .performer
+                    .as_ref()
+                    .map(|p| p.name.as_str())
+                    .unwrap_or_default();
+                entries.push(format!(
+                    "#EXTINF:{},{artist} - {}\n{file_name}",
+                    track.duration, track.title
+                ));
+            }
+
+            let playlist_file = folder.join(format!("{}.m3u", sanitize_file_name(&playlist.name)));
+            std::fs::write(
+                &playlist_file,
+                format!("#EXTM3U\n{}\n", entries.join("\n")),
+            )
+            .map_err(|error| Error::ClientError {
+                error: format!("failed to write {}: {error}", playlist_file.display()),
+            })?;
+
+            if json {
+                output(
+                    &serde_json::json!({
+                        "path": folder,
+                        "downloaded": downloaded,
+                        "upToDate": up_to_date,
+                        "skipped": skipped,
+                        "failed": failed,
+                    }),
+                    None,
+                    json,
+                )
+            } else {
+                println!(
+                    "Downloaded {downloaded} tracks of {} to {}.",
+                    playlist.name,
+                    folder.display()
+                );
+                if up_to_date > 0 {
+                    println!("Skipped {up_to_date} tracks that were already downloaded.");
+                }
+                if !skipped.is_empty() {
+                    println!("Skipped {} tracks that are not downloadable.", skipped.len());
+                }
+                if !failed.is_empty() {
+                    println!("Failed to download {} tracks.", failed.len());
+                }
+                Ok(())
+            }
+        }
+        Commands::Api { command } => {
+            let client =
+                hifirs_player::qobuz::make_client(cli.username.as_deref(), cli.password.as_deref())
+                    .await?;
+
+            match command {
+                ApiCommands::Search {
+                    query,
+                    limit,
+                    output_format,
+                } => {
+                    let results = client
+                        .search_all(&query, search_limit(limit.unwrap_or(20)), 0)
+                        .await?;
+                    output(&results, output_format, json)
+                }
+                ApiCommands::SearchAlbums {
+                    query,
+                    limit,
+                    offset,
+                    output_format,
+                } => {
+                    let results = client
+                        .search_albums(&query, limit.map(search_limit), offset)
+                        .await?;
+                    output_results(&results, &results.albums.items, output_format, json)
+                }
+                ApiCommands::SearchTracks {
+                    query,
+                    limit,
+                    offset,
+                    output_format,
+                } => {
+                    let results = client
+                        .search_tracks(&query, limit.map(search_limit), offset)
+                        .await?;
+                    output_results(&results, &results.tracks.items, output_format, json)
+                }
+                ApiCommands::SearchArtists {
+                    query,
+                    limit,
+                    output_format,
+                } => {
+                    let results = client
+                        .search_artists(&query, limit.map(search_limit))
+                        .await?;
+                    output_results(&results, &results.artists.items, output_format, json)
+                }
+                ApiCommands::Album { id, output_format } => {
+                    let album = client.album(&id).await?;
+                    output(&album, output_format, json)
+                }
+                ApiCommands::Artist {
+                    id,
+                    sort,
+                    output_format,
+                } => {
+                    let mut artist = client.artist(id, None).await?;
+                    if let Some(albums) = artist.albums.as_mut() {
+                        sort.sort(&mut albums.items);
+                    }
+
+                    output(&artist, output_format, json)
+                }
+                ApiCommands::ArtistTracks {
+                    id,
+                    limit,
+                    output_format,
+                } => {
+                    let tracks = client.artist_tracks(id, limit).await?;
+                    output(&tracks, output_format, json)
+                }
+                ApiCommands::Track { id, output_format } => {
+                    let track = client.track(id).await?;
+                    output(&track, output_format, json)
+                }
+                ApiCommands::Isrc {
+                    isrc,
+                    output_format,
+                } => {
+                    let tracks = client.tracks_by_isrc(&isrc).await?;
+
+                    if tracks.is_empty() {
+                        return Err(Error::ClientError {
+                            error: format!("no track found with ISRC {isrc}"),
+                        });
+                    }
+
+                    output(&tracks, output_format, json)
+                }
+                ApiCommands::Playlist { id, output_format } => {
+                    let playlist = client.playlist(id).await?;
+                    output(&playlist, output_format, json)
+                }
+            }
+        }
     }
 }
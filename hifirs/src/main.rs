@@ -1,11 +1,20 @@
+use clap::Parser;
+use hifi_rs::cli::Cli;
 use std::process;
 
 #[tokio::main]
 async fn main() {
-    match hifi_rs::cli::run().await {
+    let cli = Cli::parse();
+    let json = cli.json;
+
+    match hifi_rs::cli::run(cli).await {
         Ok(()) => {}
         Err(err) => {
-            println!("{err}");
+            if json {
+                eprintln!("{}", err.to_json());
+            } else {
+                println!("{err}");
+            }
             process::exit(1);
         }
     }
@@ -3,7 +3,9 @@ use crate::client::{
     track::Tracks,
     Composer, Image,
 };
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
+use std::fmt::Display;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Album {
@@ -62,12 +64,86 @@ pub struct Album {
     pub version: Option<String>,
 }
 
+/// Albums picked by Qobuz, such as new releases in a genre.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeaturedAlbums {
+    pub albums: Albums,
+}
+
+/// Orders to sort the albums of an artist in.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReleaseSort {
+    /// Newest first.
+    #[default]
+    ReleaseDate,
+    /// Alphabetically by title.
+    Title,
+    /// Most tracks first.
+    TrackCount,
+}
+
+impl ReleaseSort {
+    /// Sort `albums` in this order.
+    pub fn sort(self, albums: &mut [Album]) {
+        match self {
+            ReleaseSort::ReleaseDate => {
+                albums.sort_by(|a, b| b.release_date_original.cmp(&a.release_date_original))
+            }
+            ReleaseSort::Title => albums.sort_by_key(|album| album.title.to_lowercase()),
+            ReleaseSort::TrackCount => albums.sort_by(|a, b| b.tracks_count.cmp(&a.tracks_count)),
+        }
+    }
+}
+
+/// The lists of featured albums Qobuz curates.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FeaturedType {
+    #[default]
+    NewReleases,
+    PressAwards,
+    EditorPicks,
+    MostStreamed,
+    BestSellers,
+    Qobuzissims,
+    IdealDiscography,
+}
+
+impl Display for FeaturedType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let featured_type = match self {
+            FeaturedType::NewReleases => "new-releases",
+            FeaturedType::PressAwards => "press-awards",
+            FeaturedType::EditorPicks => "editor-picks",
+            FeaturedType::MostStreamed => "most-streamed",
+            FeaturedType::BestSellers => "best-sellers",
+            FeaturedType::Qobuzissims => "qobuzissims",
+            FeaturedType::IdealDiscography => "ideal-discography",
+        };
+
+        f.write_str(featured_type)
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AlbumSearchResults {
     pub query: String,
     pub albums: Albums,
 }
 
+impl AlbumSearchResults {
+    /// Total number of albums matching the query across all pages.
+    pub fn total(&self) -> i64 {
+        self.albums.total
+    }
+
+    /// Whether there are more albums after this page.
+    pub fn has_more(&self) -> bool {
+        self.albums.offset + (self.albums.items.len() as i64) < self.albums.total
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Albums {
     pub limit: i64,
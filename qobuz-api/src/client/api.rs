@@ -1,29 +1,43 @@
 use crate::{
     client::{
-        album::{Album, AlbumSearchResults},
+        album::{Album, AlbumSearchResults, FeaturedAlbums, FeaturedType},
         artist::{Artist, ArtistSearchResults},
         favorites::Favorites,
+        genre::GenreList,
+        label::Label,
         playlist::{Playlist, UserPlaylistsResult},
         release::{Release, ReleaseQuery},
         search_results::SearchAllResults,
-        track::Track,
-        TrackURL,
+        track::{Track, TrackSearchResults},
+        AudioQuality, TrackURL,
     },
+    redact::{redact_params, Redacted},
     Error, Result,
 };
 use base64::{engine::general_purpose, Engine as _};
 use clap::ValueEnum;
+use futures::future::select_ok;
 use reqwest::{
     header::{HeaderMap, HeaderValue},
     Method, Response, StatusCode,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+use tracing::instrument;
 
 use super::{
     album_suggestion::AlbumSuggestionResults,
-    artist::{Artists, ArtistsResponse},
+    artist::{ArtistTracksResponse, Artists, ArtistsResponse},
 };
 
 const BUNDLE_REGEX: &str =
@@ -33,58 +47,316 @@ const APP_REGEX: &str =
 const SEED_REGEX: &str =
     r#"[a-z]\.initialSeed\("(?P<seed>[\w=]+)",window\.utimezone\.(?P<timezone>[a-z]+)\)"#;
 
+/// Environment variables that replace the patterns above, to patch around changes
+/// to the web player without a new release.
+pub const BUNDLE_REGEX_ENV: &str = "HIFIRS_BUNDLE_REGEX";
+pub const APP_REGEX_ENV: &str = "HIFIRS_APP_REGEX";
+pub const SEED_REGEX_ENV: &str = "HIFIRS_SEED_REGEX";
+
+/// The most results Qobuz returns for a single search request.
+pub const MAX_SEARCH_LIMIT: i32 = 500;
+
 macro_rules! info_regex {
     () => {
         r#"name:"\w+/(?P<timezone>{}([a-z]?))",info:"(?P<info>[\w=]+)",extras:"(?P<extras>[\w=]+)""#
     };
 }
 
+/// The app_id, secret and user token are shared between clones of a client
+/// so that refreshing them in one place updates every clone.
+#[derive(Debug, Default)]
+struct AppKeys {
+    app_id: Option<String>,
+    active_secret: Option<String>,
+    user_token: Option<String>,
+    credentials: Option<(String, Redacted<String>)>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Client {
     secrets: HashMap<String, String>,
-    active_secret: Option<String>,
-    app_id: Option<String>,
+    keys: Arc<RwLock<AppKeys>>,
     base_url: String,
+    play_url: String,
     client: reqwest::Client,
-    user_token: Option<String>,
+    max_quality: Option<AudioQuality>,
     bundle_regex: regex::Regex,
     app_id_regex: regex::Regex,
     seed_regex: regex::Regex,
 }
 
-pub async fn new(
-    active_secret: Option<String>,
-    app_id: Option<String>,
-    user_token: Option<String>,
-) -> Result<Client> {
+/// Default timeout for calls to Qobuz, in seconds. API calls must finish within
+/// it, downloads only have to connect and keep receiving data within it.
+pub const DEFAULT_HTTP_TIMEOUT: u64 = 30;
+
+/// Read the timeout from `HIFIRS_HTTP_TIMEOUT` (seconds), falling back to the default.
+fn http_timeout() -> Duration {
+    let seconds = std::env::var("HIFIRS_HTTP_TIMEOUT")
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .filter(|seconds| *seconds > 0)
+        .unwrap_or(DEFAULT_HTTP_TIMEOUT);
+
+    Duration::from_secs(seconds)
+}
+
+/// The API every request goes to, unless another one is set.
+pub const DEFAULT_BASE_URL: &str = "https://www.qobuz.com/api.json/0.2/";
+/// The web player the app_id and secrets are scraped from, unless another one is set.
+pub const DEFAULT_PLAY_URL: &str = "https://play.qobuz.com";
+
+/// User-Agent sent to Qobuz unless another one is configured.
+pub const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/111.0.0.0 Safari/537.36";
+
+/// Proxy for every request, taking precedence over the standard proxy variables.
+pub const PROXY_ENV: &str = "HIFIRS_PROXY";
+/// Comma separated hosts to reach without the proxy, taking precedence over `NO_PROXY`.
+pub const NO_PROXY_ENV: &str = "HIFIRS_NO_PROXY";
+
+/// A proxy from the environment, with the credentials split off the url for
+/// clients that take them separately.
+#[derive(Debug, Clone)]
+pub struct ProxySettings {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<Redacted<String>>,
+    pub no_proxy: Option<String>,
+}
+
+impl ProxySettings {
+    /// Whether `url` is reached without the proxy, its host matching one of
+    /// the `no_proxy` entries or a subdomain of one. `*` matches every host.
+    pub fn bypasses(&self, url: &str) -> bool {
+        let Some(no_proxy) = &self.no_proxy else {
+            return false;
+        };
+        let Some(host) = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_lowercase()))
+        else {
+            return false;
+        };
+
+        no_proxy
+            .split(',')
+            .map(|entry| entry.trim().trim_start_matches('.').to_lowercase())
+            .filter(|entry| !entry.is_empty())
+            .any(|entry| {
+                entry == "*"
+                    || host == entry
+                    || host
+                        .strip_suffix(entry.as_str())
+                        .is_some_and(|subdomain| subdomain.ends_with('.'))
+            })
+    }
+}
+
+/// The proxy from `HIFIRS_PROXY`, or else from `HTTPS_PROXY`, `ALL_PROXY` or `HTTP_PROXY`.
+pub fn proxy_settings() -> Option<ProxySettings> {
+    let env = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+    };
+
+    let url = env(&[
+        PROXY_ENV,
+        "HTTPS_PROXY",
+        "https_proxy",
+        "ALL_PROXY",
+        "all_proxy",
+        "HTTP_PROXY",
+        "http_proxy",
+    ])?;
+    let mut url = match reqwest::Url::parse(&url) {
+        Ok(url) => url,
+        Err(error) => {
+            warn!("ignoring invalid proxy url: {error}");
+            return None;
+        }
+    };
+
+    let username = Some(url.username().to_string()).filter(|username| !username.is_empty());
+    let password = url
+        .password()
+        .map(|password| Redacted(password.to_string()));
+    _ = url.set_username("");
+    _ = url.set_password(None);
+
+    Some(ProxySettings {
+        url: url.to_string(),
+        username,
+        password,
+        no_proxy: env(&[NO_PROXY_ENV, "NO_PROXY", "no_proxy"]),
+    })
+}
+
+fn http_client(user_agent: &str) -> Result<reqwest::Client> {
     let mut headers = HeaderMap::new();
     headers.insert(
-            "User-Agent",
-            HeaderValue::from_str(
-                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/111.0.0.0 Safari/537.36",
-            )
-            .unwrap(),
-        );
+        "User-Agent",
+        HeaderValue::from_str(user_agent).map_err(|error| Error::Api {
+            message: format!("invalid user agent: {error}"),
+        })?,
+    );
 
-    let client = reqwest::Client::builder()
+    let timeout = http_timeout();
+    debug!("using http timeout of {}s", timeout.as_secs());
+    debug!("using user agent {}", user_agent);
+
+    let mut builder = reqwest::Client::builder()
         .cookie_store(true)
         .default_headers(headers)
-        .build()
-        .unwrap();
+        .connect_timeout(timeout)
+        .read_timeout(timeout);
+
+    if let Some(settings) = proxy_settings() {
+        let mut proxy = reqwest::Proxy::all(&settings.url).map_err(|error| Error::Api {
+            message: format!("invalid proxy: {error}"),
+        })?;
+
+        if let Some(username) = &settings.username {
+            let password = settings.password.as_ref().map_or("", |p| p.0.as_str());
+            proxy = proxy.basic_auth(username, password);
+        }
+
+        proxy = proxy.no_proxy(
+            settings
+                .no_proxy
+                .as_deref()
+                .and_then(reqwest::NoProxy::from_string),
+        );
+
+        info!(
+            "using proxy {}{}",
+            settings.url,
+            if settings.username.is_some() {
+                " with credentials"
+            } else {
+                ""
+            }
+        );
+        builder = builder.proxy(proxy);
+    }
+
+    Ok(builder.build().unwrap())
+}
+
+pub async fn new(
+    active_secret: Option<String>,
+    app_id: Option<String>,
+    user_token: Option<String>,
+) -> Result<Client> {
+    let client = http_client(DEFAULT_USER_AGENT)?;
 
     Ok(Client {
         client,
         secrets: HashMap::new(),
-        active_secret,
-        user_token,
-        app_id,
-        base_url: "https://www.qobuz.com/api.json/0.2/".to_string(),
-        bundle_regex: regex::Regex::new(BUNDLE_REGEX).unwrap(),
-        app_id_regex: regex::Regex::new(APP_REGEX).unwrap(),
-        seed_regex: regex::Regex::new(SEED_REGEX).unwrap(),
+        keys: Arc::new(RwLock::new(AppKeys {
+            app_id,
+            active_secret,
+            user_token,
+            credentials: None,
+        })),
+        base_url: DEFAULT_BASE_URL.to_string(),
+        play_url: DEFAULT_PLAY_URL.to_string(),
+        max_quality: None,
+        bundle_regex: scrape_regex(BUNDLE_REGEX_ENV, BUNDLE_REGEX),
+        app_id_regex: scrape_regex(APP_REGEX_ENV, APP_REGEX),
+        seed_regex: scrape_regex(SEED_REGEX_ENV, SEED_REGEX),
     })
 }
 
+/// The pattern from the `env` variable when it is set and compiles, otherwise `default`.
+fn scrape_regex(env: &str, default: &str) -> regex::Regex {
+    if let Ok(pattern) = std::env::var(env) {
+        match regex::Regex::new(&pattern) {
+            Ok(regex) => {
+                info!("using pattern from {env}: {pattern}");
+                return regex;
+            }
+            Err(error) => warn!("ignoring invalid pattern in {env}, using the default: {error}"),
+        }
+    } else {
+        debug!("using the default pattern, {env} is not set");
+    }
+
+    regex::Regex::new(default).unwrap()
+}
+
+/// Characters of a scraped page included in errors, enough to tell what was
+/// served without flooding the terminal.
+const SCRAPE_SNIPPET_LENGTH: usize = 200;
+
+fn scrape_error(name: &str, env: &str, page: &str) -> Error {
+    Error::Scrape {
+        name: name.to_string(),
+        env: env.to_string(),
+        snippet: page.chars().take(SCRAPE_SNIPPET_LENGTH).collect(),
+    }
+}
+
+/// Decode a secret from the characters of a seed, its info and extras.
+fn decode_secret(chars: &str) -> Option<String> {
+    let encoded_secret = chars.get(..chars.len().checked_sub(44)?)?;
+    let decoded_secret = general_purpose::URL_SAFE.decode(encoded_secret).ok()?;
+
+    String::from_utf8(decoded_secret).ok()
+}
+
+/// An ISRC without the dashes it is sometimes written with, in upper case.
+fn normalize_isrc(isrc: &str) -> String {
+    isrc.trim()
+        .chars()
+        .filter(|c| *c != '-')
+        .collect::<String>()
+        .to_uppercase()
+}
+
+/// The `request_sig` of a `track/getFileUrl` call: an md5 of the method, its
+/// parameters in alphabetical order, the timestamp and the secret.
+fn track_url_signature(format_id: &str, track_id: i32, timestamp: &str, secret: &str) -> String {
+    let sig = format!(
+        "trackgetFileUrlformat_id{}intentstreamtrack_id{}{}{}",
+        format_id, track_id, timestamp, secret
+    );
+
+    format!("{:x}", md5::compute(sig.as_str()))
+}
+
+/// Qobuz answers a `track/getFileUrl` call for a format the track is not
+/// available in with a 404, and for one the account may not stream with a 403.
+fn is_unavailable_format(error: &Error) -> bool {
+    match error {
+        Error::Api { message } => [StatusCode::NOT_FOUND, StatusCode::FORBIDDEN]
+            .iter()
+            .any(|status| message.starts_with(status.as_str())),
+        _ => false,
+    }
+}
+
+/// Requests sent to the API since startup, and how many of them failed.
+static REQUESTS: AtomicU64 = AtomicU64::new(0);
+static FAILED_REQUESTS: AtomicU64 = AtomicU64::new(0);
+
+/// Number of requests sent to the API since startup, and how many of them failed.
+pub fn request_counts() -> (u64, u64) {
+    (
+        REQUESTS.load(Ordering::Relaxed),
+        FAILED_REQUESTS.load(Ordering::Relaxed),
+    )
+}
+
+fn record_request<T>(result: Result<T>) -> Result<T> {
+    REQUESTS.fetch_add(1, Ordering::Relaxed);
+
+    if result.is_err() {
+        FAILED_REQUESTS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    result
+}
+
 #[non_exhaustive]
 enum Endpoint {
     Album,
@@ -96,6 +368,7 @@ enum Endpoint {
     UserPlaylist,
     SearchArtists,
     SearchAlbums,
+    SearchTracks,
     TrackURL,
     Playlist,
     PlaylistCreate,
@@ -110,6 +383,9 @@ enum Endpoint {
     FavoritePlaylistAdd,
     FavoritePlaylistRemove,
     AlbumSuggest,
+    Label,
+    Genres,
+    FeaturedAlbums,
 }
 
 impl Display for Endpoint {
@@ -129,6 +405,7 @@ impl Display for Endpoint {
             Endpoint::Search => "catalog/search",
             Endpoint::SearchAlbums => "album/search",
             Endpoint::SearchArtists => "artist/search",
+            Endpoint::SearchTracks => "track/search",
             Endpoint::Track => "track/get",
             Endpoint::TrackURL => "track/getFileUrl",
             Endpoint::UserPlaylist => "playlist/getUserPlaylists",
@@ -138,6 +415,9 @@ impl Display for Endpoint {
             Endpoint::FavoritePlaylistAdd => "playlist/subscribe",
             Endpoint::FavoritePlaylistRemove => "playlist/unsubscribe",
             Endpoint::AlbumSuggest => "album/suggest",
+            Endpoint::Label => "label/get",
+            Endpoint::Genres => "genre/list",
+            Endpoint::FeaturedAlbums => "album/getFeatured",
         };
 
         f.write_str(endpoint)
@@ -153,9 +433,7 @@ macro_rules! get {
                     message: error.to_string(),
                 }),
             },
-            Err(error) => Err(Error::Api {
-                message: error.to_string(),
-            }),
+            Err(error) => Err(error),
         }
     };
 }
@@ -169,26 +447,27 @@ macro_rules! post {
                     message: error.to_string(),
                 }),
             },
-            Err(error) => Err(Error::Api {
-                message: error.to_string(),
-            }),
+            Err(error) => Err(error),
         }
     };
 }
 
 impl Client {
     pub fn signed_in(&self) -> bool {
-        self.user_token.is_some()
+        self.keys.read().unwrap().user_token.is_some()
     }
 
-    /// Login a user
-    pub async fn login(&mut self, username: &str, password: &str) -> Result<()> {
+    /// Login a user. The credentials are kept in memory so that
+    /// the client can log in again when the token is rejected.
+    #[instrument(skip(self, username, password), fields(username = %Redacted(username)))]
+    pub async fn login(&self, username: &str, password: &str) -> Result<()> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::Login);
 
-        if let Some(app_id) = &self.app_id {
+        if let Some(app_id) = self.get_app_id() {
             info!(
                 "logging in with email ({}) and password **HIDDEN** for app_id {}",
-                username, app_id
+                Redacted(username),
+                Redacted(&app_id)
             );
 
             let params = vec![
@@ -197,15 +476,17 @@ impl Client {
                 ("app_id", app_id.as_str()),
             ];
 
-            match self.make_get_call(&endpoint, Some(&params)).await {
+            match self.make_get_call_once(&endpoint, Some(&params)).await {
                 Ok(response) => {
                     let json: Value = serde_json::from_str(response.as_str()).unwrap();
                     info!("Successfully logged in");
-                    debug!("{}", json);
                     let mut token = json["user_auth_token"].to_string();
                     token = token[1..token.len() - 1].to_string();
 
-                    self.user_token = Some(token);
+                    let mut keys = self.keys.write().unwrap();
+                    keys.user_token = Some(token);
+                    keys.credentials = Some((username.to_string(), Redacted(password.to_string())));
+
                     Ok(())
                 }
                 Err(err) => {
@@ -227,6 +508,7 @@ impl Client {
     }
 
     /// Retrieve a playlist
+    #[instrument(skip(self))]
     pub async fn playlist(&self, playlist_id: i64) -> Result<Playlist> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::Playlist);
         let id_string = playlist_id.to_string();
@@ -381,6 +663,7 @@ impl Client {
     }
 
     /// Retrieve track information
+    #[instrument(skip(self))]
     pub async fn track(&self, track_id: i32) -> Result<Track> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::Track);
         let track_id_string = track_id.to_string();
@@ -390,22 +673,27 @@ impl Client {
     }
 
     /// Retrieve url information for a track's audio file
-    pub async fn track_url(&self, track_id: i32, sec: Option<&str>) -> Result<TrackURL> {
+    #[instrument(skip(self, sec))]
+    pub async fn track_url(
+        &self,
+        track_id: i32,
+        quality: Option<AudioQuality>,
+        sec: Option<&str>,
+    ) -> Result<TrackURL> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::TrackURL);
         let now = format!("{}", chrono::Utc::now().timestamp());
+        let active_secret = self.get_active_secret();
         let secret = if let Some(secret) = sec {
             secret
-        } else if let Some(s) = &self.active_secret {
+        } else if let Some(s) = &active_secret {
             s
         } else {
             return Err(Error::ActiveSecret);
         };
 
-        let sig = format!(
-            "trackgetFileUrlformat_id{}intentstreamtrack_id{}{}{}",
-            "27", track_id, now, secret
-        );
-        let hashed_sig = format!("{:x}", md5::compute(sig.as_str()));
+        let format_id = self.capped_quality(quality.unwrap_or_default()).to_string();
+
+        let hashed_sig = track_url_signature(&format_id, track_id, &now, secret);
 
         let track_id = track_id.to_string();
 
@@ -413,11 +701,130 @@ impl Client {
             ("request_ts", now.as_str()),
             ("request_sig", hashed_sig.as_str()),
             ("track_id", track_id.as_str()),
-            ("format_id", "27"),
+            ("format_id", format_id.as_str()),
             ("intent", "stream"),
         ];
 
-        get!(self, &endpoint, Some(&params))
+        // A rejected signature also comes back as unauthorized, which logging in
+        // again does not fix, so the secret is left to the caller to refresh.
+        match self.make_get_call_once(&endpoint, Some(&params)).await {
+            Ok(response) => {
+                serde_json::from_str(response.as_str()).map_err(|error| Error::DeserializeJSON {
+                    message: error.to_string(),
+                })
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Retrieve url information for a track's audio file, stepping down
+    /// through the qualities until Qobuz is able to serve one.
+    /// Only a format that is not available or restricted is stepped down from,
+    /// any other error is returned as is.
+    /// The delivered quality is available through `TrackURL::quality`.
+    #[instrument(skip(self))]
+    pub async fn track_url_with_fallback(
+        &self,
+        track_id: i32,
+        quality: AudioQuality,
+    ) -> Result<TrackURL> {
+        let quality = self.capped_quality(quality);
+        let mut current = quality;
+
+        loop {
+            match self.track_url(track_id, Some(current), None).await {
+                Ok(track_url) => {
+                    match track_url.quality() {
+                        Some(actual) if actual < quality => {
+                            info!(
+                                "requested {:?} for track {}, got {:?}",
+                                quality, track_id, actual
+                            );
+                        }
+                        _ => {}
+                    }
+
+                    return Ok(track_url);
+                }
+                Err(error) if !is_unavailable_format(&error) => return Err(error),
+                Err(error) => match current.lower() {
+                    Some(lower) => {
+                        debug!(
+                            "failed to get {:?} url for track {}, trying {:?}: {}",
+                            current, track_id, lower, error
+                        );
+                        current = lower;
+                    }
+                    None => return Err(error),
+                },
+            }
+        }
+    }
+
+    /// Download the audio file at `url`, as returned by `track_url`, into `writer`.
+    /// The file is written as it arrives, so nothing is buffered in memory.
+    /// Returns the number of bytes written, or an error when fewer bytes arrived
+    /// than Qobuz announced.
+    pub async fn download<W: Write>(&self, url: &str, writer: &mut W) -> Result<u64> {
+        self.download_with_progress(url, writer, |_, _| {}).await
+    }
+
+    /// Same as `download`, calling `on_progress` with the bytes written so far
+    /// and the total size, when Qobuz reports it, after every chunk.
+    #[instrument(skip(self, url, writer, on_progress))]
+    pub async fn download_with_progress<W, F>(
+        &self,
+        url: &str,
+        writer: &mut W,
+        mut on_progress: F,
+    ) -> Result<u64>
+    where
+        W: Write,
+        F: FnMut(u64, Option<u64>),
+    {
+        // A whole track can take longer than the timeout, so only the client's
+        // connect and read timeouts apply here.
+        let mut response = self.client.get(url).send().await?.error_for_status()?;
+        let total = response.content_length();
+        let mut written = 0;
+
+        on_progress(written, total);
+
+        while let Some(chunk) = response.chunk().await? {
+            writer.write_all(&chunk).map_err(|error| Error::Write {
+                message: error.to_string(),
+            })?;
+            written += chunk.len() as u64;
+
+            on_progress(written, total);
+        }
+
+        writer.flush().map_err(|error| Error::Write {
+            message: error.to_string(),
+        })?;
+
+        debug!("downloaded {} bytes", written);
+
+        // The stream simply ends when the connection drops, which would leave a
+        // truncated file behind without an error.
+        match total {
+            Some(expected) if expected != written => {
+                Err(Error::IncompleteDownload { written, expected })
+            }
+            _ => Ok(written),
+        }
+    }
+
+    /// The size in bytes of the audio file at `url`, when Qobuz reports it,
+    /// without downloading it.
+    pub async fn download_size(&self, url: &str) -> Result<Option<u64>> {
+        let response = self
+            .request(Method::HEAD, url)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.content_length())
     }
 
     pub async fn favorites(&self, limit: i32) -> Result<Favorites> {
@@ -445,6 +852,22 @@ impl Client {
         post!(self, &endpoint, form_data)
     }
 
+    pub async fn add_favorite_track(&self, id: &str) -> Result<SuccessfulResponse> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::FavoriteAdd);
+        let mut form_data = HashMap::new();
+        form_data.insert("track_ids", id);
+
+        post!(self, &endpoint, form_data)
+    }
+
+    pub async fn remove_favorite_track(&self, id: &str) -> Result<SuccessfulResponse> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::FavoriteRemove);
+        let mut form_data = HashMap::new();
+        form_data.insert("track_ids", id);
+
+        post!(self, &endpoint, form_data)
+    }
+
     pub async fn add_favorite_artist(&self, id: &str) -> Result<SuccessfulResponse> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::FavoriteAdd);
         let mut form_data = HashMap::new();
@@ -463,7 +886,6 @@ impl Client {
 
     pub async fn add_favorite_playlist(&self, id: &str) -> Result<SuccessfulResponse> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::FavoritePlaylistAdd);
-        println!("{endpoint}");
         let mut form_data = HashMap::new();
         form_data.insert("playlist_id", id);
 
@@ -472,22 +894,28 @@ impl Client {
 
     pub async fn remove_favorite_playlist(&self, id: &str) -> Result<SuccessfulResponse> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::FavoritePlaylistRemove);
-        println!("{endpoint}");
         let mut form_data = HashMap::new();
         form_data.insert("playlist_id", id);
 
         post!(self, &endpoint, form_data)
     }
 
-    pub async fn search_all(&self, query: &str, limit: i32) -> Result<SearchAllResults> {
+    pub async fn search_all(
+        &self,
+        query: &str,
+        limit: i32,
+        offset: i32,
+    ) -> Result<SearchAllResults> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::Search);
         let limit = limit.to_string();
-        let params = vec![("query", query), ("limit", &limit)];
+        let offset = offset.to_string();
+        let params = vec![("query", query), ("limit", &limit), ("offset", &offset)];
 
         get!(self, &endpoint, Some(&params))
     }
 
     // Retrieve information about an album
+    #[instrument(skip(self))]
     pub async fn album(&self, album_id: &str) -> Result<Album> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::Album);
         let params = vec![
@@ -513,17 +941,116 @@ impl Client {
         &self,
         query: &str,
         limit: Option<i32>,
+        offset: Option<i32>,
     ) -> Result<AlbumSearchResults> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::SearchAlbums);
         let limit = limit.unwrap_or(100).to_string();
-        let params = vec![("query", query), ("limit", limit.as_str())];
+        let offset = offset.unwrap_or(0).to_string();
+        let params = vec![
+            ("query", query),
+            ("limit", limit.as_str()),
+            ("offset", offset.as_str()),
+        ];
+
+        get!(self, &endpoint, Some(&params))
+    }
+
+    // Search the database for tracks
+    pub async fn search_tracks(
+        &self,
+        query: &str,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> Result<TrackSearchResults> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::SearchTracks);
+        let limit = limit.unwrap_or(100).to_string();
+        let offset = offset.unwrap_or(0).to_string();
+        let params = vec![
+            ("query", query),
+            ("limit", limit.as_str()),
+            ("offset", offset.as_str()),
+        ];
+
+        get!(self, &endpoint, Some(&params))
+    }
+
+    /// Find the tracks with the given ISRC, one per release it appears on.
+    /// Qobuz has no lookup by ISRC, so this searches for the code and keeps
+    /// the results that carry it.
+    #[instrument(skip(self))]
+    pub async fn tracks_by_isrc(&self, isrc: &str) -> Result<Vec<Track>> {
+        let isrc = normalize_isrc(isrc);
+        let results = self.search_tracks(&isrc, None, None).await?;
+
+        Ok(results
+            .tracks
+            .items
+            .into_iter()
+            .filter(|track| {
+                track
+                    .isrc
+                    .as_deref()
+                    .is_some_and(|track_isrc| normalize_isrc(track_isrc) == isrc)
+            })
+            .collect())
+    }
+
+    // Retrieve a label and its releases
+    pub async fn label(&self, label_id: i64, limit: Option<i32>) -> Result<Label> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::Label);
+        let label_id = label_id.to_string();
+        let limit = limit.unwrap_or(100).to_string();
+        let params = vec![
+            ("label_id", label_id.as_str()),
+            ("extra", "albums"),
+            ("limit", &limit),
+            ("offset", "0"),
+        ];
+
+        get!(self, &endpoint, Some(&params))
+    }
+
+    // Retrieve the top level genres, or the children of a genre
+    pub async fn genres(&self, parent_id: Option<i64>) -> Result<GenreList> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::Genres);
+        let parent_id = parent_id.map(|id| id.to_string());
+        let mut params = vec![("limit", "100"), ("offset", "0")];
+
+        if let Some(parent_id) = &parent_id {
+            params.push(("parent_id", parent_id.as_str()));
+        }
+
+        get!(self, &endpoint, Some(&params))
+    }
+
+    // Retrieve featured albums, such as new releases, optionally in a single genre
+    pub async fn featured_albums(
+        &self,
+        featured_type: FeaturedType,
+        genre_id: Option<i64>,
+        limit: Option<i32>,
+    ) -> Result<FeaturedAlbums> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::FeaturedAlbums);
+        let featured_type = featured_type.to_string();
+        let genre_id = genre_id.map(|id| id.to_string());
+        let limit = limit.unwrap_or(50).to_string();
+        let mut params = vec![
+            ("type", featured_type.as_str()),
+            ("limit", &limit),
+            ("offset", "0"),
+        ];
+
+        if let Some(genre_id) = &genre_id {
+            params.push(("genre_id", genre_id.as_str()));
+        }
 
         get!(self, &endpoint, Some(&params))
     }
 
     // Retrieve information about an artist
+    #[instrument(skip(self))]
     pub async fn artist(&self, artist_id: i32, limit: Option<i32>) -> Result<Artist> {
-        if let Some(app_id) = &self.app_id {
+        if let Some(app_id) = self.get_app_id() {
             let endpoint = format!("{}{}", self.base_url, Endpoint::Artist);
             let limit = limit.unwrap_or(100).to_string();
 
@@ -531,7 +1058,7 @@ impl Client {
 
             let params = vec![
                 ("artist_id", artistid_string.as_str()),
-                ("app_id", app_id),
+                ("app_id", app_id.as_str()),
                 ("limit", &limit),
                 ("offset", "0"),
                 ("extra", "albums"),
@@ -543,6 +1070,25 @@ impl Client {
         }
     }
 
+    // Retrieve the most popular tracks for an artist
+    pub async fn artist_tracks(&self, artist_id: i32, limit: Option<i32>) -> Result<Vec<Track>> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::Artist);
+        let limit = limit.unwrap_or(50).to_string();
+
+        let artistid_string = artist_id.to_string();
+
+        let params = vec![
+            ("artist_id", artistid_string.as_str()),
+            ("limit", &limit),
+            ("offset", "0"),
+            ("extra", "tracks"),
+        ];
+
+        let response: Result<ArtistTracksResponse> = get!(self, &endpoint, Some(&params));
+
+        response.map(|res| res.tracks.items)
+    }
+
     // Retrieve similar artists to artist
     pub async fn similar_artists(&self, artist_id: i32, limit: Option<i32>) -> Result<Artists> {
         let limit = limit.unwrap_or(10).to_string();
@@ -590,9 +1136,7 @@ impl Client {
                     message: error.to_string(),
                 }),
             },
-            Err(error) => Err(Error::Api {
-                message: error.to_string(),
-            }),
+            Err(error) => Err(error),
         };
 
         match result {
@@ -616,43 +1160,91 @@ impl Client {
 
     // Set a user access token for authentication
     pub fn set_token(&mut self, token: String) {
-        self.user_token = Some(token);
+        self.keys.write().unwrap().user_token = Some(token);
+    }
+
+    /// Credentials to log in again with when a cached token is rejected.
+    pub fn set_credentials(&mut self, username: String, password: String) {
+        self.keys.write().unwrap().credentials = Some((username, Redacted(password)));
+    }
+
+    /// Send `user_agent` instead of the default one. Must be called before any request,
+    /// as it starts a new session.
+    pub fn set_user_agent(&mut self, user_agent: &str) -> Result<()> {
+        self.client = http_client(user_agent)?;
+
+        Ok(())
+    }
+
+    /// Send API requests to `base_url` instead of Qobuz, such as a mirror or a mock server.
+    pub fn set_base_url(&mut self, base_url: &str) {
+        // Endpoints are appended directly to the base url.
+        self.base_url = format!("{}/", base_url.trim_end_matches('/'));
+    }
+
+    /// Scrape the app_id and secrets from `play_url` instead of the Qobuz web player.
+    pub fn set_play_url(&mut self, play_url: &str) {
+        self.play_url = play_url.to_string();
+    }
+
+    /// Never request a quality above `max_quality`, asking for it instead.
+    pub fn set_max_quality(&mut self, max_quality: Option<AudioQuality>) {
+        self.max_quality = max_quality;
+    }
+
+    fn capped_quality(&self, quality: AudioQuality) -> AudioQuality {
+        match self.max_quality {
+            Some(max_quality) if quality > max_quality => {
+                info!(
+                    "{} is above the maximum quality, requesting {} instead",
+                    quality.name(),
+                    max_quality.name()
+                );
+                max_quality
+            }
+            _ => quality,
+        }
     }
 
     // Set an app_id for authentication
     pub fn set_app_id(&mut self, app_id: String) {
-        self.app_id = Some(app_id);
+        self.keys.write().unwrap().app_id = Some(app_id);
     }
 
     // Set an app secret for authentication
     pub fn set_active_secret(&mut self, active_secret: String) {
-        self.active_secret = Some(active_secret);
+        self.keys.write().unwrap().active_secret = Some(active_secret);
     }
 
-    pub fn get_token(&self) -> Option<&String> {
-        self.user_token.as_ref()
+    pub fn get_token(&self) -> Option<String> {
+        self.keys.read().unwrap().user_token.clone()
     }
 
-    pub fn get_active_secret(&self) -> Option<&String> {
-        self.active_secret.as_ref()
+    pub fn get_active_secret(&self) -> Option<String> {
+        self.keys.read().unwrap().active_secret.clone()
     }
 
-    pub fn get_app_id(&self) -> Option<&String> {
-        self.app_id.as_ref()
+    pub fn get_app_id(&self) -> Option<String> {
+        self.keys.read().unwrap().app_id.clone()
+    }
+
+    /// Number of secrets found by `refresh`, before they are tested.
+    pub fn secret_count(&self) -> usize {
+        self.secrets.len()
     }
 
     fn client_headers(&self) -> HeaderMap {
         let mut headers = HeaderMap::new();
 
-        if let Some(app_id) = &self.app_id {
-            info!("adding app_id to request headers: {}", app_id);
-            headers.insert("X-App-Id", HeaderValue::from_str(app_id).unwrap());
+        if let Some(app_id) = self.get_app_id() {
+            info!("adding app_id to request headers: {}", Redacted(&app_id));
+            headers.insert("X-App-Id", HeaderValue::from_str(&app_id).unwrap());
         } else {
             error!("no app_id");
         }
 
-        if let Some(token) = &self.user_token {
-            info!("adding token to request headers: {}", token);
+        if let Some(token) = self.get_token() {
+            info!("adding token to request headers: {}", Redacted(&token));
             headers.insert(
                 "X-User-Auth-Token",
                 HeaderValue::from_str(token.as_str()).unwrap(),
@@ -672,47 +1264,124 @@ impl Client {
         headers
     }
 
-    // Make a GET call to the API with the provided parameters
+    /// Log in again after the token was rejected, using the credentials of the
+    /// last login. Returns true when there is a new token to retry with.
+    async fn relogin(&self) -> bool {
+        let credentials = {
+            let mut keys = self.keys.write().unwrap();
+
+            if keys.user_token.take().is_none() {
+                return false;
+            }
+
+            keys.credentials.clone()
+        };
+
+        match credentials {
+            Some((username, password)) => {
+                info!("token rejected, logging in again");
+                self.login(&username, &password.0).await.is_ok()
+            }
+            None => {
+                info!("token rejected and no credentials to log in again with");
+                false
+            }
+        }
+    }
+
+    // Make a GET call to the API, logging in again once if the token is rejected
     async fn make_get_call(
         &self,
         endpoint: &str,
         params: Option<&[(&str, &str)]>,
+    ) -> Result<String> {
+        match self.make_get_call_once(endpoint, params).await {
+            Err(Error::Unauthorized) if self.relogin().await => {
+                self.make_get_call_once(endpoint, params).await
+            }
+            response => response,
+        }
+    }
+
+    // Make a GET call to the API with the provided parameters
+    #[instrument(skip(self, params))]
+    async fn make_get_call_once(
+        &self,
+        endpoint: &str,
+        params: Option<&[(&str, &str)]>,
     ) -> Result<String> {
         let headers = self.client_headers();
 
-        debug!("calling {} endpoint, with params {params:?}", endpoint);
-        let request = self.client.request(Method::GET, endpoint).headers(headers);
+        debug!(
+            "calling {} endpoint, with params {:?}",
+            endpoint,
+            params.map(|p| redact_params(p.iter().map(|(key, value)| (*key, *value))))
+        );
+        let request = self.request(Method::GET, endpoint).headers(headers);
+        let request = match params {
+            Some(p) => request.query(&p),
+            None => request,
+        };
 
-        if let Some(p) = params {
-            let response = request.query(&p).send().await?;
-            self.handle_response(response).await
-        } else {
-            let response = request.send().await?;
-            self.handle_response(response).await
+        record_request(
+            async {
+                let response = request.send().await?;
+                self.handle_response(response).await
+            }
+            .await,
+        )
+    }
+
+    // Make a POST call to the API, logging in again once if the token is rejected
+    async fn make_post_call(&self, endpoint: &str, params: HashMap<&str, &str>) -> Result<String> {
+        match self.make_post_call_once(endpoint, params.clone()).await {
+            Err(Error::Unauthorized) if self.relogin().await => {
+                self.make_post_call_once(endpoint, params).await
+            }
+            response => response,
         }
     }
 
+    /// A request that has to finish within the http timeout, unlike downloads.
+    fn request<U: reqwest::IntoUrl>(&self, method: Method, url: U) -> reqwest::RequestBuilder {
+        self.client.request(method, url).timeout(http_timeout())
+    }
+
     // Make a POST call to the API with form data
-    async fn make_post_call(&self, endpoint: &str, params: HashMap<&str, &str>) -> Result<String> {
+    #[instrument(skip(self, params))]
+    async fn make_post_call_once(
+        &self,
+        endpoint: &str,
+        params: HashMap<&str, &str>,
+    ) -> Result<String> {
         let headers = self.client_headers();
 
-        debug!("calling {} endpoint, with params {params:?}", endpoint);
-        let response = self
-            .client
+        debug!(
+            "calling {} endpoint, with params {:?}",
+            endpoint,
+            redact_params(params.iter().map(|(key, value)| (*key, *value)))
+        );
+        let request = self
             .request(Method::POST, endpoint)
             .headers(headers)
-            .form(&params)
-            .send()
-            .await?;
+            .form(&params);
 
-        self.handle_response(response).await
+        record_request(
+            async {
+                let response = request.send().await?;
+                self.handle_response(response).await
+            }
+            .await,
+        )
     }
 
     // Handle a response retrieved from the api
     async fn handle_response(&self, response: Response) -> Result<String> {
         if response.status() == StatusCode::OK {
-            let res = response.text().await.unwrap();
+            let res = response.text().await?;
             Ok(res)
+        } else if response.status() == StatusCode::UNAUTHORIZED {
+            Err(Error::Unauthorized)
         } else {
             Err(Error::Api {
                 message: response.status().to_string(),
@@ -724,97 +1393,128 @@ impl Client {
     // Retrieve the app_id and generate the secrets needed to authenticate
     pub async fn refresh(&mut self) -> Result<()> {
         debug!("fetching login page");
-        let play_url = "https://play.qobuz.com";
-        let login_page = self.client.get(format!("{play_url}/login")).send().await?;
-
-        let contents = login_page.text().await.unwrap();
-
-        if let Some(captures) = self.bundle_regex.captures(contents.as_str()) {
-            let bundle_path = captures.get(1).map_or("", |m| m.as_str());
-            let bundle_url = format!("{play_url}{bundle_path}");
-            if let Ok(bundle_page) = self.client.get(bundle_url).send().await {
-                if let Ok(bundle_contents) = bundle_page.text().await {
-                    if let Some(captures) = self.app_id_regex.captures(bundle_contents.as_str()) {
-                        let app_id = captures
-                            .name("app_id")
-                            .map_or("".to_string(), |m| m.as_str().to_string());
-
-                        self.app_id = Some(app_id.clone());
-
-                        let seed_data = self.seed_regex.captures_iter(bundle_contents.as_str());
-
-                        seed_data.for_each(|s| {
-                            let seed = s.name("seed").map_or("", |m| m.as_str()).to_string();
-                            let mut timezone =
-                                s.name("timezone").map_or("", |m| m.as_str()).to_string();
-                            crate::client::capitalize(timezone.as_mut_str());
-
-                            let info_regex = format!(info_regex!(), &timezone);
-                            regex::Regex::new(info_regex.as_str())
-                                .unwrap()
-                                .captures_iter(bundle_contents.as_str())
-                                .for_each(|c| {
-                                    let timezone =
-                                        c.name("timezone").map_or("", |m| m.as_str()).to_string();
-                                    let info =
-                                        c.name("info").map_or("", |m| m.as_str()).to_string();
-                                    let extras =
-                                        c.name("extras").map_or("", |m| m.as_str()).to_string();
-
-                                    let chars = format!("{seed}{info}{extras}");
-
-                                    let encoded_secret = chars[..chars.len() - 44].to_string();
-                                    let decoded_secret = general_purpose::URL_SAFE
-                                        .decode(encoded_secret)
-                                        .expect("failed to decode base64 secret");
-                                    let secret_utf8 = std::str::from_utf8(&decoded_secret)
-                                        .expect("failed to convert base64 to string")
-                                        .to_string();
-
-                                    debug!(
-                                        "{}\t{}\t{}",
-                                        app_id,
-                                        timezone.to_lowercase(),
-                                        secret_utf8
-                                    );
-                                    self.secrets.insert(timezone, secret_utf8);
-                                });
-                        });
-
-                        Ok(())
-                    } else {
-                        Err(Error::AppID)
-                    }
-                } else {
-                    Err(Error::AppID)
-                }
-            } else {
-                Err(Error::AppID)
-            }
-        } else {
-            Err(Error::AppID)
+        let play_url = self.play_url.trim_end_matches('/');
+        let login_page = self
+            .request(Method::GET, format!("{play_url}/login"))
+            .send()
+            .await?;
+        let contents = login_page.text().await?;
+
+        let bundle_path = self
+            .bundle_regex
+            .captures(contents.as_str())
+            .and_then(|captures| captures.get(1))
+            .map(|m| m.as_str().to_string())
+            .ok_or_else(|| scrape_error("bundle", BUNDLE_REGEX_ENV, &contents))?;
+
+        debug!("fetching bundle {}", bundle_path);
+        let bundle_url = format!("{play_url}{bundle_path}");
+        let bundle_contents = self
+            .request(Method::GET, bundle_url)
+            .send()
+            .await?
+            .text()
+            .await?;
+
+        let app_id = self
+            .app_id_regex
+            .captures(bundle_contents.as_str())
+            .and_then(|captures| captures.name("app_id"))
+            .map(|m| m.as_str().to_string())
+            .ok_or_else(|| scrape_error("app_id", APP_REGEX_ENV, &bundle_contents))?;
+
+        self.set_app_id(app_id.clone());
+
+        let seeds = self
+            .seed_regex
+            .captures_iter(bundle_contents.as_str())
+            .collect::<Vec<_>>();
+
+        if seeds.is_empty() {
+            return Err(scrape_error("seeds", SEED_REGEX_ENV, &bundle_contents));
         }
+
+        seeds.into_iter().for_each(|s| {
+            let seed = s.name("seed").map_or("", |m| m.as_str()).to_string();
+            let mut timezone = s.name("timezone").map_or("", |m| m.as_str()).to_string();
+            crate::client::capitalize(timezone.as_mut_str());
+
+            let info_regex = format!(info_regex!(), regex::escape(&timezone));
+            let Ok(info_regex) = regex::Regex::new(info_regex.as_str()) else {
+                debug!("skipping seed for {}, invalid timezone", timezone);
+                return;
+            };
+
+            info_regex
+                .captures_iter(bundle_contents.as_str())
+                .for_each(|c| {
+                    let timezone = c.name("timezone").map_or("", |m| m.as_str()).to_string();
+                    let info = c.name("info").map_or("", |m| m.as_str()).to_string();
+                    let extras = c.name("extras").map_or("", |m| m.as_str()).to_string();
+
+                    let chars = format!("{seed}{info}{extras}");
+
+                    let Some(secret_utf8) = decode_secret(&chars) else {
+                        debug!("skipping secret for {}, it could not be decoded", timezone);
+                        return;
+                    };
+
+                    debug!(
+                        "{}\t{}\t{}",
+                        Redacted(&app_id),
+                        timezone.to_lowercase(),
+                        Redacted(&secret_utf8)
+                    );
+                    self.secrets.insert(timezone, secret_utf8);
+                });
+        });
+
+        Ok(())
     }
 
     // Check the retrieved secrets to see which one works.
     pub async fn test_secrets(&mut self) -> Result<()> {
         let secrets = self.secrets.clone();
-        debug!("testing secrets: {secrets:?}");
+        debug!("testing secrets: {:?}", Redacted(&secrets));
 
-        for (timezone, secret) in secrets.iter() {
-            let response = self.track_url(64868955, Some(secret)).await;
+        if secrets.is_empty() {
+            return Err(Error::ActiveSecret);
+        }
+
+        // Probe every secret at once, the first one to succeed wins and the
+        // remaining requests are dropped.
+        let client = &*self;
+        let probes = secrets.iter().map(|(timezone, secret)| {
+            Box::pin(async move {
+                client
+                    .track_url(64868955, None, Some(secret))
+                    .await
+                    .map(|_| (timezone, secret))
+            })
+        });
 
-            if response.is_ok() {
-                debug!("found good secret: {}\t{}", timezone, secret);
+        let winner = select_ok(probes).await.map(|(winner, _)| winner);
+
+        match winner {
+            Ok((timezone, secret)) => {
+                debug!("found good secret: {}\t{}", timezone, Redacted(secret));
                 let secret_string = secret.to_string();
 
                 self.set_active_secret(secret_string);
 
-                return Ok(());
+                Ok(())
             }
+            Err(_) => Err(Error::ActiveSecret),
         }
+    }
 
-        Err(Error::ActiveSecret)
+    /// Discard the current app_id and secret, then fetch and test new ones.
+    pub async fn reauthorize(&self) -> Result<()> {
+        let mut client = self.clone();
+        client.secrets.clear();
+
+        client.refresh().await?;
+        client.test_secrets().await
     }
 }
 
@@ -827,4 +1527,60 @@ pub struct SuccessfulResponse {
 pub enum OutputFormat {
     Json,
     Tsv,
+    /// One JSON object per line, supported by the search commands.
+    Jsonl,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "abcdef0123456789abcdef0123456789";
+
+    #[test]
+    fn signs_track_urls() {
+        assert_eq!(
+            track_url_signature("27", 19512574, "1700000000", SECRET),
+            "a97613565c56cde0a0710a0bd75ffad0"
+        );
+    }
+
+    #[test]
+    fn signature_covers_the_format() {
+        assert_eq!(
+            track_url_signature("6", 19512574, "1700000000", SECRET),
+            "bfb90618415b5d2cfc997024f4469256"
+        );
+    }
+
+    fn proxy(no_proxy: Option<&str>) -> ProxySettings {
+        ProxySettings {
+            url: "http://proxy:3128/".to_string(),
+            username: None,
+            password: None,
+            no_proxy: no_proxy.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn no_proxy_matches_hosts_and_their_subdomains() {
+        let settings = proxy(Some("localhost, .qobuz.com"));
+
+        assert!(settings.bypasses("http://localhost:8080/stream"));
+        assert!(settings.bypasses("https://qobuz.com/"));
+        assert!(settings.bypasses("https://streaming.QOBUZ.com/file?id=1"));
+        assert!(!settings.bypasses("https://notqobuz.com/"));
+        assert!(!settings.bypasses("https://akamaized.net/file"));
+    }
+
+    #[test]
+    fn no_proxy_wildcard_matches_every_host() {
+        assert!(proxy(Some("*")).bypasses("https://streaming.qobuz.com/"));
+    }
+
+    #[test]
+    fn without_no_proxy_everything_goes_through_the_proxy() {
+        assert!(!proxy(None).bypasses("https://streaming.qobuz.com/"));
+        assert!(!proxy(Some("")).bypasses("https://streaming.qobuz.com/"));
+    }
 }
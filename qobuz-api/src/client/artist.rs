@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use crate::client::{album::Albums, Image};
+use crate::client::{album::Albums, track::Tracks, Image};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ArtistSearchResults {
@@ -8,12 +8,20 @@ pub struct ArtistSearchResults {
     pub artists: Artists,
 }
 
+/// Qobuz leaves out `artists`, or parts of it, when there are none to list.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ArtistsResponse {
     pub artists: Artists,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArtistTracksResponse {
+    pub tracks: Tracks,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Artists {
     pub limit: i64,
     pub offset: i64,
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+/// A page of genres, either the top level ones or the children of `parent`.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenreList {
+    pub genres: Genres,
+    pub parent: Option<Genre>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Genres {
+    pub limit: i64,
+    pub offset: i64,
+    pub total: i64,
+    pub items: Vec<Genre>,
+}
+
+/// A genre. `path` holds the ids from the top level genre down to this one.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Genre {
+    pub id: i64,
+    pub name: String,
+    pub slug: Option<String>,
+    pub color: Option<String>,
+    #[serde(default)]
+    pub path: Vec<i64>,
+}
+
+impl Genre {
+    /// Id of the genre this one belongs to, if it is not a top level genre.
+    pub fn parent_id(&self) -> Option<i64> {
+        match self.path.as_slice() {
+            [.., parent, id] if *id == self.id => Some(*parent),
+            _ => None,
+        }
+    }
+}
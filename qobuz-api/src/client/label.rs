@@ -0,0 +1,14 @@
+use crate::client::album::Albums;
+use serde::{Deserialize, Serialize};
+
+/// A record label and its releases.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Label {
+    pub id: i64,
+    pub name: String,
+    pub slug: Option<String>,
+    pub albums_count: Option<i64>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub albums: Albums,
+}
@@ -1,11 +1,15 @@
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use snafu::prelude::*;
+use std::{fmt::Display, str::FromStr};
 
 pub mod album;
 pub mod album_suggestion;
 pub mod api;
 pub mod artist;
 pub mod favorites;
+pub mod genre;
+pub mod label;
 pub mod playlist;
 pub mod release;
 pub mod search_results;
@@ -18,6 +22,15 @@ pub struct ApiConfig {
     pub user_token: Option<String>,
     pub app_id: Option<String>,
     pub active_secret: Option<String>,
+    pub secrets_updated_at: Option<i64>,
+    pub web_password: Option<String>,
+    pub replaygain_mode: Option<String>,
+    pub profile: Option<String>,
+    pub max_quality: Option<String>,
+    pub audio_sink: Option<String>,
+    pub audio_device: Option<String>,
+    pub bit_perfect: Option<bool>,
+    pub user_agent: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -47,6 +60,114 @@ pub struct TrackURL {
     pub bit_depth: i32,
 }
 
+impl TrackURL {
+    /// The quality Qobuz actually delivered, which may be lower than requested.
+    pub fn quality(&self) -> Option<AudioQuality> {
+        AudioQuality::from_format_id(self.format_id)
+    }
+
+    /// Unix timestamp after which the url stops working, taken from its `etsp` parameter.
+    pub fn expires_at(&self) -> Option<i64> {
+        let (_, query) = self.url.split_once('?')?;
+
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("etsp="))
+            .and_then(|timestamp| timestamp.parse().ok())
+    }
+}
+
+/// Streaming formats offered by Qobuz, ordered from lowest to highest.
+#[derive(
+    Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum,
+)]
+pub enum AudioQuality {
+    /// MP3 320kbps
+    #[value(name = "mp3")]
+    Mp3,
+    /// 16-bit / 44.1kHz FLAC
+    #[value(name = "cd")]
+    CD,
+    /// 24-bit FLAC, up to 96kHz
+    #[value(name = "hires96")]
+    HIFI96,
+    /// 24-bit FLAC, up to 192kHz
+    #[default]
+    #[value(name = "hires192")]
+    HIFI192,
+}
+
+#[derive(Snafu, Debug)]
+#[snafu(display("unknown quality '{value}', expected one of mp3, cd, hires96 or hires192"))]
+pub struct UnknownQualityError {
+    value: String,
+}
+
+impl AudioQuality {
+    /// The `format_id` Qobuz expects for this quality.
+    pub fn format_id(&self) -> i32 {
+        match self {
+            AudioQuality::Mp3 => 5,
+            AudioQuality::CD => 6,
+            AudioQuality::HIFI96 => 7,
+            AudioQuality::HIFI192 => 27,
+        }
+    }
+
+    pub fn from_format_id(format_id: i32) -> Option<AudioQuality> {
+        match format_id {
+            5 => Some(AudioQuality::Mp3),
+            6 => Some(AudioQuality::CD),
+            7 => Some(AudioQuality::HIFI96),
+            27 => Some(AudioQuality::HIFI192),
+            _ => None,
+        }
+    }
+
+    /// The name used on the command line and in the config.
+    pub fn name(&self) -> &'static str {
+        match self {
+            AudioQuality::Mp3 => "mp3",
+            AudioQuality::CD => "cd",
+            AudioQuality::HIFI96 => "hires96",
+            AudioQuality::HIFI192 => "hires192",
+        }
+    }
+
+    /// The next quality down, if there is one.
+    pub fn lower(&self) -> Option<AudioQuality> {
+        match self {
+            AudioQuality::Mp3 => None,
+            AudioQuality::CD => Some(AudioQuality::Mp3),
+            AudioQuality::HIFI96 => Some(AudioQuality::CD),
+            AudioQuality::HIFI192 => Some(AudioQuality::HIFI96),
+        }
+    }
+}
+
+/// Prints the `format_id`, which is what goes into the `track_url` signature.
+impl Display for AudioQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}", self.format_id()))
+    }
+}
+
+impl FromStr for AudioQuality {
+    type Err = UnknownQualityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mp3" => Ok(AudioQuality::Mp3),
+            "cd" => Ok(AudioQuality::CD),
+            "hires96" => Ok(AudioQuality::HIFI96),
+            "hires192" => Ok(AudioQuality::HIFI192),
+            _ => Err(UnknownQualityError {
+                value: s.to_string(),
+            }),
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct User {
     pub id: i64,
@@ -57,8 +57,8 @@ pub struct Genre {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Label {
-    id: i64,
-    name: String,
+    pub id: i64,
+    pub name: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -99,4 +99,8 @@ pub struct AudioInfo {
     pub maximum_bit_depth: u32,
     pub maximum_channel_count: f32,
     pub maximum_sampling_rate: f32,
+    pub replaygain_track_gain: Option<f64>,
+    pub replaygain_track_peak: Option<f64>,
+    pub replaygain_album_gain: Option<f64>,
+    pub replaygain_album_peak: Option<f64>,
 }
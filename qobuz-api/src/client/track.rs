@@ -9,6 +9,12 @@ pub struct Tracks {
     pub items: Vec<Track>,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackSearchResults {
+    pub query: String,
+    pub tracks: Tracks,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Track {
     pub album: Option<Album>,
@@ -47,6 +53,8 @@ pub struct Track {
 pub struct AudioInfo {
     pub replaygain_track_gain: Option<f64>,
     pub replaygain_track_peak: Option<f64>,
+    pub replaygain_album_gain: Option<f64>,
+    pub replaygain_album_peak: Option<f64>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
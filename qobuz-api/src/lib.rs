@@ -6,6 +6,7 @@ use snafu::prelude::*;
 extern crate tracing;
 
 pub mod client;
+pub mod redact;
 
 pub const TEST_TEMP_PATH: &str = "/tmp/hifirs_test";
 
@@ -23,20 +24,40 @@ pub enum Error {
     ActiveSecret,
     #[snafu(display("Failed to get an app id from Qobuz."))]
     AppID,
+    #[snafu(display(
+        "Failed to find the {name} in the Qobuz web player, it may have changed. Set {env} to override the pattern. The page started with: {snippet}"
+    ))]
+    Scrape {
+        name: String,
+        env: String,
+        snippet: String,
+    },
     #[snafu(display("Failed to login."))]
     Login,
     #[snafu(display("Authorization missing."))]
     Authorization,
+    #[snafu(display("Authorization rejected by Qobuz."))]
+    Unauthorized,
+    #[snafu(display("Request to Qobuz timed out."))]
+    Timeout,
     #[snafu(display("Failed to create client"))]
     Create,
     #[snafu(display("{message}"))]
     Api { message: String },
     #[snafu(display("Failed to deserialize json: {message}"))]
     DeserializeJSON { message: String },
+    #[snafu(display("Failed to write download: {message}"))]
+    Write { message: String },
+    #[snafu(display("Download stopped after {written} of {expected} bytes."))]
+    IncompleteDownload { written: u64, expected: u64 },
 }
 
 impl From<reqwest::Error> for Error {
     fn from(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            return Error::Timeout;
+        }
+
         let status = error.status();
 
         match status {
@@ -0,0 +1,61 @@
+//! Keeps tokens, secrets and passwords out of the logs.
+
+use std::{
+    fmt::{Debug, Display, Formatter},
+    sync::OnceLock,
+};
+
+/// Set to `1` to log sensitive values in full while debugging.
+pub const LOG_SECRETS_ENV: &str = "HIFIRS_LOG_SECRETS";
+
+/// Request parameters whose values are never logged.
+const SENSITIVE_PARAMS: &[&str] = &["email", "password", "app_id", "request_sig"];
+
+fn log_secrets() -> bool {
+    static LOG_SECRETS: OnceLock<bool> = OnceLock::new();
+
+    *LOG_SECRETS.get_or_init(|| std::env::var(LOG_SECRETS_ENV).is_ok_and(|value| value == "1"))
+}
+
+/// Wraps a sensitive value so that it is printed as `***`,
+/// unless `HIFIRS_LOG_SECRETS=1` is set.
+#[derive(Clone)]
+pub struct Redacted<T>(pub T);
+
+impl<T: Display> Display for Redacted<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if log_secrets() {
+            self.0.fmt(f)
+        } else {
+            f.write_str("***")
+        }
+    }
+}
+
+impl<T: Debug> Debug for Redacted<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if log_secrets() {
+            self.0.fmt(f)
+        } else {
+            f.write_str("***")
+        }
+    }
+}
+
+/// Request parameters prepared for logging, with sensitive values redacted.
+pub fn redact_params<'a>(
+    params: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Vec<(&'a str, String)> {
+    params
+        .into_iter()
+        .map(|(key, value)| {
+            let value = if SENSITIVE_PARAMS.contains(&key) {
+                Redacted(value).to_string()
+            } else {
+                value.to_string()
+            };
+
+            (key, value)
+        })
+        .collect()
+}
@@ -0,0 +1,352 @@
+//! Tests against a mock Qobuz server, serving the canned responses in `fixtures`
+//! so that changes to the models are caught before they reach a release.
+
+use axum::{
+    extract::Query,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use hifirs_qobuz_api::{
+    client::{
+        api::{self, Client},
+        AudioQuality,
+    },
+    Error,
+};
+use std::collections::HashMap;
+
+const APP_ID: &str = "123456789";
+const SECRET: &str = "abcdef0123456789abcdef0123456789";
+const TOKEN: &str = "user-token";
+const USERNAME: &str = "user@example.com";
+const PASSWORD: &str = "5ebe2294ecd0e0f08eab7690d2a6ee69";
+
+const ALBUM_ID: &str = "0886443842058";
+const TRACK_ID: i32 = 19512574;
+/// A track Qobuz fails to serve in the highest quality.
+const FAILING_TRACK_ID: i32 = 19512575;
+
+type Params = Query<HashMap<String, String>>;
+
+fn json(body: &'static str) -> Response {
+    ([("content-type", "application/json")], body).into_response()
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    let body = format!(
+        r#"{{"status":"error","code":{},"message":"{}"}}"#,
+        status.as_u16(),
+        message
+    );
+
+    (status, [("content-type", "application/json")], body).into_response()
+}
+
+/// Qobuz answers calls without the app_id or with an unknown token as unauthorized.
+fn check_headers(headers: &HeaderMap) -> Result<(), Response> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if header("x-app-id") != Some(APP_ID) {
+        return Err(error(
+            StatusCode::BAD_REQUEST,
+            "Invalid or missing app_id parameter",
+        ));
+    }
+
+    match header("x-user-auth-token") {
+        Some(TOKEN) | None => Ok(()),
+        Some(_) => Err(error(
+            StatusCode::UNAUTHORIZED,
+            "User authentication is required.",
+        )),
+    }
+}
+
+async fn login(headers: HeaderMap, Query(params): Params) -> Response {
+    if params.get("app_id").map(String::as_str) != Some(APP_ID) {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "Invalid or missing app_id parameter",
+        );
+    }
+
+    let email = params.get("email").map(String::as_str);
+    let password = params.get("password").map(String::as_str);
+
+    if headers.contains_key("x-app-id") && email == Some(USERNAME) && password == Some(PASSWORD) {
+        json(r#"{"user_auth_token":"user-token","user":{"id":1}}"#)
+    } else {
+        error(
+            StatusCode::UNAUTHORIZED,
+            "Invalid username/email and password combination.",
+        )
+    }
+}
+
+async fn album(headers: HeaderMap, Query(params): Params) -> Response {
+    if let Err(response) = check_headers(&headers) {
+        return response;
+    }
+
+    match params.get("album_id").map(String::as_str) {
+        Some(ALBUM_ID) => json(include_str!("fixtures/album.json")),
+        Some("private") => error(StatusCode::UNAUTHORIZED, "User authentication is required."),
+        Some("maintenance") => "<html><body>Down for maintenance</body></html>".into_response(),
+        Some("failing") => error(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"),
+        _ => error(StatusCode::NOT_FOUND, "No result matching given argument"),
+    }
+}
+
+async fn track(headers: HeaderMap, Query(params): Params) -> Response {
+    if let Err(response) = check_headers(&headers) {
+        return response;
+    }
+
+    if params.get("track_id") == Some(&TRACK_ID.to_string()) {
+        json(include_str!("fixtures/track.json"))
+    } else {
+        error(StatusCode::NOT_FOUND, "No result matching given argument")
+    }
+}
+
+async fn search_albums(headers: HeaderMap, Query(params): Params) -> Response {
+    if let Err(response) = check_headers(&headers) {
+        return response;
+    }
+
+    match params.get("query") {
+        Some(query) if !query.is_empty() => json(include_str!("fixtures/album_search.json")),
+        _ => error(
+            StatusCode::BAD_REQUEST,
+            "Invalid or missing query parameter",
+        ),
+    }
+}
+
+/// Checks the signature the way Qobuz does, from the parameters of the request.
+async fn track_url(headers: HeaderMap, Query(params): Params) -> Response {
+    if let Err(response) = check_headers(&headers) {
+        return response;
+    }
+
+    let param = |name: &str| params.get(name).map(String::as_str).unwrap_or_default();
+
+    let expected = format!(
+        "{:x}",
+        md5::compute(format!(
+            "trackgetFileUrlformat_id{}intent{}track_id{}{}{}",
+            param("format_id"),
+            param("intent"),
+            param("track_id"),
+            param("request_ts"),
+            SECRET
+        ))
+    );
+
+    if param("request_sig") != expected {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "Invalid Request Signature parameter (request_sig)",
+        );
+    }
+
+    if param("track_id") == FAILING_TRACK_ID.to_string()
+        && param("format_id") == AudioQuality::HIFI192.to_string()
+    {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, "Internal error");
+    }
+
+    if param("format_id") != AudioQuality::HIFI96.to_string() {
+        return error(StatusCode::NOT_FOUND, "Format not available");
+    }
+
+    json(include_str!("fixtures/track_url.json"))
+}
+
+/// Start a mock server on a free port, returning its url.
+async fn mock_server() -> String {
+    let app = Router::new()
+        .route("/user/login", get(login))
+        .route("/album/get", get(album))
+        .route("/track/get", get(track))
+        .route("/album/search", get(search_albums))
+        .route("/track/getFileUrl", get(track_url));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    url
+}
+
+async fn client(secret: Option<&str>, token: Option<&str>) -> Client {
+    let mut client = api::new(
+        secret.map(str::to_string),
+        Some(APP_ID.to_string()),
+        token.map(str::to_string),
+    )
+    .await
+    .unwrap();
+
+    client.set_base_url(&mock_server().await);
+
+    client
+}
+
+#[tokio::test]
+async fn gets_albums_with_their_tracks() {
+    let client = client(Some(SECRET), Some(TOKEN)).await;
+
+    let album = client.album(ALBUM_ID).await.unwrap();
+
+    assert_eq!(album.title, "Kind Of Blue");
+    assert_eq!(album.artist.name, "Miles Davis");
+    assert_eq!(album.maximum_sampling_rate, Some(96.0));
+
+    let tracks = album.tracks.unwrap().items;
+    assert_eq!(tracks.len(), 2);
+    assert_eq!(tracks[0].title, "So What");
+    assert_eq!(tracks[1].audio_info.replaygain_track_gain, Some(-6.54));
+    assert_eq!(tracks[1].audio_info.replaygain_album_gain, Some(-7.02));
+}
+
+#[tokio::test]
+async fn gets_tracks_with_their_album() {
+    let client = client(Some(SECRET), Some(TOKEN)).await;
+
+    let track = client.track(TRACK_ID).await.unwrap();
+
+    assert_eq!(track.title, "So What");
+    assert_eq!(track.isrc.as_deref(), Some("USSM15900113"));
+    assert_eq!(track.album.unwrap().id, ALBUM_ID);
+}
+
+#[tokio::test]
+async fn searches_albums() {
+    let client = client(Some(SECRET), Some(TOKEN)).await;
+
+    let results = client
+        .search_albums("kind of blue", None, None)
+        .await
+        .unwrap();
+
+    assert_eq!(results.total(), 1);
+    assert!(!results.has_more());
+    assert_eq!(results.albums.items[0].title, "Kind Of Blue");
+}
+
+#[tokio::test]
+async fn signs_track_url_requests() {
+    let client = client(Some(SECRET), Some(TOKEN)).await;
+
+    let track_url = client
+        .track_url(TRACK_ID, Some(AudioQuality::HIFI96), None)
+        .await
+        .unwrap();
+
+    assert_eq!(track_url.track_id, TRACK_ID);
+    assert_eq!(track_url.quality(), Some(AudioQuality::HIFI96));
+    assert_eq!(track_url.expires_at(), Some(1700000000));
+}
+
+#[tokio::test]
+async fn track_url_signed_with_another_secret_is_rejected() {
+    let client = client(Some(SECRET), Some(TOKEN)).await;
+
+    let result = client
+        .track_url(
+            TRACK_ID,
+            Some(AudioQuality::HIFI96),
+            Some("0123456789abcdef0123456789abcdef"),
+        )
+        .await;
+
+    assert!(matches!(result, Err(Error::Api { .. })), "{result:?}");
+}
+
+#[tokio::test]
+async fn track_url_without_a_secret_is_not_requested() {
+    let client = client(None, Some(TOKEN)).await;
+
+    let result = client.track_url(TRACK_ID, None, None).await;
+
+    assert!(matches!(result, Err(Error::ActiveSecret)), "{result:?}");
+}
+
+#[tokio::test]
+async fn falls_back_to_a_quality_qobuz_can_serve() {
+    let client = client(Some(SECRET), Some(TOKEN)).await;
+
+    let track_url = client
+        .track_url_with_fallback(TRACK_ID, AudioQuality::HIFI192)
+        .await
+        .unwrap();
+
+    assert_eq!(track_url.quality(), Some(AudioQuality::HIFI96));
+}
+
+#[tokio::test]
+async fn does_not_fall_back_on_other_errors() {
+    let client = client(Some(SECRET), Some(TOKEN)).await;
+
+    let result = client
+        .track_url_with_fallback(FAILING_TRACK_ID, AudioQuality::HIFI192)
+        .await;
+
+    assert!(
+        matches!(&result, Err(Error::Api { message }) if message.starts_with("500")),
+        "{result:?}"
+    );
+}
+
+#[tokio::test]
+async fn unauthorized_responses_are_reported() {
+    let client = client(Some(SECRET), Some(TOKEN)).await;
+
+    let result = client.album("private").await;
+
+    assert!(matches!(result, Err(Error::Unauthorized)), "{result:?}");
+}
+
+#[tokio::test]
+async fn error_statuses_are_reported() {
+    let client = client(Some(SECRET), Some(TOKEN)).await;
+
+    let not_found = client.album("missing").await;
+    let failing = client.album("failing").await;
+
+    assert!(
+        matches!(&not_found, Err(Error::Api { message }) if message.starts_with("404")),
+        "{not_found:?}"
+    );
+    assert!(
+        matches!(&failing, Err(Error::Api { message }) if message.starts_with("500")),
+        "{failing:?}"
+    );
+}
+
+#[tokio::test]
+async fn unexpected_bodies_are_reported() {
+    let client = client(Some(SECRET), Some(TOKEN)).await;
+
+    let result = client.album("maintenance").await;
+
+    assert!(
+        matches!(result, Err(Error::DeserializeJSON { .. })),
+        "{result:?}"
+    );
+}
+
+#[tokio::test]
+async fn logs_in_again_when_the_token_is_rejected() {
+    let mut client = client(Some(SECRET), Some("expired-token")).await;
+    client.set_credentials(USERNAME.to_string(), PASSWORD.to_string());
+
+    let track = client.track(TRACK_ID).await.unwrap();
+
+    assert_eq!(track.title, "So What");
+    assert_eq!(client.get_token().as_deref(), Some(TOKEN));
+}